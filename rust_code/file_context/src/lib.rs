@@ -1,6 +1,10 @@
 use std::mem;
 
-#[derive(Debug, PartialEq)]
+mod source_map;
+
+pub use source_map::{SourceLocation, SourceMap, SourceMapError};
+
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct FileDataLocation {
     pub from: usize,
     pub size: usize,
@@ -12,7 +16,7 @@ impl FileDataLocation {
     }
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, PartialEq, Clone, Copy)]
 pub struct FileSpan {
     pub line: usize,
     pub symbol: usize,
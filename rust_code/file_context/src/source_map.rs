@@ -0,0 +1,186 @@
+use std::{error, fmt};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct SourceLocation {
+    pub file: String,
+    /// Zero based, the same as `FileSpan::line`.
+    pub line: usize,
+}
+
+impl SourceLocation {
+    pub fn new(file: impl Into<String>, line: usize) -> Self {
+        Self {
+            file: file.into(),
+            line,
+        }
+    }
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file, self.line + 1)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceMapError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for SourceMapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{}: wrong source map entry `{}`",
+            self.line + 1,
+            self.text
+        )
+    }
+}
+
+impl error::Error for SourceMapError {}
+
+/// Maps generated items (ROM addresses or `.vm` lines, zero based) to the
+/// source lines they came from, the closest one first: a ROM address maps to a
+/// `.vm` line and then to the `.jack` line that produced it.
+///
+/// An entry holds until the next one, so only the items where the
+/// location changes are stored. The text form is one entry per line,
+/// `index file:line file:line` separated by tabs so paths can hold spaces,
+/// lines are one based.
+#[derive(Debug, PartialEq, Default, Clone)]
+pub struct SourceMap {
+    entries: Vec<(usize, Vec<SourceLocation>)>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// `index` must not go down, an empty `locations` marks generated code
+    /// without a source, e.g. the bootstrap.
+    pub fn push(&mut self, index: usize, locations: Vec<SourceLocation>) {
+        match self.entries.last_mut() {
+            Some((_, last)) if *last == locations => (),
+            Some((last_index, last)) if *last_index == index => *last = locations,
+            _ => self.entries.push((index, locations)),
+        }
+    }
+
+    pub fn lookup(&self, index: usize) -> &[SourceLocation] {
+        let idx = self.entries.partition_point(|(i, _)| *i <= index);
+        match idx.checked_sub(1) {
+            Some(idx) => &self.entries[idx].1,
+            None => &[],
+        }
+    }
+
+    pub fn entries(&self) -> &[(usize, Vec<SourceLocation>)] {
+        &self.entries
+    }
+
+    pub fn write(&self, out: &mut Vec<u8>) {
+        for (index, locations) in &self.entries {
+            out.extend(index.to_string().as_bytes());
+            for location in locations {
+                out.extend(format!("\t{}", location).as_bytes());
+            }
+            out.push(b'\n');
+        }
+    }
+
+    pub fn parse(src: &str) -> Result<Self, SourceMapError> {
+        let mut map = Self::new();
+
+        for (line, text) in src.lines().enumerate() {
+            if text.trim().is_empty() {
+                continue;
+            }
+
+            let error = || SourceMapError {
+                line,
+                text: text.to_string(),
+            };
+
+            let mut parts = text.trim_end().split('\t');
+            let index: usize = parts.next().unwrap().parse().map_err(|_| error())?;
+            if map.entries.last().is_some_and(|(last, _)| *last > index) {
+                return Err(error());
+            }
+
+            let locations = parts
+                .map(|part| {
+                    let (file, line) = part.rsplit_once(':')?;
+                    let line = line.parse::<usize>().ok()?.checked_sub(1)?;
+                    Some(SourceLocation::new(file, line))
+                })
+                .collect::<Option<Vec<_>>>()
+                .ok_or_else(error)?;
+
+            map.entries.push((index, locations));
+        }
+
+        Ok(map)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+
+    use super::*;
+
+    #[test]
+    fn source_map_test() {
+        let mut map = SourceMap::new();
+        map.push(0, vec![]);
+        map.push(4, vec![SourceLocation::new("Main.vm", 2)]);
+        map.push(9, vec![SourceLocation::new("Main.vm", 2)]);
+        map.push(12, vec![SourceLocation::new("Main.vm", 3)]);
+        map.push(
+            12,
+            vec![
+                SourceLocation::new("Main.vm", 4),
+                SourceLocation::new("Main.jack", 10),
+            ],
+        );
+
+        assert_eq!(map.lookup(3), &[]);
+        assert_eq!(map.lookup(11), &[SourceLocation::new("Main.vm", 2)]);
+        assert_eq!(map.lookup(100)[1].to_string(), "Main.jack:11");
+
+        let mut out = Vec::new();
+        map.write(&mut out);
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "0\n4\tMain.vm:3\n12\tMain.vm:5\tMain.jack:11\n"
+        );
+        assert_eq!(SourceMap::parse(from_utf8(&out).unwrap()).unwrap(), map);
+
+        let err = SourceMap::parse("4\tMain.vm:3\n2\tMain.vm:1").unwrap_err();
+        assert_eq!(err.to_string(), "2: wrong source map entry `2\tMain.vm:1`");
+        assert!(SourceMap::parse("4\tMain.vm").is_err());
+    }
+
+    #[test]
+    fn spaced_path_test() {
+        let mut map = SourceMap::new();
+        map.push(
+            3,
+            vec![
+                SourceLocation::new("my games/Main.vm", 1),
+                SourceLocation::new("my games/Main 2.jack", 7),
+            ],
+        );
+
+        let mut out = Vec::new();
+        map.write(&mut out);
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "3\tmy games/Main.vm:2\tmy games/Main 2.jack:8\n"
+        );
+        assert_eq!(SourceMap::parse(from_utf8(&out).unwrap()).unwrap(), map);
+    }
+}
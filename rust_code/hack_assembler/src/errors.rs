@@ -0,0 +1,55 @@
+use std::{error, fmt, str::from_utf8};
+
+use file_context::FileContext;
+
+#[derive(Debug)]
+pub enum AsmParseError {
+    InvalidLabel(FileContext<Vec<u8>>),
+    InvalidSymbol(FileContext<Vec<u8>>),
+    NumberOutOfRange(FileContext<Vec<u8>>),
+    UnknownDest(FileContext<Vec<u8>>),
+    UnknownExpression(FileContext<Vec<u8>>),
+    UnknownJump(FileContext<Vec<u8>>),
+    DuplicateLabel(FileContext<Vec<u8>>),
+}
+
+impl AsmParseError {
+    pub fn context(&self) -> &FileContext<Vec<u8>> {
+        match self {
+            Self::InvalidLabel(c) => c,
+            Self::InvalidSymbol(c) => c,
+            Self::NumberOutOfRange(c) => c,
+            Self::UnknownDest(c) => c,
+            Self::UnknownExpression(c) => c,
+            Self::UnknownJump(c) => c,
+            Self::DuplicateLabel(c) => c,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::InvalidLabel(_) => "invalid label declaration",
+            Self::InvalidSymbol(_) => "invalid symbol",
+            Self::NumberOutOfRange(_) => "constant should be in range 0..=32767",
+            Self::UnknownDest(_) => "unknown destination",
+            Self::UnknownExpression(_) => "unknown computation",
+            Self::UnknownJump(_) => "unknown jump",
+            Self::DuplicateLabel(_) => "label declared twice",
+        }
+    }
+}
+
+impl fmt::Display for AsmParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context();
+        let text = from_utf8(&context.payload).unwrap_or("<non utf8>");
+
+        if let Some(span) = &context.span {
+            write!(f, "{}:{}: ", span.line + 1, span.symbol + 1)?;
+        }
+
+        write!(f, "{} `{}`", self.description(), text)
+    }
+}
+
+impl error::Error for AsmParseError {}
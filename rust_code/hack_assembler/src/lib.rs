@@ -0,0 +1,162 @@
+use file_context::FileContext;
+use hack_instructions::Instruction;
+
+mod errors;
+mod parser;
+mod symbols;
+
+pub use errors::AsmParseError;
+pub use parser::AsmParser;
+pub use symbols::{SymbolTable, VARIABLES_START};
+
+const HACK_LINE_SIZE: usize = 17;
+
+pub fn parse(src: &[u8]) -> Result<Vec<FileContext<Instruction<'static>>>, AsmParseError> {
+    AsmParser::new(src).collect()
+}
+
+pub fn resolve_symbols(
+    instructions: &[FileContext<Instruction<'_>>],
+) -> Result<SymbolTable, AsmParseError> {
+    let mut symbols = SymbolTable::new();
+    symbols.collect_labels(instructions)?;
+    symbols.allocate_variables(instructions);
+    Ok(symbols)
+}
+
+pub fn write_hack(
+    instructions: &[FileContext<Instruction<'_>>],
+    symbols: &mut SymbolTable,
+    out: &mut Vec<u8>,
+) {
+    let mut buff = [0u8; HACK_LINE_SIZE];
+    let mut instruction_number = 0;
+
+    for context in instructions {
+        let (l, unresolved) = context.payload.write_bytes(
+            &mut buff,
+            &mut symbols.variable_pointer,
+            instruction_number,
+            &mut symbols.static_map,
+        );
+
+        debug_assert!(unresolved.is_none(), "symbols should be resolved before");
+
+        if l != 0 {
+            buff[l] = b'\n';
+            out.extend_from_slice(&buff[..(l + 1)]);
+            instruction_number += 1;
+        }
+    }
+}
+
+pub fn assemble(src: &[u8]) -> Result<Vec<u8>, AsmParseError> {
+    let instructions = parse(src)?;
+    let mut symbols = resolve_symbols(&instructions)?;
+    let mut out = Vec::with_capacity(instructions.len() * HACK_LINE_SIZE);
+    write_hack(&instructions, &mut symbols, &mut out);
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::str::from_utf8;
+
+    use file_context::FileSpan;
+
+    use super::*;
+
+    #[test]
+    fn a_instruction_test() {
+        assert_eq!(assemble(b"@21").unwrap(), b"0000000000010101\n");
+        assert_eq!(assemble(b"@SCREEN").unwrap(), b"0100000000000000\n");
+        assert_eq!(assemble(b"@R13").unwrap(), b"0000000000001101\n");
+    }
+
+    #[test]
+    fn c_instruction_test() {
+        let out = assemble(b"MD=M+1\nDM=M+1\nAMD=D|A\nD;JGT\n0;JMP\nD=A+D").unwrap();
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "1111110111011000\n\
+             1111110111011000\n\
+             1110010101111000\n\
+             1110001100000001\n\
+             1110101010000111\n\
+             1110000010010000\n"
+        );
+    }
+
+    #[test]
+    fn labels_and_variables_test() {
+        let src = b"// counts down
+            @i
+            M=1   // i = 1
+            (LOOP)
+            @LOOP
+            0;JMP
+            @j
+            @i";
+        let out = assemble(src).unwrap();
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "0000000000010000\n\
+             1110111111001000\n\
+             0000000000000010\n\
+             1110101010000111\n\
+             0000000000010001\n\
+             0000000000010000\n"
+        );
+    }
+
+    #[test]
+    fn forward_label_test() {
+        let out = assemble(b"@END\n0;JMP\n(END)\n@END\n0;JMP").unwrap();
+        assert_eq!(&out[..17], b"0000000000000010\n");
+    }
+
+    #[test]
+    fn parse_error_span_test() {
+        let err = assemble(b"@1\n\n   D=D*A\n").unwrap_err();
+
+        assert!(matches!(err, AsmParseError::UnknownExpression(_)));
+        assert_eq!(err.context().span, Some(FileSpan::new(2, 3)));
+        assert_eq!(err.to_string(), "3:4: unknown computation `D=D*A`");
+    }
+
+    #[test]
+    fn duplicate_label_test() {
+        let err = assemble(b"(A1)\n@0\n(A1)").unwrap_err();
+        assert!(matches!(err, AsmParseError::DuplicateLabel(_)));
+        assert_eq!(err.context().span, Some(FileSpan::new(2, 0)));
+    }
+
+    #[test]
+    fn out_of_range_test() {
+        let err = assemble(b"@32768").unwrap_err();
+        assert!(matches!(err, AsmParseError::NumberOutOfRange(_)));
+    }
+
+    #[test]
+    fn symbol_file_test() {
+        let instructions = parse(b"(START)\n@END\n0;JMP\n(END)\n@END\n0;JMP").unwrap();
+        let symbols = resolve_symbols(&instructions).unwrap();
+        let mut out = Vec::new();
+        symbols.write_symbol_file(&mut out);
+        assert_eq!(from_utf8(&out).unwrap(), "START 0\nEND 2\n");
+
+        let restored = SymbolTable::from_symbol_file(&out).unwrap();
+        assert_eq!(restored.labels, symbols.labels);
+
+        let err = SymbolTable::from_symbol_file(b"START 0\nEND -2\n").unwrap_err();
+        assert_eq!(err.context().span, Some(FileSpan::new(1, 0)));
+    }
+
+    #[test]
+    fn max_program_test() {
+        let src = fs::read("./priv/Max.asm").unwrap();
+        let answer = fs::read("./priv/Max.hack").unwrap();
+        assert_eq!(from_utf8(&assemble(&src).unwrap()), from_utf8(&answer));
+    }
+}
@@ -0,0 +1,52 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process;
+
+use hack_assembler::AsmParseError;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let file_path = Path::new(&args[1]);
+    let src = fs::read(file_path)?;
+
+    let f = file_path.with_extension("hack");
+    let write_file_path = if args.len() > 2 {
+        Path::new(&args[2])
+    } else {
+        f.as_path()
+    };
+
+    let with_symbols = env::var("WITH_SYMBOLS").is_ok();
+
+    match assemble(&src, with_symbols) {
+        Ok((out, symbols)) => {
+            if let Some(symbols) = symbols {
+                fs::write(write_file_path.with_extension("sym"), symbols)?;
+            }
+            fs::write(write_file_path, out)
+        }
+        Err(err) => {
+            eprintln!("{}:{}", file_path.display(), err);
+            process::exit(1)
+        }
+    }
+}
+
+fn assemble(src: &[u8], with_symbols: bool) -> Result<(Vec<u8>, Option<Vec<u8>>), AsmParseError> {
+    if !with_symbols {
+        return Ok((hack_assembler::assemble(src)?, None));
+    }
+
+    let instructions = hack_assembler::parse(src)?;
+    let mut symbols = hack_assembler::resolve_symbols(&instructions)?;
+
+    let mut symbol_file = Vec::new();
+    symbols.write_symbol_file(&mut symbol_file);
+
+    let mut out = Vec::new();
+    hack_assembler::write_hack(&instructions, &mut symbols, &mut out);
+    Ok((out, Some(symbol_file)))
+}
@@ -0,0 +1,194 @@
+use file_context::{FileContext, FileDataLocation, FileSpan};
+use hack_instructions::*;
+
+use crate::errors::AsmParseError;
+
+const MAX_A_VALUE: u32 = i16::MAX as u32;
+
+pub struct AsmParser<'a> {
+    src: &'a [u8],
+    cursor: usize,
+    line: usize,
+    instruction_number: usize,
+}
+
+impl<'a> AsmParser<'a> {
+    pub fn new(src: &'a [u8]) -> Self {
+        Self {
+            src,
+            cursor: 0,
+            line: 0,
+            instruction_number: 0,
+        }
+    }
+
+    fn next_line(&mut self) -> Option<(usize, &'a [u8])> {
+        if self.cursor >= self.src.len() {
+            return None;
+        }
+
+        let from = self.cursor;
+        let size = self.src[from..]
+            .iter()
+            .position(|c| *c == b'\n')
+            .unwrap_or(self.src.len() - from);
+
+        self.cursor = from + size + 1;
+        Some((from, &self.src[from..(from + size)]))
+    }
+
+    pub fn next_instruction(
+        &mut self,
+    ) -> Option<Result<FileContext<Instruction<'static>>, AsmParseError>> {
+        while let Some((line_start, raw_line)) = self.next_line() {
+            let line = self.line;
+            self.line += 1;
+
+            let without_comment = match raw_line.windows(2).position(|w| w == b"//") {
+                Some(idx) => &raw_line[..idx],
+                None => raw_line,
+            };
+
+            let symbol = match without_comment
+                .iter()
+                .position(|c| !c.is_ascii_whitespace())
+            {
+                Some(symbol) => symbol,
+                None => continue,
+            };
+
+            let text: Vec<u8> = without_comment[symbol..]
+                .iter()
+                .filter(|c| !c.is_ascii_whitespace())
+                .copied()
+                .collect();
+
+            let location = Some(FileDataLocation::new(line_start + symbol, text.len()));
+            let span = Some(FileSpan::new(line, symbol));
+            let idx = self.instruction_number;
+            self.instruction_number += 1;
+
+            return Some(match parse_instruction(&text) {
+                Ok(instruction) => Ok(FileContext::new(instruction, idx, location, span)),
+                Err(build_error) => Err(build_error(FileContext::new(text, idx, location, span))),
+            });
+        }
+
+        None
+    }
+}
+
+impl Iterator for AsmParser<'_> {
+    type Item = Result<FileContext<Instruction<'static>>, AsmParseError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.next_instruction()
+    }
+}
+
+type ErrorBuilder = fn(FileContext<Vec<u8>>) -> AsmParseError;
+
+fn parse_instruction(text: &[u8]) -> Result<Instruction<'static>, ErrorBuilder> {
+    match text[0] {
+        b'(' => parse_label(text),
+        b'@' => parse_a_instruction(&text[1..]),
+        _ => parse_c_instruction(text),
+    }
+}
+
+fn parse_label(text: &[u8]) -> Result<Instruction<'static>, ErrorBuilder> {
+    match text {
+        [b'(', name @ .., b')'] if is_symbol(name) => Ok(Instruction::new_raw_label(name.to_vec())),
+        _ => Err(AsmParseError::InvalidLabel),
+    }
+}
+
+fn parse_a_instruction(value: &[u8]) -> Result<Instruction<'static>, ErrorBuilder> {
+    if !value.is_empty() && value.iter().all(u8::is_ascii_digit) {
+        let n = value
+            .iter()
+            .try_fold(0u32, |acc, x| {
+                let v = acc * 10 + (x - b'0') as u32;
+                (v <= MAX_A_VALUE).then_some(v)
+            })
+            .ok_or(AsmParseError::NumberOutOfRange as ErrorBuilder)?;
+
+        return Ok(Instruction::new_number(n as i16));
+    }
+
+    if !is_symbol(value) {
+        return Err(AsmParseError::InvalidSymbol);
+    }
+
+    if let Some(c) = AConst::try_from_symbols(value) {
+        Ok(Instruction::A(AInstruction::Const(c)))
+    } else {
+        Ok(Instruction::new_raw_var_label(value.to_vec()))
+    }
+}
+
+fn parse_c_instruction(text: &[u8]) -> Result<Instruction<'static>, ErrorBuilder> {
+    let (dest_part, rest) = match text.iter().position(|c| *c == b'=') {
+        Some(idx) => (&text[..idx], &text[(idx + 1)..]),
+        None => (&text[..0], text),
+    };
+
+    let (expression_part, jump_part) = match rest.iter().position(|c| *c == b';') {
+        Some(idx) => (&rest[..idx], &rest[idx..]),
+        None => (rest, &rest[rest.len()..]),
+    };
+
+    let dest = parse_dest(dest_part).ok_or(AsmParseError::UnknownDest as ErrorBuilder)?;
+    let expression = parse_expression(expression_part)
+        .ok_or(AsmParseError::UnknownExpression as ErrorBuilder)?;
+    let jump = CInstructionJump::try_from_symbols(jump_part)
+        .ok_or(AsmParseError::UnknownJump as ErrorBuilder)?;
+
+    Ok(Instruction::C(CInstruction {
+        dest,
+        expression,
+        jump,
+    }))
+}
+
+fn parse_dest(dest: &[u8]) -> Option<CInstructionDest> {
+    if dest.is_empty() {
+        return Some(CInstructionDest::NODEST);
+    }
+
+    // Both `MD=` and `DM=` are in use, so registers are normalized to the `A`, `D`, `M` order.
+    let mut registers = dest.to_vec();
+    registers.sort_unstable();
+    registers.dedup();
+
+    if registers.len() != dest.len() {
+        return None;
+    }
+
+    registers.push(b'=');
+    CInstructionDest::try_from_symbols(&registers)
+}
+
+fn parse_expression(expression: &[u8]) -> Option<CInstructionExpression> {
+    if let Some(e) = CInstructionExpression::try_from_symbols(expression) {
+        return Some(e);
+    }
+
+    // Commutative operations are accepted with swapped operands, e.g. `A+D` or `M&D`.
+    match expression {
+        [l, op, r] if matches!(op, b'+' | b'&' | b'|') => {
+            CInstructionExpression::try_from_symbols(&[*r, *op, *l])
+        }
+        _ => None,
+    }
+}
+
+fn is_symbol(name: &[u8]) -> bool {
+    match name.first() {
+        None => false,
+        Some(c) if c.is_ascii_digit() => false,
+        _ => name
+            .iter()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, b'_' | b'.' | b'$' | b':')),
+    }
+}
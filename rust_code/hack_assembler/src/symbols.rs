@@ -0,0 +1,133 @@
+use std::collections::HashMap;
+
+use file_context::{FileContext, FileDataLocation, FileSpan};
+use hack_instructions::{HelperInstruction, Instruction};
+
+use crate::errors::AsmParseError;
+
+pub const VARIABLES_START: i16 = 16;
+
+#[derive(Debug)]
+pub struct SymbolTable {
+    pub static_map: HashMap<Vec<u8>, String>,
+    pub labels: Vec<(Vec<u8>, i16)>,
+    pub variable_pointer: i16,
+}
+
+impl SymbolTable {
+    pub fn new() -> Self {
+        Self {
+            static_map: HashMap::new(),
+            labels: Vec::new(),
+            variable_pointer: VARIABLES_START,
+        }
+    }
+
+    /// First pass: binds every `(LABEL)` to the ROM address of the next real instruction.
+    pub fn collect_labels(
+        &mut self,
+        instructions: &[FileContext<Instruction<'_>>],
+    ) -> Result<(), AsmParseError> {
+        let mut rom_address: i16 = 0;
+
+        for context in instructions {
+            match &context.payload {
+                Instruction::Helper(HelperInstruction::RawLabel(name)) => {
+                    if self.static_map.contains_key(name) {
+                        return Err(AsmParseError::DuplicateLabel(FileContext::new(
+                            name.clone(),
+                            context.idx,
+                            context.location,
+                            context.span,
+                        )));
+                    }
+
+                    self.static_map
+                        .insert(name.clone(), format!("{:016b}", rom_address));
+                    self.labels.push((name.clone(), rom_address));
+                }
+                Instruction::Helper(HelperInstruction::Label(_))
+                | Instruction::Helper(HelperInstruction::Comment(_)) => (),
+                _ => rom_address += 1,
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Second pass: every symbol that is not a label becomes a variable starting from `R16`.
+    pub fn allocate_variables(&mut self, instructions: &[FileContext<Instruction<'_>>]) {
+        for context in instructions {
+            if let Instruction::Helper(HelperInstruction::RawVarLabel(name)) = &context.payload {
+                if !self.static_map.contains_key(name) {
+                    self.static_map
+                        .insert(name.clone(), format!("{:016b}", self.variable_pointer));
+                    self.variable_pointer += 1;
+                }
+            }
+        }
+    }
+}
+
+impl SymbolTable {
+    /// Writes labels as `NAME ADDRESS` lines, the format `from_symbol_file` reads back.
+    pub fn write_symbol_file(&self, out: &mut Vec<u8>) {
+        for (name, address) in &self.labels {
+            out.extend_from_slice(name);
+            out.extend_from_slice(format!(" {}\n", address).as_bytes());
+        }
+    }
+
+    pub fn from_symbol_file(src: &[u8]) -> Result<Self, AsmParseError> {
+        let mut symbols = Self::new();
+        let mut line_start = 0;
+
+        for (line, raw_line) in src.split(|c| *c == b'\n').enumerate() {
+            let from = line_start;
+            line_start += raw_line.len() + 1;
+
+            let mut parts = raw_line
+                .split(u8::is_ascii_whitespace)
+                .filter(|part| !part.is_empty());
+
+            let (name, address) = match (parts.next(), parts.next(), parts.next()) {
+                (None, _, _) => continue,
+                (Some(name), Some(address), None) => (name, parse_address(address)),
+                _ => (raw_line, None),
+            };
+
+            let address = match address {
+                Some(address) if !symbols.static_map.contains_key(name) => address,
+                _ => {
+                    return Err(AsmParseError::InvalidSymbol(FileContext::new(
+                        raw_line.to_vec(),
+                        line,
+                        Some(FileDataLocation::new(from, raw_line.len())),
+                        Some(FileSpan::new(line, 0)),
+                    )))
+                }
+            };
+
+            symbols
+                .static_map
+                .insert(name.to_vec(), format!("{:016b}", address));
+            symbols.labels.push((name.to_vec(), address));
+        }
+
+        Ok(symbols)
+    }
+}
+
+fn parse_address(address: &[u8]) -> Option<i16> {
+    std::str::from_utf8(address)
+        .ok()?
+        .parse::<i16>()
+        .ok()
+        .filter(|a| *a >= 0)
+}
+
+impl Default for SymbolTable {
+    fn default() -> Self {
+        Self::new()
+    }
+}
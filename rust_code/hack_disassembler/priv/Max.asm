@@ -0,0 +1,19 @@
+@0
+D=M
+@1
+D=D-M
+@OUTPUT_FIRST
+D;JGT
+@1
+D=M
+@OUTPUT_D
+0;JMP
+(OUTPUT_FIRST)
+@0
+D=M
+(OUTPUT_D)
+@2
+M=D
+(INFINITE_LOOP)
+@INFINITE_LOOP
+0;JMP
@@ -0,0 +1,40 @@
+use std::{error, fmt, str::from_utf8};
+
+use file_context::FileContext;
+
+#[derive(Debug)]
+pub enum HackDecodeError {
+    InvalidLine(FileContext<Vec<u8>>),
+    UnknownExpression(FileContext<Vec<u8>>),
+}
+
+impl HackDecodeError {
+    pub fn context(&self) -> &FileContext<Vec<u8>> {
+        match self {
+            Self::InvalidLine(c) => c,
+            Self::UnknownExpression(c) => c,
+        }
+    }
+
+    fn description(&self) -> &'static str {
+        match self {
+            Self::InvalidLine(_) => "expected 16 binary digits",
+            Self::UnknownExpression(_) => "unknown computation",
+        }
+    }
+}
+
+impl fmt::Display for HackDecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = self.context();
+        let text = from_utf8(&context.payload).unwrap_or("<non utf8>");
+
+        if let Some(span) = &context.span {
+            write!(f, "{}:{}: ", span.line + 1, span.symbol + 1)?;
+        }
+
+        write!(f, "{} `{}`", self.description(), text)
+    }
+}
+
+impl error::Error for HackDecodeError {}
@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use file_context::{FileContext, FileDataLocation, FileSpan};
+use hack_assembler::SymbolTable;
+use hack_instructions::*;
+use symbolic::SymbolicElem;
+
+mod errors;
+
+pub use errors::HackDecodeError;
+
+const HACK_LINE_SIZE: usize = 16;
+
+type ErrorBuilder = fn(FileContext<Vec<u8>>) -> HackDecodeError;
+
+/// Decodes one 16 bit line, A values are always decoded as plain numbers.
+pub fn decode(line: &[u8]) -> Result<Instruction<'static>, ErrorBuilder> {
+    if line.len() != HACK_LINE_SIZE || !line.iter().all(|c| matches!(c, b'0' | b'1')) {
+        return Err(HackDecodeError::InvalidLine);
+    }
+
+    match line {
+        [b'0', value @ ..] => Ok(Instruction::new_number(
+            value
+                .iter()
+                .fold(0i16, |acc, c| (acc << 1) | (c - b'0') as i16),
+        )),
+        [b'1', b'1', b'1', ..] => {
+            let expression = CInstructionExpression::try_from_bytes(&line[3..10])
+                .ok_or(HackDecodeError::UnknownExpression as ErrorBuilder)?;
+
+            // Every 3 bit combination is a valid destination and jump.
+            let dest = CInstructionDest::try_from_bytes(&line[10..13])
+                .ok_or(HackDecodeError::InvalidLine as ErrorBuilder)?;
+            let jump = CInstructionJump::try_from_bytes(&line[13..16])
+                .ok_or(HackDecodeError::InvalidLine as ErrorBuilder)?;
+
+            Ok(Instruction::C(CInstruction {
+                dest,
+                expression,
+                jump,
+            }))
+        }
+        _ => Err(HackDecodeError::InvalidLine),
+    }
+}
+
+pub fn decode_word(word: i16) -> Option<Instruction<'static>> {
+    decode(format!("{:016b}", word as u16).as_bytes()).ok()
+}
+
+pub fn parse(src: &[u8]) -> Result<Vec<FileContext<Instruction<'static>>>, HackDecodeError> {
+    let mut instructions = Vec::with_capacity(src.len() / (HACK_LINE_SIZE + 1));
+    let mut line_start = 0;
+
+    for (line, raw_line) in src.split(|c| *c == b'\n').enumerate() {
+        let from = line_start;
+        line_start += raw_line.len() + 1;
+
+        let text = raw_line.trim_ascii_end();
+        if text.is_empty() {
+            continue;
+        }
+
+        let location = Some(FileDataLocation::new(from, text.len()));
+        let span = Some(FileSpan::new(line, 0));
+        let idx = instructions.len();
+
+        match decode(text) {
+            Ok(instruction) => {
+                instructions.push(FileContext::new(instruction, idx, location, span))
+            }
+            Err(build_error) => {
+                return Err(build_error(FileContext::new(
+                    text.to_vec(),
+                    idx,
+                    location,
+                    span,
+                )))
+            }
+        }
+    }
+
+    Ok(instructions)
+}
+
+/// Turns `.hack` back into `.asm`. With a symbol table `(LABEL)` lines are restored
+/// and A values that feed a jump are printed as `@LABEL`.
+pub fn disassemble(src: &[u8], symbols: Option<&SymbolTable>) -> Result<Vec<u8>, HackDecodeError> {
+    let instructions = parse(src)?;
+
+    let mut labels: HashMap<i16, Vec<&[u8]>> = HashMap::new();
+    let mut max_label_len = 0;
+    for (name, address) in symbols.iter().flat_map(|s| s.labels.iter()) {
+        labels.entry(*address).or_default().push(name);
+        max_label_len = max_label_len.max(name.len());
+    }
+
+    let mut buff = vec![0u8; HACK_LINE_SIZE + max_label_len + 2];
+    let mut out = Vec::with_capacity(instructions.len() * HACK_LINE_SIZE);
+
+    for (address, context) in instructions.iter().enumerate() {
+        write_labels(&labels, address, &mut buff, &mut out);
+
+        let next = instructions.get(address + 1).map(|c| &c.payload);
+        let reference = match (&context.payload, next) {
+            (Instruction::A(AInstruction::Number(n)), Some(Instruction::C(c)))
+                if !matches!(c.jump, CInstructionJump::NOJMP) =>
+            {
+                labels
+                    .get(n)
+                    .map(|names| Instruction::new_raw_var_label(names[0].to_vec()))
+            }
+            _ => None,
+        };
+
+        let l = reference
+            .as_ref()
+            .unwrap_or(&context.payload)
+            .write_symbols(&mut buff);
+        buff[l] = b'\n';
+        out.extend_from_slice(&buff[..(l + 1)]);
+    }
+
+    write_labels(&labels, instructions.len(), &mut buff, &mut out);
+
+    Ok(out)
+}
+
+fn write_labels(
+    labels: &HashMap<i16, Vec<&[u8]>>,
+    address: usize,
+    buff: &mut [u8],
+    out: &mut Vec<u8>,
+) {
+    for name in labels.get(&(address as i16)).into_iter().flatten() {
+        let l = Instruction::new_raw_label(name.to_vec()).write_symbols(buff);
+        buff[l] = b'\n';
+        out.extend_from_slice(&buff[..(l + 1)]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::str::from_utf8;
+
+    use super::*;
+
+    #[test]
+    fn decode_test() {
+        let out = disassemble(
+            b"0000000000010101\n\
+              1111110111011000\n\
+              1110010101111000\n\
+              1110001100000001\n\
+              1110101010000111\n\
+              1110000010010000\n",
+            None,
+        )
+        .unwrap();
+
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "@21\nDM=M+1\nADM=D|A\nD;JGT\n0;JMP\nD=D+A\n"
+        );
+    }
+
+    #[test]
+    fn decode_word_test() {
+        let mut buff = [0u8; 16];
+        let i = decode_word(0b1110001100000001u16 as i16).unwrap();
+        let l = i.write_symbols(&mut buff);
+        assert_eq!(&buff[..l], b"D;JGT");
+
+        assert!(decode_word(0b1110000001010000u16 as i16).is_none());
+    }
+
+    #[test]
+    fn decode_error_test() {
+        let err = disassemble(b"0000000000010101\r\n\n1110000001111000\n", None).unwrap_err();
+        assert!(matches!(err, HackDecodeError::UnknownExpression(_)));
+        assert_eq!(err.context().span, Some(FileSpan::new(2, 0)));
+
+        let err = disassemble(b"000000000001010", None).unwrap_err();
+        assert!(matches!(err, HackDecodeError::InvalidLine(_)));
+        assert_eq!(
+            err.to_string(),
+            "1:1: expected 16 binary digits `000000000001010`"
+        );
+    }
+
+    #[test]
+    fn max_program_test() {
+        let src = fs::read("./priv/Max.hack").unwrap();
+        let symbols = SymbolTable::from_symbol_file(&fs::read("./priv/Max.sym").unwrap()).unwrap();
+        let answer = fs::read("./priv/Max.asm").unwrap();
+
+        let out = disassemble(&src, Some(&symbols)).unwrap();
+        assert_eq!(from_utf8(&out), from_utf8(&answer));
+        assert_eq!(hack_assembler::assemble(&out).unwrap(), src);
+    }
+}
@@ -0,0 +1,35 @@
+use std::env;
+use std::fs;
+use std::io::{self, Write};
+use std::path::Path;
+use std::process;
+
+use hack_assembler::SymbolTable;
+
+fn main() -> io::Result<()> {
+    let args: Vec<String> = env::args().collect();
+
+    let file_path = Path::new(&args[1]);
+    let src = fs::read(file_path)?;
+
+    let symbols = if args.len() > 2 {
+        let symbols_path = Path::new(&args[2]);
+        match SymbolTable::from_symbol_file(&fs::read(symbols_path)?) {
+            Ok(symbols) => Some(symbols),
+            Err(err) => {
+                eprintln!("{}:{}", symbols_path.display(), err);
+                process::exit(1)
+            }
+        }
+    } else {
+        None
+    };
+
+    match hack_disassembler::disassemble(&src, symbols.as_ref()) {
+        Ok(out) => io::stdout().write_all(&out),
+        Err(err) => {
+            eprintln!("{}:{}", file_path.display(), err);
+            process::exit(1)
+        }
+    }
+}
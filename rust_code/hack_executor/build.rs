@@ -1,12 +1,25 @@
+use std::env;
+
+const OBJS: [&str; 4] = [
+    "hack_memory.o",
+    "hack_alu.o",
+    "hack_pc.o",
+    "hack_executor.o",
+];
+
 fn main() {
-    // Tell cargo to look for shared libraries in the specified directory
-    // println!("cargo:rustc-link-search=../../c_code/hack_executor/build/objs");
-    println!("cargo:rustc-link-search=../c_code/hack_executor/build/objs/");
-
-    // Tell cargo to tell rustc to link the system bzip2
-    // shared library.
-    println!("cargo:rustc-link-lib=hack_memory.o");
-    println!("cargo:rustc-link-lib=hack_alu.o");
-    println!("cargo:rustc-link-lib=hack_pc.o");
-    println!("cargo:rustc-link-lib=hack_executor.o");
+    println!("cargo:rerun-if-env-changed=CARGO_FEATURE_FFI");
+
+    // The C backend is only needed to cross-validate the Rust one.
+    if env::var("CARGO_FEATURE_FFI").is_err() {
+        return;
+    }
+
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap();
+    let objs_dir = format!("{}/../../c_code/hack_executor/build/objs", manifest_dir);
+
+    for obj in OBJS {
+        println!("cargo:rerun-if-changed={}/{}", objs_dir, obj);
+        println!("cargo:rustc-link-arg={}/{}", objs_dir, obj);
+    }
 }
@@ -0,0 +1,79 @@
+use crate::memory::HackMemory;
+
+/// Mirrors `hack_alu_perform`: A-instructions load themselves,
+/// unknown computations produce -1.
+pub fn perform(memory: &HackMemory, a: i16, d: i16, i: i16) -> i16 {
+    if i >= 0 {
+        i
+    } else {
+        compute(memory, a, d, i).unwrap_or(-1)
+    }
+}
+
+/// Evaluates the `a c1..c6` bits of a C-instruction, `None` for codes outside the Hack table.
+pub fn compute(memory: &HackMemory, a: i16, d: i16, i: i16) -> Option<i16> {
+    let op_code = (i >> 6) & 0b1111111;
+    let m = || memory.get(a);
+
+    let result = match op_code {
+        0b0101010 => 0,
+        0b0111111 => 1,
+        0b0111010 => -1,
+        0b0001100 => d,
+        0b0110000 => a,
+        0b1110000 => m(),
+        0b0001101 => !d,
+        0b0110001 => !a,
+        0b1110001 => !m(),
+        0b0001111 => d.wrapping_neg(),
+        0b0110011 => a.wrapping_neg(),
+        0b1110011 => m().wrapping_neg(),
+        0b0011111 => d.wrapping_add(1),
+        0b0110111 => a.wrapping_add(1),
+        0b1110111 => m().wrapping_add(1),
+        0b0001110 => d.wrapping_sub(1),
+        0b0110010 => a.wrapping_sub(1),
+        0b1110010 => m().wrapping_sub(1),
+        0b0000010 => d.wrapping_add(a),
+        0b1000010 => d.wrapping_add(m()),
+        0b0010011 => d.wrapping_sub(a),
+        0b1010011 => d.wrapping_sub(m()),
+        0b0000111 => a.wrapping_sub(d),
+        0b1000111 => m().wrapping_sub(d),
+        0b0000000 => d & a,
+        0b1000000 => d & m(),
+        0b0010101 => d | a,
+        0b1010101 => d | m(),
+        _ => return None,
+    };
+
+    Some(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alu_test() {
+        let mut memory = HackMemory::new();
+        memory.set(3, 7);
+
+        // @21
+        assert_eq!(perform(&memory, 3, 5, 21), 21);
+        // D=D+A
+        assert_eq!(perform(&memory, 3, 5, 0b1110000010010000u16 as i16), 8);
+        // D=M-D
+        assert_eq!(perform(&memory, 3, 5, 0b1111000111010000u16 as i16), 2);
+        // D=!M
+        assert_eq!(perform(&memory, 3, 5, 0b1111110001010000u16 as i16), !7);
+        // D=D+1 overflows like the hardware does
+        assert_eq!(
+            perform(&memory, 3, i16::MAX, 0b1110011111010000u16 as i16),
+            i16::MIN
+        );
+        // unknown computation
+        assert_eq!(perform(&memory, 3, 5, 0b1110000001010000u16 as i16), -1);
+        assert_eq!(compute(&memory, 3, 5, 0b1110000001010000u16 as i16), None);
+    }
+}
@@ -0,0 +1,140 @@
+use std::ffi::OsStr;
+use std::io;
+use std::path::PathBuf;
+
+use hack_executor::MemorySelection;
+
+pub const DEFAULT_MAX_CYCLES: u64 = 10_000_000;
+
+#[derive(Clone, Copy)]
+pub enum DumpFormat {
+    Text,
+    Binary,
+}
+
+pub struct Args {
+    pub file_path: PathBuf,
+    pub selections: Vec<MemorySelection>,
+    pub max_cycles: u64,
+    pub dump_path: Option<PathBuf>,
+    pub dump_format: DumpFormat,
+    pub screen_path: Option<PathBuf>,
+    pub screen_every: Option<u64>,
+    pub keys_path: Option<PathBuf>,
+    pub typed_text: Option<String>,
+    pub debug: bool,
+    pub symbols_path: Option<PathBuf>,
+    pub profile: bool,
+    pub folded_path: Option<PathBuf>,
+    pub backtrace: bool,
+    pub checked: bool,
+}
+
+impl Args {
+    /// `hack_executor <file.hack> [selection] [--print selection[,selection]] [--max-cycles N]
+    /// [--dump path] [--dump-format text|binary] [--screen path.pbm|path.png] [--screen-every N]
+    /// [--keys script] [--type text] [--debug] [--symbols file.sym] [--profile] [--folded path]
+    /// [--backtrace] [--checked]`
+    pub fn parse(args: &[String]) -> io::Result<Self> {
+        let mut file_path = None;
+        let mut selections = Vec::new();
+        let mut max_cycles = DEFAULT_MAX_CYCLES;
+        let mut dump_path = None;
+        let mut dump_format = DumpFormat::Text;
+        let mut screen_path = None;
+        let mut screen_every = None;
+        let mut keys_path = None;
+        let mut typed_text = None;
+        let mut debug = false;
+        let mut symbols_path = None;
+        let mut profile = false;
+        let mut folded_path = None;
+        let mut backtrace = false;
+        let mut checked = false;
+
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--max-cycles" => max_cycles = parse_value(arg, iter.next())?,
+                "--print" => {
+                    for s in value(arg, iter.next())?.split(',') {
+                        selections.push(parse_selection(s)?);
+                    }
+                }
+                "--dump" => dump_path = Some(PathBuf::from(value(arg, iter.next())?)),
+                "--dump-format" => {
+                    dump_format = match value(arg, iter.next())? {
+                        "text" => DumpFormat::Text,
+                        "binary" => DumpFormat::Binary,
+                        _ => return Err(invalid_input(format!("Wrong value for {}", arg))),
+                    }
+                }
+                "--screen" => screen_path = Some(parse_screen_path(arg, iter.next())?),
+                "--screen-every" => {
+                    let n: u64 = parse_value(arg, iter.next())?;
+                    if n == 0 {
+                        return Err(invalid_input(format!("Wrong value for {}", arg)));
+                    }
+                    screen_every = Some(n)
+                }
+                "--keys" => keys_path = Some(PathBuf::from(value(arg, iter.next())?)),
+                "--type" => typed_text = Some(value(arg, iter.next())?.to_string()),
+                "--debug" => debug = true,
+                "--symbols" => symbols_path = Some(PathBuf::from(value(arg, iter.next())?)),
+                "--profile" => profile = true,
+                "--folded" => folded_path = Some(PathBuf::from(value(arg, iter.next())?)),
+                "--backtrace" => backtrace = true,
+                "--checked" => checked = true,
+                _ if file_path.is_none() => file_path = Some(PathBuf::from(arg)),
+                _ => selections.push(parse_selection(arg)?),
+            }
+        }
+
+        Ok(Self {
+            file_path: file_path.ok_or_else(|| invalid_input("Missing .hack file".to_string()))?,
+            selections,
+            max_cycles,
+            dump_path,
+            dump_format,
+            screen_path,
+            screen_every,
+            keys_path,
+            typed_text,
+            debug,
+            symbols_path,
+            profile,
+            folded_path,
+            backtrace,
+            checked,
+        })
+    }
+}
+
+fn value<'a>(arg: &str, value: Option<&'a String>) -> io::Result<&'a str> {
+    value
+        .map(String::as_str)
+        .ok_or_else(|| invalid_input(format!("Missing value for {}", arg)))
+}
+
+fn parse_value<T: std::str::FromStr>(arg: &str, v: Option<&String>) -> io::Result<T> {
+    value(arg, v)?
+        .parse()
+        .map_err(|_| invalid_input(format!("Wrong value for {}", arg)))
+}
+
+/// Snapshots are named after the stem, so the path needs one.
+fn parse_screen_path(arg: &str, v: Option<&String>) -> io::Result<PathBuf> {
+    let path = PathBuf::from(value(arg, v)?);
+    match path.file_stem().and_then(OsStr::to_str) {
+        Some(_) => Ok(path),
+        None => Err(invalid_input(format!("Wrong value for {}", arg))),
+    }
+}
+
+fn parse_selection(s: &str) -> io::Result<MemorySelection> {
+    MemorySelection::parse(s).ok_or_else(|| invalid_input(format!("Unknown address {}", s)))
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -8,13 +8,20 @@ pub struct HackExecutor {
     pub program: *mut ::std::os::raw::c_short,
 }
 
+// The pointer always comes from `init_hack_executor`, this backend is kept for cross-validation.
+#[allow(clippy::not_unsafe_ptr_arg_deref)]
 impl HackExecutor {
     pub fn new(instructions: &mut [i16]) -> *mut Self {
         unsafe { init_hack_executor(instructions.as_mut_ptr()) }
     }
 
+    /// `run_executor` takes a `short`, so long runs are split into chunks.
     pub fn run(s: *mut Self, iteration_count: usize) {
-        unsafe { run_executor(s, iteration_count as i16) }
+        let chunk = i16::MAX as usize;
+        for from in (0..iteration_count).step_by(chunk) {
+            let n = chunk.min(iteration_count - from);
+            unsafe { run_executor(s, n as i16) }
+        }
     }
 
     pub fn result(s: *mut Self) -> i16 {
@@ -0,0 +1,232 @@
+use std::{error, fmt};
+
+use crate::alu;
+use crate::executor::{HackExecutor, HaltReason};
+use crate::locations::HEAP_START;
+use crate::memory::{KBD_ADDRESS, LCL, RAM_SIZE, SCREEN_ADDRESS, SP, THAT};
+
+const ADDRESS_MASK: i16 = 0x7FFF;
+/// The `a` bit of a C-instruction, the computation reads `M` instead of `A`.
+const A_BIT: i16 = 0b1000000000000;
+/// The `M` destination of a C-instruction.
+const DEST_M: i16 = 0b1000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FaultKind {
+    /// The `a c1..c6` bits are outside the Hack table, the C executor computes -1.
+    InvalidComputation(i16),
+    PcOutOfProgram,
+    /// The new `SP` would point past the stack into the heap.
+    StackOverflow(i16),
+    /// `KBD` and the addresses after it can not be written.
+    ReadOnlyWrite(i16),
+    UninitializedRead(i16),
+}
+
+impl fmt::Display for FaultKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::InvalidComputation(instruction) => {
+                write!(f, "invalid computation in `{:016b}`", *instruction as u16)
+            }
+            Self::PcOutOfProgram => write!(f, "PC out of the program"),
+            Self::StackOverflow(sp) => {
+                write!(f, "stack overflow, SP = {} runs into the heap", sp)
+            }
+            Self::ReadOnlyWrite(address) if *address == KBD_ADDRESS => {
+                write!(f, "write to RAM[{}] (KBD)", address)
+            }
+            Self::ReadOnlyWrite(address) => write!(f, "write to unmapped RAM[{}]", address),
+            Self::UninitializedRead(address) => {
+                write!(f, "read of never written RAM[{}]", address)
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fault {
+    pub kind: FaultKind,
+    /// Cycles executed before the faulting instruction.
+    pub cycle: u64,
+    pub pc: i16,
+}
+
+impl fmt::Display for Fault {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at cycle {}, PC = {}", self.kind, self.cycle, self.pc)
+    }
+}
+
+impl error::Error for Fault {}
+
+/// Checked mode: looks at every instruction before `HackExecutor` runs it and
+/// traps what the hardware would silently do, the faulting instruction is
+/// not executed.
+pub struct Checker {
+    written: Vec<bool>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        let mut written = vec![false; RAM_SIZE];
+        // The bootstrap `call Sys.init` saves the segment pointers before anything sets them.
+        written[LCL as usize..=THAT as usize].fill(true);
+
+        Self { written }
+    }
+
+    /// Marks RAM set up from outside the program, e.g. test inputs, as written.
+    pub fn mark_written(&mut self, address: i16) {
+        self.written[(address & ADDRESS_MASK) as usize] = true;
+    }
+
+    pub fn check(&mut self, executor: &HackExecutor) -> Result<(), Fault> {
+        let fault = |kind| Fault {
+            kind,
+            cycle: executor.cycles(),
+            pc: executor.pc,
+        };
+
+        if executor.pc < 0 || executor.pc as usize >= executor.program().len() {
+            return Err(fault(FaultKind::PcOutOfProgram));
+        }
+
+        let instruction = executor.fetch();
+        if instruction >= 0 {
+            return Ok(());
+        }
+
+        let address = executor.a & ADDRESS_MASK;
+        let result = alu::compute(executor.memory(), executor.a, executor.d, instruction)
+            .ok_or_else(|| fault(FaultKind::InvalidComputation(instruction)))?;
+
+        // The screen is cleared and the keyboard is driven by the hardware.
+        let io = (SCREEN_ADDRESS..=KBD_ADDRESS).contains(&address);
+        if instruction & A_BIT != 0 && !io && !self.written[address as usize] {
+            return Err(fault(FaultKind::UninitializedRead(address)));
+        }
+
+        if instruction & DEST_M != 0 {
+            if address >= KBD_ADDRESS {
+                return Err(fault(FaultKind::ReadOnlyWrite(address)));
+            }
+            if address == SP && result >= HEAP_START {
+                return Err(fault(FaultKind::StackOverflow(result)));
+            }
+            self.written[address as usize] = true;
+        }
+
+        Ok(())
+    }
+
+    /// The same as `HackExecutor::run_until_halt`, but every instruction is
+    /// checked before `step` runs it.
+    pub fn run_until_halt(
+        &mut self,
+        executor: &mut HackExecutor,
+        max_cycles: u64,
+        mut step: impl FnMut(&mut HackExecutor),
+    ) -> Result<HaltReason, Fault> {
+        for _ in 0..max_cycles {
+            self.check(executor)?;
+            if let Some(reason) = executor.halt_reason() {
+                return Ok(reason);
+            }
+            step(executor);
+        }
+
+        self.check(executor)?;
+        Ok(executor
+            .halt_reason()
+            .unwrap_or(HaltReason::BudgetExhausted))
+    }
+}
+
+impl Default for Checker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn fibonacci_checked_test() {
+        let src = fs::read_to_string("./priv/FibonacciElement.hack").unwrap();
+        let mut executor = HackExecutor::new(HackExecutor::parse_program(&src).unwrap());
+        let mut checker = Checker::new();
+
+        assert_eq!(
+            checker.run_until_halt(&mut executor, u64::MAX, HackExecutor::step),
+            Ok(HaltReason::Halted)
+        );
+        assert_eq!(executor.result(), 3);
+    }
+
+    fn check(program: Vec<i16>, steps: usize) -> Result<(), Fault> {
+        let mut executor = HackExecutor::new(program);
+        let mut checker = Checker::new();
+        for _ in 0..steps {
+            checker.check(&executor)?;
+            executor.step();
+        }
+        checker.check(&executor)
+    }
+
+    #[test]
+    fn faults_test() {
+        // @5 D=M
+        let err = check(vec![5, 0b1111110000010000u16 as i16], 1).unwrap_err();
+        assert_eq!(err.kind, FaultKind::UninitializedRead(5));
+        assert_eq!(
+            err.to_string(),
+            "read of never written RAM[5] at cycle 1, PC = 1"
+        );
+
+        // @5 M=0 D=M
+        let program = vec![
+            5,
+            0b1110101010001000u16 as i16,
+            0b1111110000010000u16 as i16,
+        ];
+        assert_eq!(check(program, 2), Ok(()));
+
+        // @2048 D=A @SP M=D
+        let program = vec![
+            2048,
+            0b1110110000010000u16 as i16,
+            0,
+            0b1110001100001000u16 as i16,
+        ];
+        let err = check(program, 3).unwrap_err();
+        assert_eq!(err.kind, FaultKind::StackOverflow(2048));
+
+        // @KBD M=1
+        let err = check(vec![KBD_ADDRESS, 0b1110111111001000u16 as i16], 1).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "write to RAM[24576] (KBD) at cycle 1, PC = 1"
+        );
+
+        // @SCREEN D=M is fine, the screen starts cleared
+        assert_eq!(
+            check(vec![SCREEN_ADDRESS, 0b1111110000010000u16 as i16], 2),
+            Err(Fault {
+                kind: FaultKind::PcOutOfProgram,
+                cycle: 2,
+                pc: 2
+            })
+        );
+
+        let err = check(vec![0b1110000001010000u16 as i16], 0).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "invalid computation in `1110000001010000` at cycle 0, PC = 0"
+        );
+    }
+}
@@ -0,0 +1,382 @@
+use std::collections::BTreeSet;
+use std::io::{self, BufRead, Write};
+
+use file_context::SourceMap;
+use hack_assembler::SymbolTable;
+use hack_disassembler::decode_word;
+use hack_instructions::{AInstruction, Instruction};
+use symbolic::SymbolicElem;
+
+use crate::executor::HackExecutor;
+use crate::locations::{address_name, MemorySelection};
+use crate::stack::CallStack;
+
+const PROMPT: &str = "(hdb) ";
+const DEFAULT_LIST_SIZE: usize = 5;
+
+const HELP: &str = "\
+step [n]            execute n instructions (1 by default)
+continue            run to a breakpoint, a watchpoint change or halt
+break <addr|label>  stop before the ROM address is executed
+delete <addr|label> remove a breakpoint
+watch <selection>   stop when a RAM value changes, e.g. `watch SP` or `watch 256..260`
+unwatch <selection> remove watchpoints
+print <selection>   print RAM, e.g. `print ARG`, `print stack`
+info                print A, D, PC and the cycle count
+list [n]            disassemble n instructions from PC
+backtrace           print the VM call stack with arguments and locals
+quit                leave the debugger
+An empty line repeats the previous command.";
+
+/// REPL on top of `HackExecutor`, ROM labels come from a `.sym` file
+/// written by the assembler or the translator.
+pub struct Debugger {
+    executor: HackExecutor,
+    /// Sorted by address.
+    labels: Vec<(i16, Vec<u8>)>,
+    call_stack: CallStack,
+    breakpoints: BTreeSet<i16>,
+    watchpoints: Vec<(i16, i16)>,
+    max_cycles: u64,
+    last_command: String,
+}
+
+impl Debugger {
+    pub fn new(executor: HackExecutor, symbols: &SymbolTable, max_cycles: u64) -> Self {
+        let mut labels: Vec<(i16, Vec<u8>)> = symbols
+            .labels
+            .iter()
+            .map(|(name, address)| (*address, name.clone()))
+            .collect();
+        labels.sort();
+
+        Self {
+            executor,
+            labels,
+            call_stack: CallStack::new(symbols),
+            breakpoints: BTreeSet::new(),
+            watchpoints: Vec::new(),
+            max_cycles,
+            last_command: String::new(),
+        }
+    }
+
+    pub fn executor(&self) -> &HackExecutor {
+        &self.executor
+    }
+
+    /// Adds `.vm` and `.jack` lines to the backtrace.
+    pub fn set_source_map(&mut self, source_map: SourceMap) -> &mut Self {
+        self.call_stack.set_source_map(source_map);
+        self
+    }
+
+    pub fn repl(&mut self, input: impl BufRead, mut out: impl Write) -> io::Result<()> {
+        self.print_location(&mut out)?;
+        write!(out, "{}", PROMPT)?;
+        out.flush()?;
+
+        for line in input.lines() {
+            if !self.execute(&line?, &mut out)? {
+                break;
+            }
+            write!(out, "{}", PROMPT)?;
+            out.flush()?;
+        }
+
+        Ok(())
+    }
+
+    /// Returns `false` when the debugger should stop.
+    pub fn execute(&mut self, line: &str, out: &mut impl Write) -> io::Result<bool> {
+        let line = if line.trim().is_empty() {
+            self.last_command.clone()
+        } else {
+            self.last_command = line.trim().to_string();
+            self.last_command.clone()
+        };
+
+        let mut parts = line.split_whitespace();
+        let command = parts.next().unwrap_or_default();
+        let arg = parts.next();
+
+        match (command, arg) {
+            ("", _) => (),
+            ("s" | "step", n) => match n.map_or(Some(1), |n| n.parse().ok()) {
+                Some(n) => self.run(n, false, out)?,
+                None => writeln!(out, "Wrong number of steps")?,
+            },
+            ("c" | "continue", None) => self.run(self.max_cycles, true, out)?,
+            ("b" | "break", Some(target)) => match self.resolve_target(target) {
+                Some(address) => {
+                    self.breakpoints.insert(address);
+                    writeln!(out, "Breakpoint at {}", self.format_address(address))?
+                }
+                None => writeln!(out, "Unknown ROM address {}", target)?,
+            },
+            ("d" | "delete", Some(target)) => match self.resolve_target(target) {
+                Some(address) if self.breakpoints.remove(&address) => writeln!(
+                    out,
+                    "Deleted breakpoint at {}",
+                    self.format_address(address)
+                )?,
+                _ => writeln!(out, "No breakpoint at {}", target)?,
+            },
+            ("w" | "watch", Some(selection)) => match MemorySelection::parse(selection) {
+                Some(selection) => {
+                    for addr in selection.addresses(&self.executor) {
+                        if !self.watchpoints.iter().any(|(a, _)| *a == addr) {
+                            self.watchpoints
+                                .push((addr, self.executor.read_memory(addr)));
+                        }
+                    }
+                    writeln!(out, "Watching {} addresses", self.watchpoints.len())?
+                }
+                None => writeln!(out, "Unknown address {}", selection)?,
+            },
+            ("unwatch", Some(selection)) => match MemorySelection::parse(selection) {
+                Some(selection) => {
+                    let addresses = selection.addresses(&self.executor);
+                    self.watchpoints.retain(|(a, _)| !addresses.contains(a));
+                    writeln!(out, "Watching {} addresses", self.watchpoints.len())?
+                }
+                None => writeln!(out, "Unknown address {}", selection)?,
+            },
+            ("p" | "print", Some(selection)) => match MemorySelection::parse(selection) {
+                Some(selection) => {
+                    for addr in selection.addresses(&self.executor) {
+                        self.print_memory(addr, out)?;
+                    }
+                }
+                None => writeln!(out, "Unknown address {}", selection)?,
+            },
+            ("i" | "info", None) => writeln!(
+                out,
+                "A = {}, D = {}, PC = {}, cycles = {}",
+                self.executor.a,
+                self.executor.d,
+                self.executor.pc,
+                self.executor.cycles()
+            )?,
+            ("l" | "list", n) => match n.map_or(Some(DEFAULT_LIST_SIZE), |n| n.parse().ok()) {
+                Some(n) => {
+                    let pc = self.executor.pc;
+                    for address in (pc..).take(n) {
+                        if address as usize >= self.executor.program().len() {
+                            break;
+                        }
+                        self.print_instruction(address, out)?;
+                    }
+                }
+                None => writeln!(out, "Wrong number of instructions")?,
+            },
+            ("bt" | "backtrace", None) => self.call_stack.write_backtrace(&self.executor, out)?,
+            ("h" | "help", None) => writeln!(out, "{}", HELP)?,
+            ("q" | "quit", None) => return Ok(false),
+            _ => writeln!(out, "Unknown command `{}`, try `help`", line)?,
+        }
+
+        Ok(true)
+    }
+
+    fn run(
+        &mut self,
+        max_steps: u64,
+        stop_at_breakpoints: bool,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        for _ in 0..max_steps {
+            if let Some(reason) = self.executor.halt_reason() {
+                writeln!(out, "Program {}", reason)?;
+                break;
+            }
+
+            self.executor.step();
+
+            if self.check_watchpoints(out)? {
+                break;
+            }
+
+            if stop_at_breakpoints && self.breakpoints.contains(&self.executor.pc) {
+                writeln!(
+                    out,
+                    "Breakpoint at {}",
+                    self.format_address(self.executor.pc)
+                )?;
+                break;
+            }
+        }
+
+        self.print_location(out)
+    }
+
+    fn check_watchpoints(&mut self, out: &mut impl Write) -> io::Result<bool> {
+        let mut changed = false;
+
+        for (addr, value) in self.watchpoints.iter_mut() {
+            let new_value = self.executor.read_memory(*addr);
+            if new_value != *value {
+                writeln!(out, "Watchpoint RAM[{}]: {} -> {}", addr, value, new_value)?;
+                *value = new_value;
+                changed = true;
+            }
+        }
+
+        Ok(changed)
+    }
+
+    fn resolve_target(&self, target: &str) -> Option<i16> {
+        self.labels
+            .iter()
+            .find(|(_, name)| name == target.as_bytes())
+            .map(|(address, _)| *address)
+            .or_else(|| target.parse().ok().filter(|a: &i16| *a >= 0))
+    }
+
+    /// `115 <Main.fibonacci+2>`, the closest label before the address is used.
+    pub fn format_address(&self, address: i16) -> String {
+        let idx = self.labels.partition_point(|(a, _)| *a <= address);
+        let label = idx.checked_sub(1).map(|idx| {
+            // Aliases share the address, the first one by name is used everywhere.
+            let label_address = self.labels[idx].0;
+            &self.labels[self.labels.partition_point(|(a, _)| *a < label_address)]
+        });
+
+        match label {
+            Some((label_address, name)) => {
+                let name = String::from_utf8_lossy(name);
+                match address - label_address {
+                    0 => format!("{} <{}>", address, name),
+                    offset => format!("{} <{}+{}>", address, name, offset),
+                }
+            }
+            None => address.to_string(),
+        }
+    }
+
+    fn print_location(&self, out: &mut impl Write) -> io::Result<()> {
+        if (self.executor.pc as usize) < self.executor.program().len() {
+            self.print_instruction(self.executor.pc, out)
+        } else {
+            writeln!(out, "PC = {} is out of ROM", self.executor.pc)
+        }
+    }
+
+    fn print_instruction(&self, address: i16, out: &mut impl Write) -> io::Result<()> {
+        let word = self.executor.program()[address as usize];
+        let marker = if address == self.executor.pc {
+            "=>"
+        } else {
+            "  "
+        };
+
+        let text = match decode_word(word) {
+            Some(instruction) => {
+                let mut buff = [0u8; 16];
+                let l = instruction.write_symbols(&mut buff);
+                let mut text = String::from_utf8_lossy(&buff[..l]).to_string();
+
+                if let Instruction::A(AInstruction::Number(n)) = instruction {
+                    if let Some((_, name)) = self.labels.iter().find(|(a, _)| *a == n) {
+                        text.push_str(&format!(" // {}", String::from_utf8_lossy(name)));
+                    }
+                }
+
+                text
+            }
+            None => format!("{:016b} // unknown", word as u16),
+        };
+
+        writeln!(out, "{} {}: {}", marker, self.format_address(address), text)
+    }
+
+    fn print_memory(&self, addr: i16, out: &mut impl Write) -> io::Result<()> {
+        match address_name(addr) {
+            Some(name) => writeln!(
+                out,
+                "RAM[{}] ({}) = {}",
+                addr,
+                name,
+                self.executor.read_memory(addr)
+            ),
+            None => writeln!(out, "RAM[{}] = {}", addr, self.executor.read_memory(addr)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::str::from_utf8;
+
+    use super::*;
+
+    fn fibonacci_debugger() -> Debugger {
+        let src = fs::read_to_string("./priv/FibonacciElement.hack").unwrap();
+        let symbols =
+            SymbolTable::from_symbol_file(&fs::read("./priv/FibonacciElement.sym").unwrap())
+                .unwrap();
+        let executor = HackExecutor::new(HackExecutor::parse_program(&src).unwrap());
+        Debugger::new(executor, &symbols, 1_000_000)
+    }
+
+    fn execute(debugger: &mut Debugger, line: &str) -> String {
+        let mut out = Vec::new();
+        assert!(debugger.execute(line, &mut out).unwrap());
+        from_utf8(&out).unwrap().to_string()
+    }
+
+    #[test]
+    fn breakpoint_test() {
+        let mut debugger = fibonacci_debugger();
+
+        assert_eq!(
+            execute(&mut debugger, "break Main.fibonacci"),
+            "Breakpoint at 113 <Main.fibonacci>\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "Breakpoint at 113 <Main.fibonacci>\n=> 113 <Main.fibonacci>: @2\n"
+        );
+        assert_eq!(execute(&mut debugger, "print ARG"), "RAM[2] (ARG) = 261\n");
+        assert_eq!(
+            execute(&mut debugger, "backtrace"),
+            "#0   Main.fibonacci(4) at 113\n#1   Sys.init() at 111\n"
+        );
+
+        assert_eq!(
+            execute(&mut debugger, "step 2"),
+            "=> 115 <Main.fibonacci+2>: D=M\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, ""),
+            "=> 117 <Main.fibonacci+4>: A=M\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "delete 113"),
+            "Deleted breakpoint at 113 <Main.fibonacci>\n"
+        );
+        assert!(execute(&mut debugger, "continue").starts_with("Program halted\n"));
+        assert_eq!(debugger.executor().result(), 3);
+    }
+
+    #[test]
+    fn watchpoint_test() {
+        let mut debugger = fibonacci_debugger();
+
+        assert_eq!(execute(&mut debugger, "watch SP"), "Watching 1 addresses\n");
+        assert_eq!(
+            execute(&mut debugger, "continue"),
+            "Watchpoint RAM[0]: 0 -> 256\n=> 4: @54 // Sys.init\n"
+        );
+        assert_eq!(
+            execute(&mut debugger, "info"),
+            "A = 0, D = 256, PC = 4, cycles = 4\n"
+        );
+        assert!(execute(&mut debugger, "list 2").ends_with("   5: D=A\n"));
+        assert_eq!(
+            execute(&mut debugger, "jump"),
+            "Unknown command `jump`, try `help`\n"
+        );
+    }
+}
@@ -0,0 +1,279 @@
+use std::fmt;
+
+use crate::alu;
+use crate::memory::{HackMemory, SP};
+use crate::pc::perform_jump;
+
+const ROM_ADDRESS_MASK: i16 = 0x7FFF;
+const DEST_MASK: i16 = 0b111000;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HaltReason {
+    /// The program reached a loop that can not change the machine state, e.g. `(END) @END 0;JMP`.
+    Halted,
+    BudgetExhausted,
+    PcOutOfRom,
+}
+
+impl fmt::Display for HaltReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Halted => write!(f, "halted"),
+            Self::BudgetExhausted => write!(f, "cycle budget exhausted"),
+            Self::PcOutOfRom => write!(f, "PC out of ROM"),
+        }
+    }
+}
+
+pub struct HackExecutor {
+    pub a: i16,
+    pub d: i16,
+    pub pc: i16,
+    cycles: u64,
+    memory: HackMemory,
+    program: Vec<i16>,
+}
+
+impl HackExecutor {
+    pub fn new(program: Vec<i16>) -> Self {
+        Self {
+            a: 0,
+            d: 0,
+            pc: 0,
+            cycles: 0,
+            memory: HackMemory::new(),
+            program,
+        }
+    }
+
+    /// Reads `.hack` text, one 16 bit binary number per line.
+    pub fn parse_program(src: &str) -> Option<Vec<i16>> {
+        src.lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty())
+            .map(|line| u16::from_str_radix(line, 2).ok().map(|v| v as i16))
+            .collect()
+    }
+
+    /// Unused ROM reads as zero, that is `@0`, the same as the Hack computer.
+    pub fn fetch(&self) -> i16 {
+        self.program
+            .get((self.pc & ROM_ADDRESS_MASK) as usize)
+            .copied()
+            .unwrap_or(0)
+    }
+
+    pub fn step(&mut self) {
+        let instruction = self.fetch();
+        let result = alu::perform(&self.memory, self.a, self.d, instruction);
+        self.pc = perform_jump(self.pc, result, self.a, instruction);
+        self.save_result(result, instruction);
+        self.cycles += 1;
+    }
+
+    pub fn run(&mut self, iterations_count: usize) {
+        for _ in 0..iterations_count {
+            self.step();
+        }
+    }
+
+    /// Steps until the program halts, leaves the ROM or `max_cycles` steps are made.
+    pub fn run_until_halt(&mut self, max_cycles: u64) -> HaltReason {
+        for _ in 0..max_cycles {
+            if let Some(reason) = self.halt_reason() {
+                return reason;
+            }
+            self.step();
+        }
+
+        self.halt_reason().unwrap_or(HaltReason::BudgetExhausted)
+    }
+
+    pub fn halt_reason(&self) -> Option<HaltReason> {
+        if self.pc < 0 || self.pc as usize >= self.program.len() {
+            return Some(HaltReason::PcOutOfRom);
+        }
+
+        self.is_halt_loop().then_some(HaltReason::Halted)
+    }
+
+    /// A taken jump without destination to itself, or to an `@k` stored at address `k`
+    /// right before it, repeats the same state forever.
+    fn is_halt_loop(&self) -> bool {
+        let instruction = self.fetch();
+        if instruction >= 0 || instruction & DEST_MASK != 0 {
+            return false;
+        }
+
+        let result = alu::perform(&self.memory, self.a, self.d, instruction);
+        if perform_jump(self.pc, result, self.a, instruction) != self.a {
+            return false;
+        }
+
+        self.a == self.pc
+            || (self.a == self.pc - 1 && self.program.get(self.a as usize) == Some(&self.a))
+    }
+
+    pub fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    pub fn result(&self) -> i16 {
+        let v = self.read_memory(SP);
+        self.read_memory(v.wrapping_sub(1))
+    }
+
+    pub fn read_memory(&self, pointer: i16) -> i16 {
+        self.memory.get(pointer)
+    }
+
+    pub fn write_memory(&mut self, pointer: i16, value: i16) {
+        self.memory.set(pointer, value)
+    }
+
+    pub fn memory(&self) -> &HackMemory {
+        &self.memory
+    }
+
+    pub fn program(&self) -> &[i16] {
+        &self.program
+    }
+
+    fn save_result(&mut self, result: i16, i: i16) {
+        if i >= 0 {
+            self.a = result;
+            return;
+        }
+
+        let result_code = (i >> 3) & 0b111;
+        if result_code & 0b1 != 0 {
+            self.memory.set(self.a, result);
+        }
+        if result_code > 3 {
+            self.a = result;
+        }
+        if (result_code & 0b11) > 1 {
+            self.d = result;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    fn fibonacci_program() -> Vec<i16> {
+        let src = fs::read_to_string("./priv/FibonacciElement.hack").unwrap();
+        HackExecutor::parse_program(&src).unwrap()
+    }
+
+    #[test]
+    fn simple_add_program_test() {
+        let mut executor = HackExecutor::new(vec![
+            0b0000000000000010,
+            0b1110110000010000u16 as i16,
+            0b0000000000000011,
+            0b1110000010010000u16 as i16,
+            0b0000000000000000,
+            0b1110001100001000u16 as i16,
+        ]);
+        executor.run(6);
+
+        assert_eq!(executor.read_memory(0), 5);
+        assert_eq!(executor.pc, 6);
+    }
+
+    #[test]
+    fn write_memory_test() {
+        // @100 D=M @101 M=D
+        let mut executor = HackExecutor::new(vec![
+            100,
+            0b1111110000010000u16 as i16,
+            101,
+            0b1110001100001000u16 as i16,
+        ]);
+        executor.write_memory(100, -42);
+        executor.run(4);
+
+        assert_eq!(executor.read_memory(101), -42);
+    }
+
+    #[test]
+    fn fibonacci_element_test() {
+        let mut executor = HackExecutor::new(fibonacci_program());
+        executor.run(5_000);
+
+        assert_eq!(executor.result(), 3);
+    }
+
+    #[test]
+    fn run_until_halt_test() {
+        let mut executor = HackExecutor::new(fibonacci_program());
+        assert_eq!(executor.run_until_halt(u64::MAX), HaltReason::Halted);
+        assert_eq!(executor.result(), 3);
+
+        let cycles = executor.cycles();
+        assert_eq!(executor.run_until_halt(u64::MAX), HaltReason::Halted);
+        assert_eq!(executor.cycles(), cycles);
+
+        let mut executor = HackExecutor::new(fibonacci_program());
+        assert_eq!(executor.run_until_halt(100), HaltReason::BudgetExhausted);
+        assert_eq!(executor.cycles(), 100);
+    }
+
+    #[test]
+    fn halt_loop_test() {
+        // @2 0;JMP (END) @END 0;JMP
+        let end_loop = vec![
+            2,
+            0b1110101010000111u16 as i16,
+            2,
+            0b1110101010000111u16 as i16,
+        ];
+        let mut executor = HackExecutor::new(end_loop);
+        assert_eq!(executor.run_until_halt(100), HaltReason::Halted);
+        assert_eq!(executor.cycles(), 3);
+
+        // @1 D;JEQ loops only while D is zero
+        let self_jump = vec![1, 0b1110001100000010u16 as i16];
+        let mut executor = HackExecutor::new(self_jump.clone());
+        assert_eq!(executor.run_until_halt(100), HaltReason::Halted);
+
+        let mut executor = HackExecutor::new(self_jump);
+        executor.d = 1;
+        assert_eq!(executor.run_until_halt(100), HaltReason::PcOutOfRom);
+    }
+
+    #[cfg(feature = "ffi")]
+    #[test]
+    fn ffi_cross_validation_test() {
+        use crate::bindings;
+
+        let mut program = fibonacci_program();
+        let mut executor = HackExecutor::new(program.clone());
+        let ffi_executor = bindings::HackExecutor::new(&mut program);
+
+        for _ in 0..5_000 {
+            executor.step();
+            bindings::HackExecutor::run(ffi_executor, 1);
+
+            let ffi_state = unsafe { *ffi_executor };
+            assert_eq!(
+                (executor.a, executor.d, executor.pc),
+                (ffi_state.a, ffi_state.d, ffi_state.pc)
+            );
+        }
+
+        // Longer than a C `short` iterations count.
+        executor.run_until_halt(40_000);
+        bindings::HackExecutor::run(ffi_executor, 40_000);
+
+        assert_eq!(
+            executor.result(),
+            bindings::HackExecutor::result(ffi_executor)
+        );
+        bindings::HackExecutor::drop(ffi_executor);
+    }
+}
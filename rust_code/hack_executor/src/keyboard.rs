@@ -0,0 +1,241 @@
+use std::{error, fmt};
+
+use crate::executor::HackExecutor;
+use crate::memory::KBD_ADDRESS;
+
+pub const KEY_HOLD_CYCLES: u64 = 50_000;
+pub const KEY_GAP_CYCLES: u64 = 50_000;
+
+/// Keys outside printable ASCII, the codes are the ones `Keyboard.keyPressed` returns.
+pub const KEY_NAMES: [(&str, i16); 26] = [
+    ("NEWLINE", 128),
+    ("BACKSPACE", 129),
+    ("LEFT", 130),
+    ("UP", 131),
+    ("RIGHT", 132),
+    ("DOWN", 133),
+    ("HOME", 134),
+    ("END", 135),
+    ("PAGEUP", 136),
+    ("PAGEDOWN", 137),
+    ("INSERT", 138),
+    ("DELETE", 139),
+    ("ESC", 140),
+    ("F1", 141),
+    ("F2", 142),
+    ("F3", 143),
+    ("F4", 144),
+    ("F5", 145),
+    ("F6", 146),
+    ("F7", 147),
+    ("F8", 148),
+    ("F9", 149),
+    ("F10", 150),
+    ("F11", 151),
+    ("F12", 152),
+    ("SPACE", 32),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyEvent {
+    pub cycle: u64,
+    /// 0 releases the key.
+    pub keycode: i16,
+}
+
+#[derive(Debug)]
+pub struct KeyboardScriptError {
+    pub line: usize,
+    pub text: String,
+}
+
+impl fmt::Display for KeyboardScriptError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: wrong key event `{}`", self.line + 1, self.text)
+    }
+}
+
+impl error::Error for KeyboardScriptError {}
+
+/// Key events ordered by cycle, applied to `KBD` while the program runs.
+#[derive(Debug, Default)]
+pub struct KeyboardScript {
+    events: Vec<KeyEvent>,
+    next: usize,
+}
+
+impl KeyboardScript {
+    pub fn new(mut events: Vec<KeyEvent>) -> Self {
+        events.sort_by_key(|e| e.cycle);
+        Self { events, next: 0 }
+    }
+
+    /// Every line is `cycle:keycode`, `cycle:KEY_NAME` or `cycle:"text"`,
+    /// `#` starts a comment.
+    pub fn parse(src: &str) -> Result<Self, KeyboardScriptError> {
+        let mut events = Vec::new();
+
+        for (line, raw_line) in src.lines().enumerate() {
+            let text = match raw_line.find('#') {
+                Some(idx) => &raw_line[..idx],
+                None => raw_line,
+            }
+            .trim();
+
+            if text.is_empty() {
+                continue;
+            }
+
+            let error = || KeyboardScriptError {
+                line,
+                text: text.to_string(),
+            };
+
+            let (cycle, key) = text.split_once(':').ok_or_else(error)?;
+            let cycle: u64 = cycle.trim().parse().map_err(|_| error())?;
+            let key = key.trim();
+
+            match key.strip_prefix('"').and_then(|k| k.strip_suffix('"')) {
+                Some(typed) => {
+                    let typed = typed.replace("\\n", "\n");
+                    events.extend(type_events(&typed, cycle).ok_or_else(error)?)
+                }
+                None => events.push(KeyEvent {
+                    cycle,
+                    keycode: parse_keycode(key).ok_or_else(error)?,
+                }),
+            }
+        }
+
+        Ok(Self::new(events))
+    }
+
+    /// Types `text` one key at a time from `start`, each key is held for
+    /// `KEY_HOLD_CYCLES` and released for `KEY_GAP_CYCLES`.
+    pub fn from_text(text: &str, start: u64) -> Option<Self> {
+        type_events(text, start).map(Self::new)
+    }
+
+    pub fn events(&self) -> &[KeyEvent] {
+        &self.events
+    }
+
+    pub fn next_cycle(&self) -> Option<u64> {
+        self.events.get(self.next).map(|e| e.cycle)
+    }
+
+    /// Writes every event due by the current cycle to `KBD`.
+    pub fn apply(&mut self, executor: &mut HackExecutor) {
+        while let Some(event) = self.events.get(self.next) {
+            if event.cycle > executor.cycles() {
+                break;
+            }
+
+            executor.write_memory(KBD_ADDRESS, event.keycode);
+            self.next += 1;
+        }
+    }
+}
+
+fn parse_keycode(key: &str) -> Option<i16> {
+    KEY_NAMES
+        .iter()
+        .find(|(name, _)| *name == key)
+        .map(|(_, code)| *code)
+        .or_else(|| key.parse().ok().filter(|code| *code >= 0))
+}
+
+fn char_keycode(c: char) -> Option<i16> {
+    match c {
+        '\n' => Some(128),
+        ' '..='~' => Some(c as i16),
+        _ => None,
+    }
+}
+
+fn type_events(text: &str, start: u64) -> Option<Vec<KeyEvent>> {
+    let mut events = Vec::with_capacity(text.len() * 2);
+    let mut cycle = start;
+
+    for c in text.chars() {
+        events.push(KeyEvent {
+            cycle,
+            keycode: char_keycode(c)?,
+        });
+        events.push(KeyEvent {
+            cycle: cycle + KEY_HOLD_CYCLES,
+            keycode: 0,
+        });
+        cycle += KEY_HOLD_CYCLES + KEY_GAP_CYCLES;
+    }
+
+    Some(events)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn events(events: &[(u64, i16)]) -> Vec<KeyEvent> {
+        events
+            .iter()
+            .map(|(cycle, keycode)| KeyEvent {
+                cycle: *cycle,
+                keycode: *keycode,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn parse_script_test() {
+        let script = KeyboardScript::parse(
+            "# press and release
+            100:65
+            200:0
+            300:NEWLINE
+            0:\"a\\n\"",
+        )
+        .unwrap();
+
+        assert_eq!(
+            script.events(),
+            events(&[
+                (0, 97),
+                (100, 65),
+                (200, 0),
+                (300, 128),
+                (50_000, 0),
+                (100_000, 128),
+                (150_000, 0)
+            ])
+        );
+
+        let err = KeyboardScript::parse("100:65\n200:SHIFT").unwrap_err();
+        assert_eq!(err.to_string(), "2: wrong key event `200:SHIFT`");
+        assert!(KeyboardScript::from_text("é", 0).is_none());
+    }
+
+    #[test]
+    fn apply_script_test() {
+        // (LOOP) @KBD D=M @100 M=D @LOOP 0;JMP
+        let mut executor = HackExecutor::new(vec![
+            KBD_ADDRESS,
+            0b1111110000010000u16 as i16,
+            100,
+            0b1110001100001000u16 as i16,
+            0,
+            0b1110101010000111u16 as i16,
+        ]);
+        let mut script = KeyboardScript::new(events(&[(6, 0), (0, 75)]));
+
+        script.apply(&mut executor);
+        assert_eq!(script.next_cycle(), Some(6));
+        executor.run(6);
+        assert_eq!(executor.read_memory(100), 75);
+
+        script.apply(&mut executor);
+        assert_eq!(script.next_cycle(), None);
+        executor.run(6);
+        assert_eq!(executor.read_memory(100), 0);
+    }
+}
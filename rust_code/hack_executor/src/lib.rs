@@ -0,0 +1,28 @@
+mod alu;
+mod checker;
+mod debugger;
+mod executor;
+mod keyboard;
+mod locations;
+mod memory;
+mod pc;
+mod profiler;
+mod screen;
+mod stack;
+
+#[cfg(feature = "ffi")]
+pub mod bindings;
+
+pub use checker::{Checker, Fault, FaultKind};
+pub use debugger::Debugger;
+pub use executor::{HackExecutor, HaltReason};
+pub use keyboard::{
+    KeyEvent, KeyboardScript, KeyboardScriptError, KEY_GAP_CYCLES, KEY_HOLD_CYCLES, KEY_NAMES,
+};
+pub use locations::{
+    address_name, MemorySelection, HEAP_START, NAMED_ADDRESSES, STACK_START, STATIC_START,
+};
+pub use memory::{HackMemory, ARG, KBD_ADDRESS, LCL, RAM_SIZE, SCREEN_ADDRESS, SP, THAT, THIS};
+pub use profiler::Profiler;
+pub use screen::{framebuffer, write_pbm, write_png, SCREEN_HEIGHT, SCREEN_WIDTH};
+pub use stack::{CallStack, StackFrame};
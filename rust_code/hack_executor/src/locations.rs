@@ -0,0 +1,149 @@
+use std::ops::RangeInclusive;
+
+use crate::executor::HackExecutor;
+use crate::memory::{KBD_ADDRESS, RAM_SIZE, SCREEN_ADDRESS, SP};
+
+pub const STATIC_START: i16 = 16;
+pub const STACK_START: i16 = 256;
+pub const HEAP_START: i16 = 2048;
+
+/// Names of the fixed addresses, the first name wins for aliases like `SP` and `R0`.
+pub const NAMED_ADDRESSES: [(&str, i16); 23] = [
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", SCREEN_ADDRESS),
+    ("KBD", KBD_ADDRESS),
+];
+
+pub fn address_name(addr: i16) -> Option<&'static str> {
+    NAMED_ADDRESSES
+        .iter()
+        .find(|(_, a)| *a == addr)
+        .map(|(name, _)| *name)
+}
+
+fn parse_address(s: &str) -> Option<i16> {
+    NAMED_ADDRESSES
+        .iter()
+        .find(|(name, _)| *name == s)
+        .map(|(_, a)| *a)
+        .or_else(|| s.parse().ok().filter(|a| *a >= 0))
+}
+
+/// The end of a range can be one past the last address.
+fn parse_end(s: &str) -> Option<i32> {
+    parse_address(s)
+        .map(i32::from)
+        .or_else(|| s.parse().ok().filter(|a| *a == RAM_SIZE as i32))
+}
+
+/// A part of RAM to print: `SP`, `R13`, `256`, `256..260`, `static` or `stack`.
+/// The end of `from..to` is left out like in Rust.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MemorySelection {
+    /// Inclusive, so that it can hold the last address.
+    Range(RangeInclusive<i16>),
+    /// From `STACK_START` up to the current `SP`.
+    Stack,
+}
+
+impl MemorySelection {
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "static" => Some(Self::Range(STATIC_START..=STACK_START - 1)),
+            "stack" => Some(Self::Stack),
+            _ => match s.split_once("..") {
+                Some((from, to)) => {
+                    let (from, to) = (parse_address(from)?, parse_end(to)?);
+                    (i32::from(from) < to).then(|| Self::Range(from..=(to - 1) as i16))
+                }
+                None => parse_address(s).map(|a| Self::Range(a..=a)),
+            },
+        }
+    }
+
+    pub fn addresses(&self, executor: &HackExecutor) -> RangeInclusive<i16> {
+        match self {
+            Self::Range(range) => range.clone(),
+            Self::Stack => STACK_START..=executor.read_memory(SP).max(STACK_START) - 1,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_selection_test() {
+        assert_eq!(
+            MemorySelection::parse("SP"),
+            Some(MemorySelection::Range(0..=0))
+        );
+        assert_eq!(
+            MemorySelection::parse("R13"),
+            Some(MemorySelection::Range(13..=13))
+        );
+        assert_eq!(
+            MemorySelection::parse("256..260"),
+            Some(MemorySelection::Range(256..=259))
+        );
+        assert_eq!(
+            MemorySelection::parse("SCREEN..16400"),
+            Some(MemorySelection::Range(16384..=16399))
+        );
+        assert_eq!(
+            MemorySelection::parse("static"),
+            Some(MemorySelection::Range(16..=255))
+        );
+        assert_eq!(
+            MemorySelection::parse("32767"),
+            Some(MemorySelection::Range(32767..=32767))
+        );
+        assert_eq!(
+            MemorySelection::parse("32760..32767"),
+            Some(MemorySelection::Range(32760..=32766))
+        );
+        assert_eq!(
+            MemorySelection::parse("32760..32768"),
+            Some(MemorySelection::Range(32760..=32767))
+        );
+        assert_eq!(MemorySelection::parse("32760..32769"), None);
+        assert_eq!(MemorySelection::parse("32768"), None);
+        assert_eq!(MemorySelection::parse("260..256"), None);
+        assert_eq!(MemorySelection::parse("-1"), None);
+        assert_eq!(MemorySelection::parse("FOO"), None);
+
+        assert_eq!(address_name(0), Some("SP"));
+        assert_eq!(address_name(13), Some("R13"));
+        assert_eq!(address_name(300), None);
+    }
+
+    #[test]
+    fn stack_selection_test() {
+        let mut executor = HackExecutor::new(vec![]);
+        assert!(MemorySelection::Stack.addresses(&executor).is_empty());
+
+        executor.write_memory(SP, 259);
+        assert_eq!(MemorySelection::Stack.addresses(&executor), 256..=258);
+    }
+}
@@ -1,37 +1,225 @@
 use std::env;
-use std::fs::File;
-use std::io::{self, prelude::*, BufReader};
+use std::fs;
+use std::io::{self, Write};
 use std::path::Path;
+use std::process::ExitCode;
 
-mod bindings;
-use bindings::HackExecutor;
+use file_context::SourceMap;
+use hack_assembler::SymbolTable;
+use hack_executor::{
+    address_name, write_pbm, write_png, CallStack, Checker, Debugger, HackExecutor, HaltReason,
+    KeyboardScript, Profiler, HEAP_START, SP, STACK_START,
+};
 
-fn main() -> io::Result<()> {
+mod args;
+use args::{Args, DumpFormat};
+
+/// Fails when the program faults or jumps out of ROM, like `n2t run`.
+fn main() -> io::Result<ExitCode> {
     let args: Vec<String> = env::args().collect();
+    let args = Args::parse(&args)?;
+
+    let src = fs::read_to_string(&args.file_path)?;
+
+    let instructions = HackExecutor::parse_program(&src)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "Wrong .hack format"))?;
+
+    let mut h = HackExecutor::new(instructions);
+
+    if args.debug {
+        let symbols = read_symbols(&args)?;
+        let mut debugger = Debugger::new(h, &symbols, args.max_cycles);
+        if let Some(source_map) = read_source_map(&args)? {
+            debugger.set_source_map(source_map);
+        }
+        debugger.repl(io::stdin().lock(), io::stdout())?;
+        return Ok(ExitCode::SUCCESS);
+    }
+
+    let mut keyboard = keyboard_script(&args)?;
+    let screen_every = args.screen_path.as_ref().and(args.screen_every);
+    let mut profiler = if args.profile || args.folded_path.is_some() {
+        Some(Profiler::new(&read_symbols(&args)?, h.program().len()))
+    } else {
+        None
+    };
+    let mut checker = args.checked.then(Checker::new);
+
+    let result = loop {
+        keyboard.apply(&mut h);
+
+        let mut stop = args.max_cycles;
+        if let Some(cycle) = keyboard.next_cycle() {
+            stop = stop.min(cycle);
+        }
+        if let Some(every) = screen_every {
+            stop = stop.min((h.cycles() / every + 1) * every);
+        }
+
+        let budget = stop - h.cycles();
+        let result = match (&mut checker, &mut profiler) {
+            (Some(checker), Some(profiler)) => {
+                checker.run_until_halt(&mut h, budget, |h| profiler.step(h))
+            }
+            (Some(checker), None) => checker.run_until_halt(&mut h, budget, HackExecutor::step),
+            (None, Some(profiler)) => Ok(profiler.run_until_halt(&mut h, budget)),
+            (None, None) => Ok(h.run_until_halt(budget)),
+        };
+        if result != Ok(HaltReason::BudgetExhausted) || h.cycles() >= args.max_cycles {
+            break result;
+        }
+
+        if let (Some(screen_path), Some(every)) = (&args.screen_path, screen_every) {
+            if h.cycles().is_multiple_of(every) {
+                let stem = screen_path.file_stem().unwrap().to_str().unwrap();
+                let ext = screen_path.extension().unwrap_or_default();
+                let snapshot_path = screen_path
+                    .with_file_name(format!("{}_{}", stem, h.cycles()))
+                    .with_extension(ext);
+                write_screen(&h, &snapshot_path)?;
+            }
+        }
+    };
+
+    let symbols = read_symbols(&args)?;
+    let mut call_stack = CallStack::new(&symbols);
+    if let Some(source_map) = read_source_map(&args)? {
+        call_stack.set_source_map(source_map);
+    }
+
+    match &result {
+        Ok(reason) => eprintln!("{} after {} cycles, PC = {}", reason, h.cycles(), h.pc),
+        Err(fault) => {
+            eprintln!("{}", fault);
+            for location in call_stack.location(fault.pc) {
+                eprintln!("    at {}", location);
+            }
+        }
+    }
+
+    // Without VM functions RAM[0] is not necessarily a stack pointer.
+    let sp = h.read_memory(SP);
+    let stack_overflow = call_stack.has_functions() && !(STACK_START..HEAP_START).contains(&sp);
+    if stack_overflow {
+        eprintln!("SP = {} is out of the stack", sp);
+    }
+    let fault = matches!(result, Ok(HaltReason::PcOutOfRom) | Err(_));
+    if args.backtrace || call_stack.has_functions() && (stack_overflow || fault) {
+        call_stack.write_backtrace(&h, &mut io::stderr().lock())?;
+    }
 
-    let file_path = Path::new(&args[1]);
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
+    if args.selections.is_empty() {
+        println!("{}", h.result());
+    }
+
+    for selection in &args.selections {
+        for addr in selection.addresses(&h) {
+            match address_name(addr) {
+                Some(name) => println!("RAM[{}] ({}) = {}", addr, name, h.read_memory(addr)),
+                None => println!("RAM[{}] = {}", addr, h.read_memory(addr)),
+            }
+        }
+    }
 
-    let mut instructions: Vec<i16> = Vec::new();
-    for l in reader.lines() {
-        let line = l?;
-        let intval = u16::from_str_radix(&line, 2).unwrap();
-        instructions.push(intval as i16)
+    if let Some(profiler) = &profiler {
+        let mut out = io::stdout().lock();
+        if args.profile {
+            writeln!(out)?;
+            profiler.write_flat_profile(&mut out)?;
+            writeln!(out)?;
+            profiler.write_call_counts(&mut out)?;
+        }
+        if let Some(folded_path) = &args.folded_path {
+            let mut folded = Vec::new();
+            profiler.write_folded_stacks(&mut folded)?;
+            fs::write(folded_path, folded)?;
+        }
     }
 
-    let h = HackExecutor::new(&mut instructions);
+    if let Some(dump_path) = &args.dump_path {
+        let mut out = Vec::new();
+        match args.dump_format {
+            DumpFormat::Text => h.memory().dump_text(&mut out),
+            DumpFormat::Binary => h.memory().dump_binary(&mut out),
+        }
+        fs::write(dump_path, out)?;
+    }
 
-    HackExecutor::run(h, 5_000);
+    if let Some(screen_path) = &args.screen_path {
+        write_screen(&h, screen_path)?;
+    }
 
-    if args.len() > 2 {
-        let v: i16 = args[2].parse().unwrap();
-        HackExecutor::read_memory(h, v)
+    Ok(if fault {
+        ExitCode::FAILURE
     } else {
-        HackExecutor::result(h)
+        ExitCode::SUCCESS
+    })
+}
+
+/// The `.sym` file next to the program is used when `--symbols` is not passed.
+fn read_symbols(args: &Args) -> io::Result<SymbolTable> {
+    let default_path = args.file_path.with_extension("sym");
+    let path = match &args.symbols_path {
+        Some(path) => path,
+        None if default_path.exists() => &default_path,
+        None => return Ok(SymbolTable::new()),
     };
 
-    HackExecutor::drop(h);
+    SymbolTable::from_symbol_file(&fs::read(path)?).map_err(|err| {
+        io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{}:{}", path.display(), err),
+        )
+    })
+}
 
-    Ok(())
+/// `WITH_SOURCE_MAP=1` translations leave a `.hack.map` next to the program.
+fn read_source_map(args: &Args) -> io::Result<Option<SourceMap>> {
+    let path = args.file_path.with_extension("hack.map");
+    if !path.exists() {
+        return Ok(None);
+    }
+
+    SourceMap::parse(&fs::read_to_string(&path)?)
+        .map(Some)
+        .map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}", path.display(), err),
+            )
+        })
+}
+
+fn keyboard_script(args: &Args) -> io::Result<KeyboardScript> {
+    let mut events = Vec::new();
+
+    if let Some(keys_path) = &args.keys_path {
+        let src = fs::read_to_string(keys_path)?;
+        let script = KeyboardScript::parse(&src).map_err(|err| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{}:{}", keys_path.display(), err),
+            )
+        })?;
+        events.extend(script.events());
+    }
+
+    if let Some(text) = &args.typed_text {
+        let script = KeyboardScript::from_text(&text.replace("\\n", "\n"), 0).ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "Only ASCII can be typed")
+        })?;
+        events.extend(script.events());
+    }
+
+    Ok(KeyboardScript::new(events))
+}
+
+fn write_screen(h: &HackExecutor, path: &Path) -> io::Result<()> {
+    let mut out = Vec::new();
+    if path.extension().is_some_and(|ext| ext == "png") {
+        write_png(h.memory(), &mut out)?;
+    } else {
+        write_pbm(h.memory(), &mut out);
+    }
+    fs::write(path, out)
 }
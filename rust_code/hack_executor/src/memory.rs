@@ -0,0 +1,73 @@
+pub const SP: i16 = 0;
+pub const LCL: i16 = 1;
+pub const ARG: i16 = 2;
+pub const THIS: i16 = 3;
+pub const THAT: i16 = 4;
+pub const SCREEN_ADDRESS: i16 = 16384;
+pub const KBD_ADDRESS: i16 = 24576;
+
+/// Every 15 bit address is addressable, the same as the A register can hold.
+pub const RAM_SIZE: usize = 32768;
+
+const ADDRESS_MASK: i16 = 0x7FFF;
+
+pub struct HackMemory {
+    cells: Box<[i16]>,
+}
+
+impl HackMemory {
+    pub fn new() -> Self {
+        Self {
+            cells: vec![0; RAM_SIZE].into_boxed_slice(),
+        }
+    }
+
+    pub fn get(&self, addr: i16) -> i16 {
+        self.cells[(addr & ADDRESS_MASK) as usize]
+    }
+
+    pub fn set(&mut self, addr: i16, val: i16) {
+        self.cells[(addr & ADDRESS_MASK) as usize] = val;
+    }
+
+    /// One signed decimal value per line, the line number is the address.
+    pub fn dump_text(&self, out: &mut Vec<u8>) {
+        for v in self.cells.iter() {
+            out.extend_from_slice(format!("{}\n", v).as_bytes());
+        }
+    }
+
+    /// Every cell as 2 little endian bytes.
+    pub fn dump_binary(&self, out: &mut Vec<u8>) {
+        for v in self.cells.iter() {
+            out.extend_from_slice(&v.to_le_bytes());
+        }
+    }
+}
+
+impl Default for HackMemory {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dump_test() {
+        let mut memory = HackMemory::new();
+        memory.set(1, -2);
+
+        let mut text = Vec::new();
+        memory.dump_text(&mut text);
+        assert!(text.starts_with(b"0\n-2\n0\n"));
+        assert_eq!(text.iter().filter(|c| **c == b'\n').count(), RAM_SIZE);
+
+        let mut binary = Vec::new();
+        memory.dump_binary(&mut binary);
+        assert_eq!(binary.len(), RAM_SIZE * 2);
+        assert_eq!(&binary[..4], &[0, 0, 0xFE, 0xFF]);
+    }
+}
@@ -0,0 +1,33 @@
+/// Returns the next PC, a jump goes to the address stored in A.
+pub fn perform_jump(pc: i16, result: i16, a: i16, i: i16) -> i16 {
+    if i >= 0 {
+        return pc.wrapping_add(1);
+    }
+
+    let jump_code1 = i & 0b111;
+    let jump_code2 = (result < 0) as i16 * 4 + (result == 0) as i16 * 2 + (result > 0) as i16;
+
+    if jump_code1 & jump_code2 != 0 {
+        a
+    } else {
+        pc.wrapping_add(1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn jump_test() {
+        // 0;JMP
+        assert_eq!(perform_jump(3, 0, 10, 0b1110101010000111u16 as i16), 10);
+        // D;JGT
+        assert_eq!(perform_jump(3, 0, 10, 0b1110001100000001u16 as i16), 4);
+        assert_eq!(perform_jump(3, 1, 10, 0b1110001100000001u16 as i16), 10);
+        // D;JLE
+        assert_eq!(perform_jump(3, -1, 10, 0b1110001100000110u16 as i16), 10);
+        // @10
+        assert_eq!(perform_jump(3, 10, 10, 10), 4);
+    }
+}
@@ -0,0 +1,334 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+
+use hack_assembler::SymbolTable;
+
+use crate::executor::{HackExecutor, HaltReason};
+use crate::memory::{LCL, SP};
+
+/// Code before the first function, e.g. the bootstrap.
+const TOP_LEVEL: &str = "<bootstrap>";
+
+/// Return labels written by the translator's `call` are `Callee.name.File.n`,
+/// so a label is a function entry when some return label starts with it.
+pub(crate) fn function_labels(symbols: &SymbolTable) -> Vec<(i16, String)> {
+    let mut functions: Vec<(i16, String)> = symbols
+        .labels
+        .iter()
+        .filter(|(name, _)| {
+            symbols.labels.iter().any(|(other, _)| {
+                other.len() > name.len() + 1 && other.starts_with(name) && other[name.len()] == b'.'
+            })
+        })
+        .map(|(name, address)| (*address, String::from_utf8_lossy(name).to_string()))
+        .collect();
+    functions.sort();
+    functions
+}
+
+struct StackNode {
+    function: Option<usize>,
+    parent: usize,
+    children: HashMap<usize, usize>,
+    cycles: u64,
+}
+
+/// Counts cycles per ROM address and per call stack while stepping a `HackExecutor`.
+///
+/// A call is a jump to a function entry right after the VM `call` sequence has
+/// pushed a new frame, `LCL == SP` and `LCL` is not the frame of the innermost
+/// call. The latter rules out loops on a label at the entry of a function
+/// without locals, like `Sys.halt`. The sequence can be inlined or shared, so
+/// the return address is taken from the frame. A return is a jump to the
+/// return address of the innermost call.
+pub struct Profiler {
+    /// Sorted by address.
+    functions: Vec<(i16, String)>,
+    rom_cycles: Vec<u64>,
+    calls: Vec<u64>,
+    /// Call stacks as a tree, the root is the top level code.
+    nodes: Vec<StackNode>,
+    current: usize,
+    /// Return address and `LCL` of every call in progress.
+    frames: Vec<(i16, i16)>,
+}
+
+impl Profiler {
+    pub fn new(symbols: &SymbolTable, rom_size: usize) -> Self {
+        let functions = function_labels(symbols);
+        let root = StackNode {
+            function: None,
+            parent: 0,
+            children: HashMap::new(),
+            cycles: 0,
+        };
+
+        Self {
+            calls: vec![0; functions.len()],
+            functions,
+            rom_cycles: vec![0; rom_size],
+            nodes: vec![root],
+            current: 0,
+            frames: Vec::new(),
+        }
+    }
+
+    pub fn step(&mut self, executor: &mut HackExecutor) {
+        let pc = executor.pc;
+        if let Some(cycles) = self.rom_cycles.get_mut(pc as usize) {
+            *cycles += 1;
+        }
+        self.nodes[self.current].cycles += 1;
+
+        executor.step();
+
+        // Not only jumps: the bootstrap `call Sys.init` returns to the next
+        // address, which is `Sys.init` itself.
+        let new_pc = executor.pc;
+        match self.function_entry(new_pc) {
+            Some(function) if self.is_call(executor) => {
+                let sp = executor.read_memory(SP);
+                self.enter(function, executor.read_memory(sp.wrapping_sub(5)), sp)
+            }
+            _ if self.frames.last().map(|(ret, _)| *ret) == Some(new_pc) => self.leave(),
+            _ => (),
+        }
+    }
+
+    /// The same as `HackExecutor::run_until_halt`, but every step is profiled.
+    pub fn run_until_halt(&mut self, executor: &mut HackExecutor, max_cycles: u64) -> HaltReason {
+        for _ in 0..max_cycles {
+            if let Some(reason) = executor.halt_reason() {
+                return reason;
+            }
+            self.step(executor);
+        }
+
+        executor
+            .halt_reason()
+            .unwrap_or(HaltReason::BudgetExhausted)
+    }
+
+    pub fn rom_cycles(&self) -> &[u64] {
+        &self.rom_cycles
+    }
+
+    pub fn calls(&self, function: &str) -> u64 {
+        self.functions
+            .iter()
+            .position(|(_, name)| name == function)
+            .map_or(0, |idx| self.calls[idx])
+    }
+
+    /// Cycles spent in the code of every function, the most expensive first.
+    pub fn write_flat_profile(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut cycles = vec![0u64; self.functions.len() + 1];
+        for (address, c) in self.rom_cycles.iter().enumerate() {
+            let idx = self
+                .function_containing(address as i16)
+                .unwrap_or(self.functions.len());
+            cycles[idx] += c;
+        }
+        let total = cycles.iter().sum::<u64>().max(1);
+
+        let mut rows: Vec<(u64, &str)> = cycles
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| **c != 0)
+            .map(|(idx, c)| (*c, self.function_name(idx)))
+            .collect();
+        rows.sort_by(|a, b| b.0.cmp(&a.0).then(a.1.cmp(b.1)));
+
+        writeln!(out, "{:>12} {:>7}  function", "cycles", "%")?;
+        for (c, name) in rows {
+            let percent = c as f64 * 100.0 / total as f64;
+            writeln!(out, "{:>12} {:>6.2}%  {}", c, percent, name)?;
+        }
+
+        Ok(())
+    }
+
+    /// Calls of every function and the cycles spent in them including callees.
+    pub fn write_call_counts(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut inclusive = vec![0u64; self.functions.len()];
+        for (idx, node) in self.nodes.iter().enumerate() {
+            // Recursive calls repeat a function in the stack, it is counted once.
+            let mut seen = Vec::new();
+            for function in self.stack_of(idx) {
+                if !seen.contains(&function) {
+                    seen.push(function);
+                    inclusive[function] += node.cycles;
+                }
+            }
+        }
+
+        let mut rows: Vec<(u64, u64, &str)> = self
+            .calls
+            .iter()
+            .enumerate()
+            .filter(|(_, calls)| **calls != 0)
+            .map(|(idx, calls)| (*calls, inclusive[idx], self.functions[idx].1.as_str()))
+            .collect();
+        rows.sort_by(|a, b| b.1.cmp(&a.1).then(a.2.cmp(b.2)));
+
+        writeln!(out, "{:>10} {:>12}  function", "calls", "cycles")?;
+        for (calls, cycles, name) in rows {
+            writeln!(out, "{:>10} {:>12}  {}", calls, cycles, name)?;
+        }
+
+        Ok(())
+    }
+
+    /// `Sys.init;Main.main;Main.fibonacci 1234` lines for flamegraph tools.
+    pub fn write_folded_stacks(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut lines: Vec<(String, u64)> = self
+            .nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, node)| node.cycles != 0)
+            .map(|(idx, node)| {
+                let mut names: Vec<&str> = self
+                    .stack_of(idx)
+                    .into_iter()
+                    .map(|function| self.functions[function].1.as_str())
+                    .collect();
+                if names.is_empty() {
+                    names.push(TOP_LEVEL);
+                }
+                (names.join(";"), node.cycles)
+            })
+            .collect();
+        lines.sort();
+
+        for (stack, cycles) in lines {
+            writeln!(out, "{} {}", stack, cycles)?;
+        }
+
+        Ok(())
+    }
+
+    fn is_call(&self, executor: &HackExecutor) -> bool {
+        let lcl = executor.read_memory(LCL);
+        lcl == executor.read_memory(SP) && self.frames.last().map(|(_, frame)| *frame) != Some(lcl)
+    }
+
+    fn enter(&mut self, function: usize, return_address: i16, frame: i16) {
+        self.calls[function] += 1;
+        self.frames.push((return_address, frame));
+
+        let next = self.nodes.len();
+        let child = *self.nodes[self.current]
+            .children
+            .entry(function)
+            .or_insert(next);
+        if child == next {
+            self.nodes.push(StackNode {
+                function: Some(function),
+                parent: self.current,
+                children: HashMap::new(),
+                cycles: 0,
+            });
+        }
+        self.current = child;
+    }
+
+    fn leave(&mut self) {
+        self.frames.pop();
+        self.current = self.nodes[self.current].parent;
+    }
+
+    fn function_entry(&self, address: i16) -> Option<usize> {
+        self.functions
+            .binary_search_by_key(&address, |(a, _)| *a)
+            .ok()
+    }
+
+    fn function_containing(&self, address: i16) -> Option<usize> {
+        self.functions
+            .partition_point(|(a, _)| *a <= address)
+            .checked_sub(1)
+    }
+
+    fn function_name(&self, idx: usize) -> &str {
+        match self.functions.get(idx) {
+            Some((_, name)) => name,
+            None => TOP_LEVEL,
+        }
+    }
+
+    /// Functions from the outermost call to the node.
+    fn stack_of(&self, mut idx: usize) -> Vec<usize> {
+        let mut stack = Vec::new();
+        while let Some(function) = self.nodes[idx].function {
+            stack.push(function);
+            idx = self.nodes[idx].parent;
+        }
+        stack.reverse();
+        stack
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::str::from_utf8;
+
+    use super::*;
+
+    #[test]
+    fn fibonacci_profile_test() {
+        let src = fs::read_to_string("./priv/FibonacciElement.hack").unwrap();
+        let symbols =
+            SymbolTable::from_symbol_file(&fs::read("./priv/FibonacciElement.sym").unwrap())
+                .unwrap();
+        let mut executor = HackExecutor::new(HackExecutor::parse_program(&src).unwrap());
+        let mut profiler = Profiler::new(&symbols, executor.program().len());
+
+        assert_eq!(
+            profiler.run_until_halt(&mut executor, u64::MAX),
+            HaltReason::Halted
+        );
+        assert_eq!(executor.result(), 3);
+        assert_eq!(profiler.rom_cycles().iter().sum::<u64>(), executor.cycles());
+        assert_eq!(profiler.calls("Sys.init"), 1);
+        assert_eq!(profiler.calls("Main.fibonacci"), 9);
+
+        let mut out = Vec::new();
+        profiler.write_folded_stacks(&mut out).unwrap();
+        let folded = from_utf8(&out).unwrap();
+        assert!(folded.starts_with("<bootstrap> "));
+        assert!(folded.contains("\nSys.init;Main.fibonacci;Main.fibonacci;Main.fibonacci "));
+
+        let mut out = Vec::new();
+        profiler.write_call_counts(&mut out).unwrap();
+        let calls = from_utf8(&out).unwrap();
+        assert!(calls.lines().nth(1).unwrap().ends_with("  Sys.init"));
+    }
+
+    /// `Sys.halt` loops on a label at its entry with no locals, the back
+    /// edge jumps to a function entry with `LCL == SP` but is not a call.
+    #[test]
+    fn halt_loop_profile_test() {
+        let src = fs::read_to_string("./priv/Halt.hack").unwrap();
+        let symbols = SymbolTable::from_symbol_file(&fs::read("./priv/Halt.sym").unwrap()).unwrap();
+        let mut executor = HackExecutor::new(HackExecutor::parse_program(&src).unwrap());
+        let mut profiler = Profiler::new(&symbols, executor.program().len());
+
+        profiler.run_until_halt(&mut executor, 10000);
+        assert_eq!(profiler.calls("Sys.init"), 1);
+        assert_eq!(profiler.calls("Main.main"), 1);
+        assert_eq!(profiler.calls("Sys.halt"), 1);
+
+        let mut out = Vec::new();
+        profiler.write_folded_stacks(&mut out).unwrap();
+        let (halt, _) = function_labels(&symbols)
+            .into_iter()
+            .find(|(_, name)| name == "Sys.halt")
+            .unwrap();
+        let halt_cycles: u64 = profiler.rom_cycles()[halt as usize..].iter().sum();
+        assert_eq!(
+            from_utf8(&out).unwrap().lines().last().unwrap(),
+            format!("Sys.init;Sys.halt {}", halt_cycles)
+        );
+    }
+}
@@ -0,0 +1,94 @@
+use std::io;
+
+use crate::memory::{HackMemory, SCREEN_ADDRESS};
+
+pub const SCREEN_WIDTH: usize = 512;
+pub const SCREEN_HEIGHT: usize = 256;
+
+const WORDS_PER_ROW: usize = SCREEN_WIDTH / 16;
+
+/// Packs the framebuffer into rows of bytes, the most significant bit is the leftmost
+/// pixel and 1 is black. In RAM the least significant bit of a word is the leftmost one.
+pub fn framebuffer(memory: &HackMemory) -> Vec<u8> {
+    let mut out = Vec::with_capacity(SCREEN_WIDTH * SCREEN_HEIGHT / 8);
+
+    for word_idx in 0..(WORDS_PER_ROW * SCREEN_HEIGHT) {
+        let word = memory.get(SCREEN_ADDRESS + word_idx as i16) as u16;
+        out.push((word as u8).reverse_bits());
+        out.push(((word >> 8) as u8).reverse_bits());
+    }
+
+    out
+}
+
+/// Binary PBM (`P4`), it has the same pixel layout as `framebuffer`.
+pub fn write_pbm(memory: &HackMemory, out: &mut Vec<u8>) {
+    out.extend_from_slice(format!("P4\n{} {}\n", SCREEN_WIDTH, SCREEN_HEIGHT).as_bytes());
+    out.extend_from_slice(&framebuffer(memory));
+}
+
+#[cfg(feature = "png")]
+pub fn write_png(memory: &HackMemory, out: &mut Vec<u8>) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(out, SCREEN_WIDTH as u32, SCREEN_HEIGHT as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::One);
+
+    // In grayscale 0 is black.
+    let pixels: Vec<u8> = framebuffer(memory).iter().map(|b| !b).collect();
+
+    let mut writer = encoder.write_header().map_err(io::Error::other)?;
+    writer.write_image_data(&pixels).map_err(io::Error::other)
+}
+
+#[cfg(not(feature = "png"))]
+pub fn write_png(_memory: &HackMemory, _out: &mut Vec<u8>) -> io::Result<()> {
+    Err(io::Error::new(
+        io::ErrorKind::Unsupported,
+        "PNG support needs the png feature",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn pbm_test() {
+        let mut memory = HackMemory::new();
+        // The leftmost pixel of the first row and the rightmost one of the last row.
+        memory.set(SCREEN_ADDRESS, 1);
+        memory.set(SCREEN_ADDRESS + 8191, i16::MIN);
+
+        let mut out = Vec::new();
+        write_pbm(&memory, &mut out);
+
+        let header = b"P4\n512 256\n";
+        assert_eq!(&out[..header.len()], header);
+
+        let pixels = &out[header.len()..];
+        assert_eq!(pixels.len(), 512 * 256 / 8);
+        assert_eq!(pixels[0], 0b1000_0000);
+        assert_eq!(pixels[pixels.len() - 1], 0b0000_0001);
+        assert_eq!(pixels.iter().filter(|b| **b != 0).count(), 2);
+    }
+
+    #[cfg(feature = "png")]
+    #[test]
+    fn png_test() {
+        let mut memory = HackMemory::new();
+        memory.set(SCREEN_ADDRESS, 1);
+
+        let mut out = Vec::new();
+        write_png(&memory, &mut out).unwrap();
+
+        let mut reader = png::Decoder::new(std::io::Cursor::new(out))
+            .read_info()
+            .unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size().unwrap()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+
+        assert_eq!((info.width, info.height), (512, 256));
+        assert_eq!(pixels[0], 0b0111_1111);
+        assert_eq!(pixels[1], 0b1111_1111);
+    }
+}
@@ -0,0 +1,236 @@
+use std::fmt;
+use std::io::{self, Write};
+
+use file_context::SourceMap;
+use hack_assembler::SymbolTable;
+
+use crate::executor::HackExecutor;
+use crate::locations::STACK_START;
+use crate::memory::{ARG, LCL};
+use crate::profiler::function_labels;
+
+/// `push constant 0` the translator repeats for every local of a `function`:
+/// `@0 D=A @SP A=M M=D @SP M=M+1`.
+const PUSH_ZERO: [i16; 7] = [
+    0,
+    0b1110110000010000u16 as i16,
+    0,
+    0b1111110000100000u16 as i16,
+    0b1110001100001000u16 as i16,
+    0,
+    0b1111110111001000u16 as i16,
+];
+
+/// Deeper stacks are cut, a broken frame chain can loop.
+const MAX_FRAMES: usize = 1024;
+
+/// `call` saves the return address, LCL, ARG, THIS and THAT right below the callee's `LCL`.
+const FRAME_SIZE: i16 = 5;
+
+#[derive(Debug, PartialEq)]
+pub struct StackFrame {
+    /// `None` for code before the first function, e.g. the bootstrap, or out of ROM.
+    pub function: Option<String>,
+    /// The current instruction for the innermost frame, the return address for the others.
+    pub pc: i16,
+    pub arg: i16,
+    pub lcl: i16,
+    pub args: Vec<i16>,
+    pub locals: Vec<i16>,
+    /// `.vm` and `.jack` lines of `pc` from the source map.
+    pub location: Vec<String>,
+}
+
+impl fmt::Display for StackFrame {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let args: Vec<String> = self.args.iter().map(i16::to_string).collect();
+        write!(
+            f,
+            "{}({}) at {}",
+            self.function.as_deref().unwrap_or("??"),
+            args.join(", "),
+            self.pc
+        )?;
+        for location in &self.location {
+            write!(f, " {}", location)?;
+        }
+        if !self.locals.is_empty() {
+            let locals: Vec<String> = self.locals.iter().map(i16::to_string).collect();
+            write!(f, "\n    locals: {}", locals.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Walks the frames the VM calling convention leaves on the stack.
+pub struct CallStack {
+    /// Sorted by address.
+    functions: Vec<(i16, String)>,
+    source_map: Option<SourceMap>,
+}
+
+impl CallStack {
+    pub fn new(symbols: &SymbolTable) -> Self {
+        Self {
+            functions: function_labels(symbols),
+            source_map: None,
+        }
+    }
+
+    pub fn set_source_map(&mut self, source_map: SourceMap) -> &mut Self {
+        self.source_map = Some(source_map);
+        self
+    }
+
+    /// The innermost frame first. Values are read as they are, in the middle
+    /// of a `call` or `return` sequence the innermost frame can be wrong.
+    pub fn frames(&self, executor: &HackExecutor) -> Vec<StackFrame> {
+        let mut frames = Vec::new();
+        let mut pc = executor.pc;
+        let mut lcl = executor.read_memory(LCL);
+        let mut arg = executor.read_memory(ARG);
+
+        while frames.len() < MAX_FRAMES {
+            // A jump out of ROM still leaves the frame of the function it came from.
+            let in_rom = pc >= 0 && (pc as usize) < executor.program().len();
+            let function = self.function_containing(pc).filter(|_| in_rom);
+            let valid_frame =
+                (function.is_some() || !in_rom) && lcl >= STACK_START + FRAME_SIZE && arg <= lcl;
+
+            let (args, locals) = match function {
+                Some((entry, _)) if valid_frame => {
+                    let args_count = (lcl - FRAME_SIZE - arg).max(0);
+                    let locals_count = locals_count(executor.program(), *entry) as i16;
+                    (
+                        read(executor, arg, args_count),
+                        read(executor, lcl, locals_count),
+                    )
+                }
+                _ => (Vec::new(), Vec::new()),
+            };
+
+            frames.push(StackFrame {
+                function: function.map(|(_, name)| name.clone()),
+                pc,
+                arg,
+                lcl,
+                args,
+                locals,
+                location: self.location(pc),
+            });
+
+            if !valid_frame {
+                break;
+            }
+
+            pc = executor.read_memory(lcl - 5);
+            arg = executor.read_memory(lcl - 3);
+            lcl = executor.read_memory(lcl - 4);
+
+            // The bootstrap calls `Sys.init` without a frame of its own.
+            if lcl < STACK_START {
+                break;
+            }
+        }
+
+        frames
+    }
+
+    pub fn has_functions(&self) -> bool {
+        !self.functions.is_empty()
+    }
+
+    pub fn write_backtrace(&self, executor: &HackExecutor, out: &mut impl Write) -> io::Result<()> {
+        for (idx, frame) in self.frames(executor).iter().enumerate() {
+            writeln!(out, "#{:<3} {}", idx, frame)?;
+        }
+        Ok(())
+    }
+
+    fn function_containing(&self, address: i16) -> Option<&(i16, String)> {
+        let idx = self.functions.partition_point(|(a, _)| *a <= address);
+        idx.checked_sub(1).map(|idx| &self.functions[idx])
+    }
+
+    /// `.vm` and `.jack` lines of a ROM address, empty without a source map.
+    pub fn location(&self, pc: i16) -> Vec<String> {
+        match (&self.source_map, usize::try_from(pc)) {
+            (Some(source_map), Ok(pc)) => source_map
+                .lookup(pc)
+                .iter()
+                .map(ToString::to_string)
+                .collect(),
+            _ => Vec::new(),
+        }
+    }
+}
+
+/// The translator initializes locals right after the function label.
+fn locals_count(program: &[i16], entry: i16) -> usize {
+    program
+        .get(entry.max(0) as usize..)
+        .unwrap_or_default()
+        .chunks_exact(PUSH_ZERO.len())
+        .take_while(|chunk| *chunk == PUSH_ZERO)
+        .count()
+}
+
+fn read(executor: &HackExecutor, from: i16, count: i16) -> Vec<i16> {
+    (from..from.saturating_add(count))
+        .map(|addr| executor.read_memory(addr))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+
+    #[test]
+    fn fibonacci_backtrace_test() {
+        let src = fs::read_to_string("./priv/FibonacciElement.hack").unwrap();
+        let symbols =
+            SymbolTable::from_symbol_file(&fs::read("./priv/FibonacciElement.sym").unwrap())
+                .unwrap();
+        let mut executor = HackExecutor::new(HackExecutor::parse_program(&src).unwrap());
+        let call_stack = CallStack::new(&symbols);
+
+        // fibonacci(n) calls fibonacci(n - 2) first, the first four deep chain is 4 -> 3 -> 2 -> 0.
+        while executor.pc != 113 || call_stack.frames(&executor).len() < 5 {
+            executor.step();
+        }
+        let frames = call_stack.frames(&executor);
+        let names: Vec<_> = frames.iter().map(|f| f.function.as_deref()).collect();
+        let args: Vec<_> = frames.iter().map(|f| f.args.clone()).collect();
+
+        assert_eq!(
+            names,
+            vec![
+                Some("Main.fibonacci"),
+                Some("Main.fibonacci"),
+                Some("Main.fibonacci"),
+                Some("Main.fibonacci"),
+                Some("Sys.init")
+            ]
+        );
+        assert_eq!(args, vec![vec![0], vec![2], vec![3], vec![4], vec![]]);
+        assert_eq!(frames[0].pc, 113);
+
+        let mut out = Vec::new();
+        call_stack.write_backtrace(&executor, &mut out).unwrap();
+        assert!(String::from_utf8(out)
+            .unwrap()
+            .starts_with("#0   Main.fibonacci(0) at 113\n#1   Main.fibonacci(2) at "));
+    }
+
+    #[test]
+    fn locals_count_test() {
+        let mut program = PUSH_ZERO.repeat(2);
+        program.extend([0, 0b1110101010000111u16 as i16]);
+
+        assert_eq!(locals_count(&program, 0), 2);
+        assert_eq!(locals_count(&program, 7), 1);
+        assert_eq!(locals_count(&program, 14), 0);
+    }
+}
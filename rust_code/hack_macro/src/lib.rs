@@ -42,9 +42,17 @@ impl ParsedEnumField {
         format!("b\"{v}\" => Self::{i}", i = self.ident, v = self.lit)
     }
 
+    fn try_from_symbol_format(&self) -> String {
+        format!("b\"{v}\" => Some(Self::{i})", i = self.ident, v = self.lit)
+    }
+
     fn as_bytes_binary_format(&self) -> String {
         format!("Self::{i} => b\"{v}\"", i = self.ident, v = self.lit)
     }
+
+    fn try_from_binary_format(&self) -> String {
+        format!("b\"{v}\" => Some(Self::{i})", i = self.ident, v = self.lit)
+    }
 }
 
 fn parse_hack_attrs<'a>(attrs: &'a Vec<Attribute>) -> impl Iterator<Item = &'a Attribute> {
@@ -281,6 +289,9 @@ pub fn declar_binary_methods(input: proc_macro::TokenStream) -> proc_macro::Toke
     let as_bytes_body =
         parsed_enums_to_token_stream(&enums, ParsedEnumField::as_bytes_binary_format, true);
 
+    let try_from_bytes_body =
+        parsed_enums_to_token_stream(&enums, ParsedEnumField::try_from_binary_format, true);
+
     let as_bytes_body_size: TokenStream = enums[0]
         .lit_len
         .to_string()
@@ -292,6 +303,11 @@ pub fn declar_binary_methods(input: proc_macro::TokenStream) -> proc_macro::Toke
             pub const fn as_bytes_const(&self) -> &'static [u8; #as_bytes_body_size] {
                 match self { #as_bytes_body }
             }
+
+            #[allow(unreachable_patterns)]
+            pub fn try_from_bytes(bytes: &[u8]) -> Option<Self> {
+                match bytes { #try_from_bytes_body, _ => None }
+            }
         }
     };
     TokenStream::from(expanded).into()
@@ -317,6 +333,8 @@ pub fn declar_as_a_constant(input: proc_macro::TokenStream) -> proc_macro::Token
         parsed_enums_to_token_stream(&enums, ParsedEnumField::from_static_symbol_format, false);
     let to_static =
         parsed_enums_to_token_stream(&enums, ParsedEnumField::to_static_symbol_format, true);
+    let try_from =
+        parsed_enums_to_token_stream(&enums, ParsedEnumField::try_from_symbol_format, true);
 
     let expanded = quote! {
         impl #ident {
@@ -327,6 +345,10 @@ pub fn declar_as_a_constant(input: proc_macro::TokenStream) -> proc_macro::Token
             pub const fn __as_static_symbols(&self) -> &'static [u8] {
                 match self { #to_static }
             }
+
+            pub fn try_from_symbols(symbols: &[u8]) -> Option<Self> {
+                match symbols { #try_from, _ => None }
+            }
         }
 
         impl<'a> SymbolicElem<'a> for #ident {
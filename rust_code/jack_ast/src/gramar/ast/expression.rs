@@ -1,74 +1,116 @@
+use file_context::FileSpan;
+
 use super::{term::JackTerm, JackConstantTerm, JackTermPayload};
 use crate::tokens::{JackSymbol, JackToken};
 
 #[derive(Debug, PartialEq, Default)]
 pub struct JackExpression {
     pub term: JackTerm,
-    pub extra: Vec<(JackSymbol, JackTerm)>,
+    /// Operators with their spans and right operands.
+    pub extra: Vec<(JackSymbol, Option<FileSpan>, JackTerm)>,
     pub size: usize,
 }
 
+/// What is wrong with an expression, `idx` is of the token it is found at
+/// and is past the end when the expression ends too early.
+#[derive(Debug, PartialEq)]
+pub struct JackExpressionError {
+    pub idx: usize,
+    pub message: &'static str,
+}
+
+impl JackExpressionError {
+    pub fn new(idx: usize, message: &'static str) -> Self {
+        Self { idx, message }
+    }
+
+    /// The error of an expression that starts at `offset` of the outer one.
+    pub fn shift(mut self, offset: usize) -> Self {
+        self.idx += offset;
+        self
+    }
+}
+
 impl JackExpression {
     pub fn is_this(&self) -> bool {
         self.extra.len() == 0 && self.term.payload == JackTermPayload::Const(JackConstantTerm::This)
     }
 
-    pub fn new(tokens: &mut [JackToken], tokens_count: usize) -> Self {
-        let term = JackTerm::new(tokens, tokens_count);
+    pub fn new(
+        tokens: &mut [JackToken],
+        spans: &[Option<FileSpan>],
+        tokens_count: usize,
+    ) -> Result<Self, JackExpressionError> {
+        let term = JackTerm::new(tokens, spans, tokens_count)?;
         let mut size = term.size;
         let mut extra = Vec::new();
 
-        while size != tokens_count {
+        while size < tokens_count {
             if let JackToken::Symbol(s) = tokens[size] {
                 if !s.is_op() {
-                    panic!("wrong op")
+                    return Err(JackExpressionError::new(size, "expected an operator"));
                 }
 
+                let span = spans[size];
                 size += 1;
-                let new_term = JackTerm::new(&mut tokens[size..], tokens_count - size);
+                let new_term =
+                    JackTerm::new(&mut tokens[size..], &spans[size..], tokens_count - size)
+                        .map_err(|err| err.shift(size))?;
                 size += new_term.size;
 
-                extra.push((s, new_term))
+                extra.push((s, span, new_term))
             } else {
-                panic!("wrong op")
+                return Err(JackExpressionError::new(size, "expected an operator"));
             };
         }
 
-        Self { term, extra, size }
+        Ok(Self { term, extra, size })
     }
 
-    pub fn extract_from_round_bracket(tokens: &mut [JackToken]) -> JackExpression {
-        let mut brakets = 1;
-        let mut i = 0;
-
-        while brakets != 0 {
-            match tokens[i] {
-                JackToken::Symbol(JackSymbol::OpenRoundBracket) => brakets += 1,
-                JackToken::Symbol(JackSymbol::CloseRoundBracket) => brakets -= 1,
-                _ => (),
-            };
-
-            i += 1;
-        }
+    pub fn extract_from_round_bracket(
+        tokens: &mut [JackToken],
+        spans: &[Option<FileSpan>],
+    ) -> Result<JackExpression, JackExpressionError> {
+        let i = Self::closing_bracket(
+            tokens,
+            JackSymbol::OpenRoundBracket,
+            JackSymbol::CloseRoundBracket,
+        )
+        .ok_or(JackExpressionError::new(tokens.len(), "expected `)`"))?;
+
+        Self::new(&mut tokens[..i], &spans[..i], i - 1)
+    }
 
-        Self::new(&mut tokens[..i], i - 1)
+    pub fn extract_from_square_bracket(
+        tokens: &mut [JackToken],
+        spans: &[Option<FileSpan>],
+    ) -> Result<JackExpression, JackExpressionError> {
+        let i = Self::closing_bracket(
+            tokens,
+            JackSymbol::OpenSquareBracket,
+            JackSymbol::CloseSquareBracket,
+        )
+        .ok_or(JackExpressionError::new(tokens.len(), "expected `]`"))?;
+
+        Self::new(&mut tokens[..i], &spans[..i], i - 1)
     }
 
-    pub fn extract_from_square_bracket(tokens: &mut [JackToken]) -> JackExpression {
+    /// Tokens up to and with the bracket closing an already open one.
+    fn closing_bracket(tokens: &[JackToken], open: JackSymbol, close: JackSymbol) -> Option<usize> {
         let mut brakets = 1;
         let mut i = 0;
 
         while brakets != 0 {
-            match tokens[i] {
-                JackToken::Symbol(JackSymbol::OpenSquareBracket) => brakets += 1,
-                JackToken::Symbol(JackSymbol::CloseSquareBracket) => brakets -= 1,
+            match tokens.get(i)? {
+                JackToken::Symbol(s) if *s == open => brakets += 1,
+                JackToken::Symbol(s) if *s == close => brakets -= 1,
                 _ => (),
             };
 
             i += 1;
         }
 
-        Self::new(&mut tokens[..i], i - 1)
+        Some(i)
     }
 }
 
@@ -85,20 +127,31 @@ mod tests {
 
     #[tokio::test]
     async fn simple_expression_test() {
-        let mut data = bytes_to_tokens(b"a + 7 * 4").await;
+        let (mut data, spans) = bytes_to_tokens(b"a + 7 * 4").await;
         let l = data.len();
-        let expr = JackExpression::new(&mut data, l);
-
+        let expr = JackExpression::new(&mut data, &spans, l).unwrap();
+
+        let at = |symbol| Some(FileSpan::new(0, symbol));
+        let located = |term, symbol| JackTerm {
+            span: at(symbol),
+            name_span: at(symbol),
+            ..term
+        };
         assert_eq!(
             expr,
             JackExpression {
                 size: l,
-                term: JackTerm::new_ident(JackVariableName(b"a".to_vec())),
+                term: located(JackTerm::new_ident(JackVariableName(b"a".to_vec())), 0),
                 extra: vec![
-                    (JackSymbol::Plus, JackTerm::new_int(JackInt(b"7".to_vec()))),
+                    (
+                        JackSymbol::Plus,
+                        at(2),
+                        located(JackTerm::new_int(JackInt(b"7".to_vec())), 4)
+                    ),
                     (
                         JackSymbol::Multiply,
-                        JackTerm::new_int(JackInt(b"4".to_vec()))
+                        at(6),
+                        located(JackTerm::new_int(JackInt(b"4".to_vec())), 8)
                     )
                 ]
             }
@@ -107,18 +160,31 @@ mod tests {
 
     #[tokio::test]
     async fn expression_test() {
-        let mut data = bytes_to_tokens(b"art + Main.calc(12 + 46, \"hi\") * 4 / a[24 - k]").await;
+        let (mut data, spans) =
+            bytes_to_tokens(b"art + Main.calc(12 + 46, \"hi\") * 4 / a[24 - k]").await;
         let l = data.len();
-        let expr = JackExpression::new(&mut data, l);
+        let expr = JackExpression::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(expr.size, l);
     }
 
-    async fn bytes_to_tokens(expr: &'static [u8]) -> Vec<JackToken> {
+    #[tokio::test]
+    async fn expression_error_test() {
+        let (mut data, spans) = bytes_to_tokens(b"a + (7 * 4").await;
+        let l = data.len();
+        let err = JackExpression::new(&mut data, &spans, l).unwrap_err();
+        assert_eq!(err, JackExpressionError::new(l, "expected `)`"));
+
+        let (mut data, spans) = bytes_to_tokens(b"a 7").await;
+        let err = JackExpression::new(&mut data, &spans, 2).unwrap_err();
+        assert_eq!(err, JackExpressionError::new(1, "expected an operator"));
+    }
+
+    async fn bytes_to_tokens(expr: &'static [u8]) -> (Vec<JackToken>, Vec<Option<FileSpan>>) {
         let tokenizer = JackTokenizer::from_slice(expr, true);
         tokenizer
-            .map(|x| x.payload)
-            .collect::<Vec<JackToken>>()
+            .map(|x| (x.payload, x.span))
+            .unzip::<_, _, Vec<_>, Vec<_>>()
             .await
     }
 }
@@ -1,6 +1,8 @@
+use file_context::FileSpan;
+
 use crate::tokens::{JackSymbol, JackToken};
 
-use super::expression::JackExpression;
+use super::expression::{JackExpression, JackExpressionError};
 
 #[derive(Debug, PartialEq)]
 pub struct JackExpressions {
@@ -9,21 +11,27 @@ pub struct JackExpressions {
 }
 
 impl JackExpressions {
-    pub fn extract_from_round_bracket(tokens: &mut [JackToken]) -> Self {
+    pub fn extract_from_round_bracket(
+        tokens: &mut [JackToken],
+        spans: &[Option<FileSpan>],
+    ) -> Result<Self, JackExpressionError> {
         let mut brakets = 1;
         let mut i = 0;
         let mut expressions = Vec::new();
         let mut expression_cursor = i;
 
         while brakets != 0 {
-            match tokens[i] {
-                JackToken::Symbol(JackSymbol::OpenRoundBracket) => brakets += 1,
-                JackToken::Symbol(JackSymbol::CloseRoundBracket) => brakets -= 1,
-                JackToken::Symbol(JackSymbol::Comma) => {
+            match tokens.get(i) {
+                None => return Err(JackExpressionError::new(i, "expected `)`")),
+                Some(JackToken::Symbol(JackSymbol::OpenRoundBracket)) => brakets += 1,
+                Some(JackToken::Symbol(JackSymbol::CloseRoundBracket)) => brakets -= 1,
+                Some(JackToken::Symbol(JackSymbol::Comma)) => {
                     let expr = JackExpression::new(
                         &mut tokens[expression_cursor..i],
+                        &spans[expression_cursor..i],
                         i - expression_cursor,
-                    );
+                    )
+                    .map_err(|err| err.shift(expression_cursor))?;
 
                     expression_cursor = i + 1;
                     expressions.push(expr)
@@ -35,15 +43,19 @@ impl JackExpressions {
         }
 
         if expression_cursor + 1 < i {
-            let expr =
-                JackExpression::new(&mut tokens[expression_cursor..i], i - expression_cursor - 1);
+            let expr = JackExpression::new(
+                &mut tokens[expression_cursor..i],
+                &spans[expression_cursor..i],
+                i - expression_cursor - 1,
+            )
+            .map_err(|err| err.shift(expression_cursor))?;
             expressions.push(expr)
         }
 
-        JackExpressions {
+        Ok(JackExpressions {
             data: expressions,
             size: i - 1,
-        }
+        })
     }
 }
 
@@ -57,9 +69,9 @@ mod tests {
 
     #[tokio::test]
     async fn simple_expressions_test() {
-        let mut data = bytes_to_tokens(b"12 + c, \"hi\")").await;
+        let (mut data, spans) = bytes_to_tokens(b"12 + c, \"hi\")").await;
         let l = data.len();
-        let expressions = JackExpressions::extract_from_round_bracket(&mut data);
+        let expressions = JackExpressions::extract_from_round_bracket(&mut data, &spans).unwrap();
 
         assert_eq!(expressions.size, l - 1);
         assert_eq!(expressions.data.len(), 2);
@@ -67,19 +79,19 @@ mod tests {
 
     #[tokio::test]
     async fn nasted_expressions_test() {
-        let mut data = bytes_to_tokens(b"12 + (c * 10), \"hi\")").await;
+        let (mut data, spans) = bytes_to_tokens(b"12 + (c * 10), \"hi\")").await;
         let l = data.len();
-        let expressions = JackExpressions::extract_from_round_bracket(&mut data);
+        let expressions = JackExpressions::extract_from_round_bracket(&mut data, &spans).unwrap();
 
         assert_eq!(expressions.size, l - 1);
         assert_eq!(expressions.data.len(), 2);
     }
 
-    async fn bytes_to_tokens(expr: &'static [u8]) -> Vec<JackToken> {
+    async fn bytes_to_tokens(expr: &'static [u8]) -> (Vec<JackToken>, Vec<Option<FileSpan>>) {
         let tokenizer = JackTokenizer::from_slice(expr, true);
         tokenizer
-            .map(|x| x.payload)
-            .collect::<Vec<JackToken>>()
+            .map(|x| (x.payload, x.span))
+            .unzip::<_, _, Vec<_>, Vec<_>>()
             .await
     }
 }
@@ -1,3 +1,5 @@
+use file_context::FileSpan;
+
 use super::{assign::JackLet, call::JackDo, condition::JackIf, cycle::JackWhile, ret::JackReturn};
 
 #[derive(Debug, PartialEq)]
@@ -10,4 +12,5 @@ pub enum JackStatement {
 }
 
 #[derive(Debug, PartialEq, Default)]
-pub struct JackStatements(pub Vec<Box<JackStatement>>);
+/// Statements and the span of the keyword each one starts with.
+pub struct JackStatements(pub Vec<Box<JackStatement>>, pub Vec<Option<FileSpan>>);
@@ -1,3 +1,5 @@
+use file_context::FileSpan;
+
 use crate::gramar::units::{JackSubroutineType, JackType, JackVariableName};
 
 use super::{declaration::JackDeclaration, statements::JackStatements};
@@ -9,4 +11,6 @@ pub struct JackSubroutine {
     pub key: JackSubroutineType,
     pub vars: Vec<JackDeclaration>,
     pub statements: JackStatements,
+    /// Span of the name.
+    pub span: Option<FileSpan>,
 }
@@ -1,16 +1,25 @@
 use std::mem;
 
+use file_context::FileSpan;
+
 use crate::{
     gramar::units::{JackVariableName, JackVariableNameStyle},
     tokens::{JackInt, JackKeyword, JackString, JackSymbol, JackToken},
 };
 
-use super::{expression::JackExpression, expressions::JackExpressions};
+use super::{
+    expression::{JackExpression, JackExpressionError},
+    expressions::JackExpressions,
+};
 
 #[derive(Debug, PartialEq, Default)]
 pub struct JackTerm {
     pub payload: JackTermPayload,
     pub size: usize,
+    /// The first token.
+    pub span: Option<FileSpan>,
+    /// The subroutine name of a `Foo.bar()` call, the first token otherwise.
+    pub name_span: Option<FileSpan>,
 }
 
 #[derive(Debug, PartialEq)]
@@ -45,6 +54,7 @@ impl JackTerm {
         Self {
             payload: JackTermPayload::Ident(token),
             size: 1,
+            ..Default::default()
         }
     }
 
@@ -52,6 +62,7 @@ impl JackTerm {
         Self {
             payload: JackTermPayload::String(data),
             size: 1,
+            ..Default::default()
         }
     }
 
@@ -59,29 +70,43 @@ impl JackTerm {
         Self {
             payload: JackTermPayload::Int(data),
             size: 1,
+            ..Default::default()
         }
     }
 
-    pub fn new(tokens: &mut [JackToken], tokens_count: usize) -> Self {
+    /// The term at the start of `tokens`, `spans` are of the tokens.
+    pub fn new(
+        tokens: &mut [JackToken],
+        spans: &[Option<FileSpan>],
+        tokens_count: usize,
+    ) -> Result<Self, JackExpressionError> {
+        if tokens_count == 0 {
+            return Err(JackExpressionError::new(0, "expected an expression"));
+        }
+
         let mut v = Vec::new();
         let head = &mut tokens[0] as *mut JackToken;
 
-        match unsafe { &mut *head } {
+        let mut term = match unsafe { &mut *head } {
             JackToken::Keyword(JackKeyword::True) => Self {
                 payload: JackTermPayload::Const(JackConstantTerm::True),
                 size: 1,
+                ..Default::default()
             },
             JackToken::Keyword(JackKeyword::False) => Self {
                 payload: JackTermPayload::Const(JackConstantTerm::False),
                 size: 1,
+                ..Default::default()
             },
             JackToken::Keyword(JackKeyword::Null) => Self {
                 payload: JackTermPayload::Const(JackConstantTerm::Null),
                 size: 1,
+                ..Default::default()
             },
             JackToken::Keyword(JackKeyword::This) => Self {
                 payload: JackTermPayload::Const(JackConstantTerm::This),
                 size: 1,
+                ..Default::default()
             },
             JackToken::StringLiteral(s) => {
                 mem::swap(&mut s.0, &mut v);
@@ -92,82 +117,107 @@ impl JackTerm {
                 Self::new_int(JackInt(v))
             }
             JackToken::Symbol(JackSymbol::OpenRoundBracket) => {
-                let expr = JackExpression::extract_from_round_bracket(&mut tokens[1..]);
+                let expr =
+                    JackExpression::extract_from_round_bracket(&mut tokens[1..], &spans[1..])
+                        .map_err(|err| err.shift(1))?;
                 let size = expr.size + 2;
                 Self {
                     payload: JackTermPayload::Expression(Box::new(expr)),
                     size,
+                    ..Default::default()
                 }
             }
             JackToken::Symbol(symbol) if symbol.is_unary_op() => {
-                let term = JackTerm::new(&mut tokens[1..], tokens_count - 1);
+                let term = JackTerm::new(&mut tokens[1..], &spans[1..], tokens_count - 1)
+                    .map_err(|err| err.shift(1))?;
                 let size = term.size + 1;
                 Self {
                     payload: JackTermPayload::Unary(*symbol, Box::new(term)),
                     size,
+                    ..Default::default()
                 }
             }
             s => {
-                if let Some(new_ident) =
-                    JackVariableName::from_token(s, JackVariableNameStyle::Utf8)
-                {
-                    match &tokens.get(1) {
-                        Some(JackToken::Symbol(JackSymbol::OpenRoundBracket)) => {
-                            let expr =
-                                JackExpressions::extract_from_round_bracket(&mut tokens[2..]);
-                            let size = expr.size + 3;
-                            Self {
-                                payload: JackTermPayload::MethodCall(new_ident, expr),
-                                size,
-                            }
+                let Some(new_ident) = JackVariableName::from_token(s, JackVariableNameStyle::Utf8)
+                else {
+                    return Err(JackExpressionError::new(0, "expected an expression"));
+                };
+
+                match &tokens.get(1) {
+                    Some(JackToken::Symbol(JackSymbol::OpenRoundBracket)) => {
+                        let expr = JackExpressions::extract_from_round_bracket(
+                            &mut tokens[2..],
+                            &spans[2..],
+                        )
+                        .map_err(|err| err.shift(2))?;
+                        let size = expr.size + 3;
+                        Self {
+                            payload: JackTermPayload::MethodCall(new_ident, expr),
+                            size,
+                            ..Default::default()
                         }
-                        Some(JackToken::Symbol(JackSymbol::OpenSquareBracket)) => {
-                            let expr =
-                                JackExpression::extract_from_square_bracket(&mut tokens[2..]);
-                            let size = expr.size + 3;
-                            Self {
-                                payload: JackTermPayload::ArrayElem(new_ident, Box::new(expr)),
-                                size,
-                            }
+                    }
+                    Some(JackToken::Symbol(JackSymbol::OpenSquareBracket)) => {
+                        let expr = JackExpression::extract_from_square_bracket(
+                            &mut tokens[2..],
+                            &spans[2..],
+                        )
+                        .map_err(|err| err.shift(2))?;
+                        let size = expr.size + 3;
+                        Self {
+                            payload: JackTermPayload::ArrayElem(new_ident, Box::new(expr)),
+                            size,
+                            ..Default::default()
+                        }
+                    }
+                    Some(JackToken::Symbol(JackSymbol::Period)) => {
+                        if tokens_count < 5 {
+                            return Err(JackExpressionError::new(2, "expected a subroutine call"));
                         }
-                        Some(JackToken::Symbol(JackSymbol::Period)) => {
-                            if tokens_count < 5 {
-                                panic!("Unknown term");
-                            }
 
-                            let maybe_function_name = JackVariableName::from_token(
-                                &mut tokens[2],
-                                JackVariableNameStyle::CamelCase,
-                            );
-
-                            match (maybe_function_name, &tokens[3]) {
-                                (
-                                    Some(function_name),
-                                    JackToken::Symbol(JackSymbol::OpenRoundBracket),
-                                ) => {
-                                    let expr = JackExpressions::extract_from_round_bracket(
-                                        &mut tokens[4..],
-                                    );
-                                    let size = expr.size + 5;
-                                    Self {
-                                        payload: JackTermPayload::FunctionCall(
-                                            new_ident,
-                                            function_name,
-                                            expr,
-                                        ),
-                                        size,
-                                    }
+                        let maybe_function_name = JackVariableName::from_token(
+                            &mut tokens[2],
+                            JackVariableNameStyle::CamelCase,
+                        );
+
+                        match (maybe_function_name, &tokens[3]) {
+                            (
+                                Some(function_name),
+                                JackToken::Symbol(JackSymbol::OpenRoundBracket),
+                            ) => {
+                                let expr = JackExpressions::extract_from_round_bracket(
+                                    &mut tokens[4..],
+                                    &spans[4..],
+                                )
+                                .map_err(|err| err.shift(4))?;
+                                let size = expr.size + 5;
+                                Self {
+                                    payload: JackTermPayload::FunctionCall(
+                                        new_ident,
+                                        function_name,
+                                        expr,
+                                    ),
+                                    size,
+                                    name_span: spans[2],
+                                    ..Default::default()
                                 }
-                                _ => unimplemented!(),
+                            }
+                            _ => {
+                                return Err(JackExpressionError::new(
+                                    2,
+                                    "expected a subroutine call",
+                                ))
                             }
                         }
-                        _ => Self::new_ident(new_ident),
                     }
-                } else {
-                    panic!("Not valid term")
+                    _ => Self::new_ident(new_ident),
                 }
             }
-        }
+        };
+
+        term.span = spans[0];
+        term.name_span = term.name_span.or(term.span);
+        Ok(term)
     }
 }
 
@@ -181,9 +231,9 @@ mod tests {
 
     #[tokio::test]
     async fn function_call_test() {
-        let mut data = bytes_to_tokens(b"Main.calc(12 + (46 + c), \"hi\")").await;
+        let (mut data, spans) = bytes_to_tokens(b"Main.calc(12 + (46 + c), \"hi\")").await;
         let l = data.len();
-        let term = JackTerm::new(&mut data, l);
+        let term = JackTerm::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(term.size, l);
 
@@ -198,9 +248,9 @@ mod tests {
 
     #[tokio::test]
     async fn function_call_zero_args_test() {
-        let mut data = bytes_to_tokens(b"Main.calc()").await;
+        let (mut data, spans) = bytes_to_tokens(b"Main.calc()").await;
         let l = data.len();
-        let term = JackTerm::new(&mut data, l);
+        let term = JackTerm::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(term.size, l);
 
@@ -215,9 +265,9 @@ mod tests {
 
     #[tokio::test]
     async fn function_call_one_arg_test() {
-        let mut data = bytes_to_tokens(b"Main.calc(5)").await;
+        let (mut data, spans) = bytes_to_tokens(b"Main.calc(5)").await;
         let l = data.len();
-        let term = JackTerm::new(&mut data, l);
+        let term = JackTerm::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(term.size, l);
 
@@ -232,9 +282,9 @@ mod tests {
 
     #[tokio::test]
     async fn method_call_test() {
-        let mut data = bytes_to_tokens(b"calc(5, (1488))").await;
+        let (mut data, spans) = bytes_to_tokens(b"calc(5, (1488))").await;
         let l = data.len();
-        let term = JackTerm::new(&mut data, l);
+        let term = JackTerm::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(term.size, l);
 
@@ -247,9 +297,9 @@ mod tests {
 
     #[tokio::test]
     async fn array_call_test() {
-        let mut data = bytes_to_tokens(b"myArray[450 - (l / 9)]").await;
+        let (mut data, spans) = bytes_to_tokens(b"myArray[450 - (l / 9)]").await;
         let l = data.len();
-        let term = JackTerm::new(&mut data, l);
+        let term = JackTerm::new(&mut data, &spans, l).unwrap();
 
         assert_eq!(term.size, l);
 
@@ -260,11 +310,11 @@ mod tests {
         }
     }
 
-    async fn bytes_to_tokens(expr: &'static [u8]) -> Vec<JackToken> {
+    async fn bytes_to_tokens(expr: &'static [u8]) -> (Vec<JackToken>, Vec<Option<FileSpan>>) {
         let tokenizer = JackTokenizer::from_slice(expr, true);
         tokenizer
-            .map(|x| x.payload)
-            .collect::<Vec<JackToken>>()
+            .map(|x| (x.payload, x.span))
+            .unzip::<_, _, Vec<_>, Vec<_>>()
             .await
     }
 }
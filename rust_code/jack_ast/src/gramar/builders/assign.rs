@@ -1,6 +1,6 @@
 use std::mem;
 
-use file_context::FileContext;
+use file_context::{FileContext, FileSpan};
 
 use crate::gramar::ast::{JackExpression, JackLet, JackStatement, JackTerm, JackTermPayload};
 use crate::tokens::JackSymbol;
@@ -26,6 +26,7 @@ pub struct JackLetBuilder {
     stage: JackLetStage,
     assign: Option<Box<JackStatement>>,
     acc: Vec<JackToken>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackLetBuilder {
@@ -44,6 +45,7 @@ impl Default for JackLetBuilder {
             stage: Default::default(),
             assign: Some(Box::new(JackStatement::Let(Default::default()))),
             acc: Default::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -53,6 +55,7 @@ impl JackAstBuilder for JackLetBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackLetStage::AwaitLet, JackToken::Keyword(JackKeyword::Let)) => {
                 self.stage = JackLetStage::AwaitEq;
@@ -60,16 +63,23 @@ impl JackAstBuilder for JackLetBuilder {
             }
             (JackLetStage::AwaitEq, JackToken::Symbol(JackSymbol::Eq)) => {
                 let s = self.acc.len();
-                let term = JackTerm::new(&mut self.acc, s);
+                let term = JackTerm::new(&mut self.acc, &self.spans, s)
+                    .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
 
                 match term.payload {
                     JackTermPayload::Ident(_) => (),
                     JackTermPayload::ArrayElem(_, _) => (),
-                    _ => panic!("wrong assign term"),
+                    _ => {
+                        return Err(JackAnalizerError::InvalidExpression(
+                            self.spans.first().copied().flatten(),
+                            "expected a variable or an array element",
+                        ))
+                    }
                 }
 
                 self.unwrap_assign().variable = term;
                 unsafe { self.acc.set_len(0) };
+                self.spans.clear();
                 self.stage = JackLetStage::AwaitSemicolon;
                 Ok(JackAstBuilderResponse::Continue)
             }
@@ -77,12 +87,16 @@ impl JackAstBuilder for JackLetBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
             (JackLetStage::AwaitSemicolon, JackToken::Symbol(JackSymbol::Semicolon)) => {
                 let s = self.acc.len();
-                self.unwrap_assign().expression = JackExpression::new(&mut self.acc, s);
+                self.unwrap_assign().expression =
+                    JackExpression::new(&mut self.acc, &self.spans, s)
+                        .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
                 unsafe { self.acc.set_len(0) };
+                self.spans.clear();
                 self.stage = JackLetStage::Ready;
                 Ok(JackAstBuilderResponse::Ready)
             }
@@ -90,9 +104,12 @@ impl JackAstBuilder for JackLetBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
-            _ => panic!("let error {:?}", token),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
@@ -10,10 +10,25 @@ pub enum JackAstBuilderResponse {
     Move(*mut dyn JackAstBuilder),
 }
 
+/// How a scope goes on after an error in it or in one of its children.
+pub enum JackAstRecovery {
+    /// The scope can not go on from the token, its parent is asked.
+    Parent,
+    /// The token is fed to the scope.
+    Feed,
+    /// The token is dropped and the next one is fed to the scope.
+    Skip,
+}
+
 pub trait JackAstBuilder {
     fn feed(
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError>;
     fn is_ready(&self) -> bool;
+    /// Asked with the tokens after an error, from the innermost scope out,
+    /// the scopes above the one that goes on are dropped.
+    fn recover(&mut self, _token: &JackToken) -> JackAstRecovery {
+        JackAstRecovery::Parent
+    }
 }
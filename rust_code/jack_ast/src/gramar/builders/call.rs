@@ -1,6 +1,6 @@
 use std::mem;
 
-use file_context::FileContext;
+use file_context::{FileContext, FileSpan};
 
 use crate::gramar::ast::{JackDo, JackStatement, JackTerm, JackTermPayload};
 use crate::tokens::JackSymbol;
@@ -25,6 +25,7 @@ pub struct JackDoBuilder {
     stage: JackDoStage,
     assign: Option<Box<JackStatement>>,
     acc: Vec<JackToken>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackDoBuilder {
@@ -43,6 +44,7 @@ impl Default for JackDoBuilder {
             stage: Default::default(),
             assign: Some(Box::new(JackStatement::Do(Default::default()))),
             acc: Default::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -52,6 +54,7 @@ impl JackAstBuilder for JackDoBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackDoStage::AwaitDo, JackToken::Keyword(JackKeyword::Do)) => {
                 self.stage = JackDoStage::AwaitSemicolon;
@@ -59,12 +62,18 @@ impl JackAstBuilder for JackDoBuilder {
             }
             (JackDoStage::AwaitSemicolon, JackToken::Symbol(JackSymbol::Semicolon)) => {
                 let s = self.acc.len();
-                let term = JackTerm::new(&mut self.acc, s);
+                let term = JackTerm::new(&mut self.acc, &self.spans, s)
+                    .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
 
                 match term.payload {
                     JackTermPayload::MethodCall(_, _) => (),
                     JackTermPayload::FunctionCall(_, _, _) => (),
-                    _ => panic!("wrong call term"),
+                    _ => {
+                        return Err(JackAnalizerError::InvalidExpression(
+                            self.spans.first().copied().flatten(),
+                            "expected a subroutine call",
+                        ))
+                    }
                 }
 
                 self.unwrap_assign().call = term;
@@ -75,9 +84,12 @@ impl JackAstBuilder for JackDoBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
-            _ => panic!("let error {:?}", token),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
@@ -11,7 +11,7 @@ use crate::{
 };
 
 use super::{
-    behaviour::{JackAstBuilder, JackAstBuilderResponse},
+    behaviour::{JackAstBuilder, JackAstBuilderResponse, JackAstRecovery},
     declaration::JackDeclarationBuilder,
     errors::JackAnalizerError,
     subroutine::JackSubroutineBuilder,
@@ -41,6 +41,18 @@ impl JackClassBuilder {
         self.class
     }
 
+    /// The class with the unfinished subroutine as far as it got.
+    pub fn build_partial(mut self) -> JackClass {
+        self.save_broken_subroutine();
+        self.class
+    }
+
+    fn save_broken_subroutine(&mut self) {
+        if let Some(subroutine) = self.subroutine.take() {
+            self.class.subroutines.push(subroutine.build_partial());
+        }
+    }
+
     pub fn save_old_var(&mut self) {
         let mut old_var = None;
         mem::swap(&mut old_var, &mut self.var);
@@ -128,11 +140,50 @@ impl JackAstBuilder for JackClassBuilder {
                 self.save_old_subroutine();
                 Ok(JackAstBuilderResponse::Ready)
             }
-            _ => unimplemented!(),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
     fn is_ready(&self) -> bool {
         self.stage == JackClassStage::Ready
     }
+
+    fn recover(&mut self, token: &JackToken) -> JackAstRecovery {
+        match (&self.stage, token) {
+            (
+                JackClassStage::AwaitClass
+                | JackClassStage::AwaitClassName
+                | JackClassStage::AwaitBracket,
+                JackToken::Symbol(JackSymbol::OpenCurlyBracket),
+            ) => {
+                self.stage = JackClassStage::AwaitVars;
+                JackAstRecovery::Skip
+            }
+            (JackClassStage::AwaitVars, JackToken::Symbol(JackSymbol::Semicolon)) => {
+                self.var = None;
+                JackAstRecovery::Skip
+            }
+            (JackClassStage::AwaitVars, JackToken::Keyword(keyword)) if keyword.is_var_declar() => {
+                self.var = None;
+                JackAstRecovery::Feed
+            }
+            (
+                JackClassStage::AwaitVars | JackClassStage::AwaitSubroutins,
+                JackToken::Keyword(keyword),
+            ) if keyword.is_function() => {
+                self.var = None;
+                self.save_broken_subroutine();
+                self.stage = JackClassStage::AwaitSubroutins;
+                JackAstRecovery::Feed
+            }
+            (JackClassStage::AwaitSubroutins, JackToken::Symbol(JackSymbol::CloseCurlyBracket))
+                if self.subroutine.as_ref().is_none_or(|s| s.is_ready()) =>
+            {
+                JackAstRecovery::Feed
+            }
+            _ => JackAstRecovery::Parent,
+        }
+    }
 }
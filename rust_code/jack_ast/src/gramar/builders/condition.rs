@@ -1,8 +1,8 @@
 use std::mem;
 
-use file_context::FileContext;
+use file_context::{FileContext, FileSpan};
 
-use crate::gramar::ast::{JackIf, JackStatement, JackStatements, JackTerm, JackTermPayload};
+use crate::gramar::ast::{JackIf, JackStatement, JackTerm, JackTermPayload};
 use crate::tokens::JackSymbol;
 use crate::tokens::{JackKeyword, JackToken};
 
@@ -30,6 +30,7 @@ pub struct JackIfBuilder {
     condition: Option<Box<JackStatement>>,
     statements: Option<JackStatementsBuilder>,
     acc: Vec<JackToken>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackIfBuilder {
@@ -48,7 +49,7 @@ impl JackIfBuilder {
         if let Some(var) = old_var {
             if var.is_ready() {
                 let new_var = var.build();
-                self.unwrap_condition().statements = Box::new(JackStatements(new_var));
+                *self.unwrap_condition().statements = new_var;
             } else {
                 unreachable!()
             }
@@ -62,7 +63,7 @@ impl JackIfBuilder {
         if let Some(var) = old_var {
             if var.is_ready() {
                 let new_var = var.build();
-                self.unwrap_condition().else_statements = Some(Box::new(JackStatements(new_var)));
+                self.unwrap_condition().else_statements = Some(Box::new(new_var));
             } else {
                 unreachable!()
             }
@@ -77,6 +78,7 @@ impl Default for JackIfBuilder {
             condition: Some(Box::new(JackStatement::If(Default::default()))),
             statements: Default::default(),
             acc: Default::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -86,6 +88,7 @@ impl JackAstBuilder for JackIfBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackIfStage::AwaitIf, JackToken::Keyword(JackKeyword::If)) => {
                 self.stage = JackIfStage::AwaitOpenBracket;
@@ -104,15 +107,22 @@ impl JackAstBuilder for JackIfBuilder {
             }
             (JackIfStage::AwaitOpenBracket, JackToken::Symbol(JackSymbol::OpenCurlyBracket)) => {
                 let s = self.acc.len();
-                let term = JackTerm::new(&mut self.acc, s);
+                let term = JackTerm::new(&mut self.acc, &self.spans, s)
+                    .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
 
                 match term.payload {
                     JackTermPayload::Expression(_) => (),
-                    _ => panic!("wrong condition term"),
+                    _ => {
+                        return Err(JackAnalizerError::InvalidExpression(
+                            self.spans.first().copied().flatten(),
+                            "expected a condition in parentheses",
+                        ))
+                    }
                 }
 
                 self.unwrap_condition().condition = term;
                 unsafe { self.acc.set_len(0) };
+                self.spans.clear();
                 self.stage = JackIfStage::AwaitStatements;
                 Ok(JackAstBuilderResponse::Continue)
             }
@@ -120,6 +130,7 @@ impl JackAstBuilder for JackIfBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
             (JackIfStage::AwaitStatements, JackToken::Symbol(JackSymbol::CloseCurlyBracket)) => {
@@ -154,12 +165,14 @@ impl JackAstBuilder for JackIfBuilder {
                 let link = self.statements.as_mut().unwrap();
                 Ok(JackAstBuilderResponse::Move(link))
             }
-            _ => panic!("if error {:?}", token),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
     fn is_ready(&self) -> bool {
-        self.stage == JackIfStage::Ready
+        matches!(self.stage, JackIfStage::AwaitElse | JackIfStage::Ready)
     }
 }
 
@@ -1,8 +1,8 @@
 use std::mem;
 
-use file_context::FileContext;
+use file_context::{FileContext, FileSpan};
 
-use crate::gramar::ast::{JackStatement, JackStatements, JackTerm, JackTermPayload, JackWhile};
+use crate::gramar::ast::{JackStatement, JackTerm, JackTermPayload, JackWhile};
 use crate::tokens::JackSymbol;
 use crate::tokens::{JackKeyword, JackToken};
 
@@ -27,6 +27,7 @@ pub struct JackWhileBuilder {
     condition: Option<Box<JackStatement>>,
     statements: Option<JackStatementsBuilder>,
     acc: Vec<JackToken>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackWhileBuilder {
@@ -45,7 +46,7 @@ impl JackWhileBuilder {
         if let Some(var) = old_var {
             if var.is_ready() {
                 let new_var = var.build();
-                self.unwrap_condition().statements = Box::new(JackStatements(new_var));
+                *self.unwrap_condition().statements = new_var;
             } else {
                 unreachable!()
             }
@@ -60,6 +61,7 @@ impl Default for JackWhileBuilder {
             condition: Some(Box::new(JackStatement::While(Default::default()))),
             statements: Default::default(),
             acc: Default::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -69,6 +71,7 @@ impl JackAstBuilder for JackWhileBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackWhileStage::AwaitWhile, JackToken::Keyword(JackKeyword::While)) => {
                 self.stage = JackWhileStage::AwaitOpenBracket;
@@ -76,15 +79,22 @@ impl JackAstBuilder for JackWhileBuilder {
             }
             (JackWhileStage::AwaitOpenBracket, JackToken::Symbol(JackSymbol::OpenCurlyBracket)) => {
                 let s = self.acc.len();
-                let term = JackTerm::new(&mut self.acc, s);
+                let term = JackTerm::new(&mut self.acc, &self.spans, s)
+                    .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
 
                 match term.payload {
                     JackTermPayload::Expression(_) => (),
-                    _ => panic!("wrong condition term"),
+                    _ => {
+                        return Err(JackAnalizerError::InvalidExpression(
+                            self.spans.first().copied().flatten(),
+                            "expected a condition in parentheses",
+                        ))
+                    }
                 }
 
                 self.unwrap_condition().condition = term;
                 unsafe { self.acc.set_len(0) };
+                self.spans.clear();
                 self.stage = JackWhileStage::AwaitStatements;
                 Ok(JackAstBuilderResponse::Continue)
             }
@@ -92,6 +102,7 @@ impl JackAstBuilder for JackWhileBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
             (JackWhileStage::AwaitStatements, JackToken::Symbol(JackSymbol::CloseCurlyBracket)) => {
@@ -108,7 +119,9 @@ impl JackAstBuilder for JackWhileBuilder {
                 let link = self.statements.as_mut().unwrap();
                 Ok(JackAstBuilderResponse::Move(link))
             }
-            _ => panic!("while error {:?}", token),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
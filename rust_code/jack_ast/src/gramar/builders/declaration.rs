@@ -80,7 +80,9 @@ impl JackAstBuilder for JackDeclarationBuilder {
                 self.stage = JackDeclarationStage::AwaitName;
                 Ok(JackAstBuilderResponse::Continue)
             }
-            _ => unimplemented!(),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
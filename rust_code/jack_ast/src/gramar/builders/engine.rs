@@ -1,17 +1,29 @@
-use file_context::FileContext;
+use std::mem;
+
+use file_context::{FileContext, FileSpan};
 use futures::{Stream, StreamExt};
 
-use crate::{gramar::ast::JackClass, tokens::JackToken};
+use crate::{
+    gramar::ast::JackClass,
+    tokens::{JackSymbol, JackToken},
+};
 
 use super::{
-    behaviour::{JackAstBuilder, JackAstBuilderResponse},
+    behaviour::{JackAstBuilder, JackAstBuilderResponse, JackAstRecovery},
     class::JackClassBuilder,
+    errors::JackAnalizerError,
 };
 
 pub struct JackASTBuilderEngine<'a, S: Stream<Item = FileContext<JackToken>> + Unpin> {
     stream: &'a mut S,
     scopes: Vec<*mut dyn JackAstBuilder>,
     class: Box<JackClassBuilder>,
+    last_span: Option<FileSpan>,
+    errors: Vec<JackAnalizerError>,
+    /// Tokens are skipped until a scope recovers.
+    recovering: bool,
+    /// Curly brackets skipped while recovering and not closed yet.
+    depth: usize,
 }
 
 impl<'a, S> JackASTBuilderEngine<'a, S>
@@ -26,6 +38,10 @@ where
             stream,
             scopes,
             class,
+            last_span: None,
+            errors: Vec::new(),
+            recovering: false,
+            depth: 0,
         };
 
         state.set_pointer();
@@ -43,49 +59,134 @@ where
         self.class.build()
     }
 
-    pub async fn step(&mut self) {
-        if let Some(token) = self.stream.next().await {
-            if let Some(scope) = self.scopes.pop() {
-                self.feed_scope(token, scope)
-            } else {
-                panic!("Out of scopes")
-            }
+    /// Feeds the next token, false at the end of the source. An error is
+    /// kept and the tokens are skipped until a scope goes on.
+    pub async fn step(&mut self) -> bool {
+        let Some(token) = self.stream.next().await else {
+            return false;
+        };
+        self.last_span = token.span.or(self.last_span);
+
+        if self.recovering {
+            self.recover(token);
+        } else if let Some(scope) = self.scopes.pop() {
+            self.feed(token, scope, false);
+        } else {
+            panic!("Out of scopes")
         }
+        true
     }
 
-    pub async fn build_class(mut self) -> JackClass {
+    /// Goes on after errors, the class is built from what was parsed.
+    pub async fn parse_class(mut self) -> (JackClass, Vec<JackAnalizerError>) {
         while !self.class.is_ready() {
-            self.step().await;
+            if !self.step().await {
+                self.errors
+                    .push(JackAnalizerError::UnexpectedEnd(self.last_span));
+                break;
+            }
         }
 
-        self.get_class()
+        let errors = mem::take(&mut self.errors);
+        (self.class.build_partial(), errors)
+    }
+
+    /// All the errors of the source, in order.
+    pub async fn build_class(self) -> Result<JackClass, Vec<JackAnalizerError>> {
+        let (class, errors) = self.parse_class().await;
+        if errors.is_empty() {
+            Ok(class)
+        } else {
+            Err(errors)
+        }
+    }
+
+    fn feed(
+        &mut self,
+        token: FileContext<JackToken>,
+        scope: *mut dyn JackAstBuilder,
+        recovered: bool,
+    ) {
+        let sync = match &token.payload {
+            JackToken::Keyword(keyword) => Some(JackToken::Keyword(*keyword)),
+            JackToken::Symbol(symbol) => Some(JackToken::Symbol(*symbol)),
+            _ => None,
+        };
+        let (idx, location, span) = (token.idx, token.location, token.span);
+
+        if let Err(err) = self.feed_scope(token, scope) {
+            self.errors.push(err);
+            self.recovering = true;
+            self.depth = 0;
+            // The scope that recovered at this token failed on it again.
+            if recovered {
+                return;
+            }
+            if let Some(payload) = sync {
+                self.recover(FileContext::new(payload, idx, location, span));
+            }
+        }
+    }
+
+    fn recover(&mut self, token: FileContext<JackToken>) {
+        if self.depth == 0 {
+            for idx in (0..self.scopes.len()).rev() {
+                let scope = unsafe { &mut *self.scopes[idx] };
+                match scope.recover(&token.payload) {
+                    JackAstRecovery::Parent => continue,
+                    JackAstRecovery::Skip => {
+                        self.scopes.truncate(idx + 1);
+                        self.recovering = false;
+                        return;
+                    }
+                    JackAstRecovery::Feed => {
+                        let scope = self.scopes[idx];
+                        self.scopes.truncate(idx);
+                        self.recovering = false;
+                        return self.feed(token, scope, true);
+                    }
+                }
+            }
+        }
+
+        match token.payload {
+            JackToken::Symbol(JackSymbol::OpenCurlyBracket) => self.depth += 1,
+            JackToken::Symbol(JackSymbol::CloseCurlyBracket) => {
+                self.depth = self.depth.saturating_sub(1)
+            }
+            _ => (),
+        }
     }
 
     fn feed_scope(
         &mut self,
         mut token: FileContext<JackToken>,
         unsafe_scope: *mut dyn JackAstBuilder,
-    ) {
+    ) -> Result<(), JackAnalizerError> {
         let scope = unsafe { &mut *unsafe_scope };
-        match scope.feed(&mut token) {
+        let response = match scope.feed(&mut token) {
+            Ok(response) => response,
             Err(err) => {
-                println!("{:?}", err);
-                unimplemented!()
+                self.scopes.push(scope);
+                return Err(err);
             }
-            Ok(JackAstBuilderResponse::Continue) => self.scopes.push(scope),
-            Ok(JackAstBuilderResponse::Ready) => (),
-            Ok(JackAstBuilderResponse::MoveParent) => {
+        };
+        match response {
+            JackAstBuilderResponse::Continue => self.scopes.push(scope),
+            JackAstBuilderResponse::Ready => (),
+            JackAstBuilderResponse::MoveParent => {
                 if let Some(scope) = self.scopes.pop() {
-                    self.feed_scope(token, scope)
+                    return self.feed_scope(token, scope);
                 } else {
                     panic!("Parent not found")
                 }
             }
-            Ok(JackAstBuilderResponse::Move(new_scope)) => {
+            JackAstBuilderResponse::Move(new_scope) => {
                 self.scopes.push(scope);
-                self.feed_scope(token, new_scope)
+                return self.feed_scope(token, new_scope);
             }
         }
+        Ok(())
     }
 }
 
@@ -121,7 +222,7 @@ mod tests {
             true,
         );
         let ast_engine = JackASTBuilderEngine::new(&mut tokenizer);
-        let class = ast_engine.build_class().await;
+        let class = ast_engine.build_class().await.unwrap();
 
         let var1 = JackDeclaration {
             names: vec![
@@ -142,20 +243,31 @@ mod tests {
             segment: JackSegment::Static,
         };
 
+        let at = |symbol| Some(FileSpan::new(8, symbol));
         let statement_variable = JackTerm {
             size: 4,
             payload: JackTermPayload::ArrayElem(
                 JackVariableName(b"k".to_vec()),
                 Box::new(JackExpression {
-                    term: JackTerm::new_int(JackInt(b"10".to_vec())),
+                    term: JackTerm {
+                        span: at(26),
+                        name_span: at(26),
+                        ..JackTerm::new_int(JackInt(b"10".to_vec()))
+                    },
                     size: 1,
                     extra: vec![],
                 }),
             ),
+            span: at(24),
+            name_span: at(24),
         };
 
         let statement_expression = JackExpression {
-            term: JackTerm::new_int(JackInt(b"12".to_vec())),
+            term: JackTerm {
+                span: at(32),
+                name_span: at(32),
+                ..JackTerm::new_int(JackInt(b"12".to_vec()))
+            },
             size: 1,
             extra: vec![],
         };
@@ -165,10 +277,13 @@ mod tests {
             expression: statement_expression,
         };
 
-        let statements = JackStatements(vec![
-            Box::new(JackStatement::Let(statement)),
-            Box::new(JackStatement::Return(JackReturn::default())),
-        ]);
+        let statements = JackStatements(
+            vec![
+                Box::new(JackStatement::Let(statement)),
+                Box::new(JackStatement::Return(JackReturn::default())),
+            ],
+            vec![at(20), Some(FileSpan::new(9, 20))],
+        );
         let vars = vec![var1, var2, var3];
 
         let local_var = JackDeclaration {
@@ -195,6 +310,7 @@ mod tests {
             key: JackSubroutineType::Function,
             vars: vec![arg1, arg2, local_var],
             statements,
+            span: Some(FileSpan::new(5, 30)),
         };
 
         let expected_class = JackClass {
@@ -205,4 +321,50 @@ mod tests {
 
         assert_eq!(expected_class, class)
     }
+
+    #[tokio::test]
+    async fn recovery_test() {
+        let mut tokenizer = JackTokenizer::from_slice(
+            b"class Main {
+                field int 7;
+                field int y;
+
+                function void main() {
+                    let y = 1 + ;
+                    do Output.printInt(y;
+                    while (y {
+                        let y = y - 1;
+                    }
+                    return;
+                }
+
+                method int ( {
+                    return y;
+                }
+
+                function int last() {
+                    return 1;
+                }
+            }",
+            true,
+        );
+        let ast_engine = JackASTBuilderEngine::new(&mut tokenizer);
+        let (class, errors) = ast_engine.parse_class().await;
+
+        let lines: Vec<usize> = errors.iter().map(|err| err.span().unwrap().line).collect();
+        assert_eq!(lines, vec![1, 5, 6, 7, 13]);
+
+        assert_eq!(class.vars.len(), 1);
+        let names: Vec<&[u8]> = class
+            .subroutines
+            .iter()
+            .map(|subroutine| subroutine.name.0.as_slice())
+            .collect();
+        assert_eq!(names, vec![b"main".as_slice(), b"", b"last"]);
+        let lines = |statements: &JackStatements| -> Vec<usize> {
+            statements.1.iter().map(|span| span.unwrap().line).collect()
+        };
+        assert_eq!(lines(&class.subroutines[0].statements), vec![10]);
+        assert_eq!(lines(&class.subroutines[1].statements), vec![14]);
+    }
 }
@@ -1,5 +1,8 @@
-use file_context::FileContext;
+use std::fmt;
 
+use file_context::{FileContext, FileSpan};
+
+use crate::gramar::ast::JackExpressionError;
 use crate::tokens::JackToken;
 
 #[derive(Debug)]
@@ -8,4 +11,60 @@ pub enum JackAnalizerError {
     UnknownSegment(FileContext<JackToken>),
     UnknownVar(FileContext<JackToken>),
     UnknownSubroutineKeyword(FileContext<JackToken>),
+    /// A token no rule of the current scope accepts.
+    UnexpectedToken(FileContext<JackToken>),
+    /// The span is of the token the expression goes wrong at.
+    InvalidExpression(Option<FileSpan>, &'static str),
+    /// The source ends inside the class, the span is of the last token.
+    UnexpectedEnd(Option<FileSpan>),
+}
+
+impl JackAnalizerError {
+    /// `spans` are of the tokens the expression is parsed from and `end` is
+    /// of the token after them.
+    pub fn expression(
+        err: JackExpressionError,
+        spans: &[Option<FileSpan>],
+        end: Option<FileSpan>,
+    ) -> Self {
+        let span = spans.get(err.idx).copied().unwrap_or(end);
+        Self::InvalidExpression(span, err.message)
+    }
+
+    pub fn span(&self) -> Option<FileSpan> {
+        match self {
+            Self::UnknownType(token)
+            | Self::UnknownSegment(token)
+            | Self::UnknownVar(token)
+            | Self::UnknownSubroutineKeyword(token)
+            | Self::UnexpectedToken(token) => token.span,
+            Self::InvalidExpression(span, _) | Self::UnexpectedEnd(span) => *span,
+        }
+    }
 }
+
+impl fmt::Display for JackAnalizerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::UnknownType(token) => write!(f, "expected a type, found `{}`", token.payload),
+            Self::UnknownSegment(token) => {
+                write!(
+                    f,
+                    "expected `var`, `static` or `field`, found `{}`",
+                    token.payload
+                )
+            }
+            Self::UnknownVar(token) => write!(f, "expected a name, found `{}`", token.payload),
+            Self::UnknownSubroutineKeyword(token) => write!(
+                f,
+                "expected `constructor`, `function` or `method`, found `{}`",
+                token.payload
+            ),
+            Self::UnexpectedToken(token) => write!(f, "unexpected `{}`", token.payload),
+            Self::InvalidExpression(_, message) => write!(f, "{}", message),
+            Self::UnexpectedEnd(_) => write!(f, "unexpected end of file"),
+        }
+    }
+}
+
+impl std::error::Error for JackAnalizerError {}
@@ -14,3 +14,4 @@ mod statements;
 mod subroutine;
 
 pub use engine::JackASTBuilderEngine;
+pub use errors::JackAnalizerError;
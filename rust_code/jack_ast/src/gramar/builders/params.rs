@@ -97,7 +97,9 @@ impl JackAstBuilder for JackParamsBuilder {
                 self.stage = JackParamsStage::AwaitType;
                 Ok(JackAstBuilderResponse::Continue)
             }
-            _ => unimplemented!(),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
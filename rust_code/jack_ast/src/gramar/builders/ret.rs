@@ -1,6 +1,6 @@
 use std::mem;
 
-use file_context::FileContext;
+use file_context::{FileContext, FileSpan};
 
 use crate::gramar::ast::{JackExpression, JackReturn, JackStatement};
 use crate::tokens::JackSymbol;
@@ -25,6 +25,7 @@ pub struct JackReturnBuilder {
     stage: JackReturnStage,
     assign: Option<Box<JackStatement>>,
     acc: Vec<JackToken>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackReturnBuilder {
@@ -43,6 +44,7 @@ impl Default for JackReturnBuilder {
             stage: Default::default(),
             assign: Some(Box::new(JackStatement::Return(Default::default()))),
             acc: Default::default(),
+            spans: Default::default(),
         }
     }
 }
@@ -52,6 +54,7 @@ impl JackAstBuilder for JackReturnBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackReturnStage::AwaitReturn, JackToken::Keyword(JackKeyword::Return)) => {
                 self.stage = JackReturnStage::AwaitSemicolon;
@@ -60,8 +63,11 @@ impl JackAstBuilder for JackReturnBuilder {
             (JackReturnStage::AwaitSemicolon, JackToken::Symbol(JackSymbol::Semicolon)) => {
                 let s = self.acc.len();
                 if s != 0 {
-                    self.unwrap_ret().expression = Some(JackExpression::new(&mut self.acc, s));
+                    let expression = JackExpression::new(&mut self.acc, &self.spans, s)
+                        .map_err(|err| JackAnalizerError::expression(err, &self.spans, span))?;
+                    self.unwrap_ret().expression = Some(expression);
                     unsafe { self.acc.set_len(0) };
+                    self.spans.clear();
                 }
                 self.stage = JackReturnStage::Ready;
                 Ok(JackAstBuilderResponse::Ready)
@@ -70,9 +76,12 @@ impl JackAstBuilder for JackReturnBuilder {
                 let mut t = JackToken::default();
                 mem::swap(&mut t, token);
                 self.acc.push(t);
+                self.spans.push(span);
                 Ok(JackAstBuilderResponse::Continue)
             }
-            _ => panic!("let error {:?}", token),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
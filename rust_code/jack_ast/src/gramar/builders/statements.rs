@@ -1,8 +1,8 @@
 use std::mem;
 
-use crate::gramar::ast::JackStatement;
-use crate::tokens::{JackKeyword, JackToken};
-use file_context::FileContext;
+use crate::gramar::ast::{JackStatement, JackStatements};
+use crate::tokens::{JackKeyword, JackSymbol, JackToken};
+use file_context::{FileContext, FileSpan};
 
 use super::assign::JackLetBuilder;
 use super::call::JackDoBuilder;
@@ -11,7 +11,7 @@ use super::cycle::JackWhileBuilder;
 use super::ret::JackReturnBuilder;
 use super::statement::JackAstBuilderStatements;
 use super::{
-    behaviour::{JackAstBuilder, JackAstBuilderResponse},
+    behaviour::{JackAstBuilder, JackAstBuilderResponse, JackAstRecovery},
     errors::JackAnalizerError,
 };
 
@@ -19,6 +19,7 @@ use super::{
 pub struct JackStatementsBuilder {
     prev: Option<Box<dyn JackAstBuilderStatements>>,
     acc: Vec<Box<JackStatement>>,
+    spans: Vec<Option<FileSpan>>,
 }
 
 impl JackStatementsBuilder {
@@ -31,8 +32,31 @@ impl JackStatementsBuilder {
         }
     }
 
-    pub fn build(self) -> Vec<Box<JackStatement>> {
-        self.acc
+    fn start_statement(&mut self, token: &FileContext<JackToken>) {
+        self.save_statement();
+        self.spans.push(token.span);
+    }
+
+    pub fn build(self) -> JackStatements {
+        JackStatements(self.acc, self.spans)
+    }
+
+    /// The statements parsed before an error.
+    pub fn build_partial(mut self) -> JackStatements {
+        self.drop_broken_statement();
+        self.build()
+    }
+
+    /// A statement is kept if it got to its end, the error came after it.
+    fn drop_broken_statement(&mut self) {
+        match self.prev.as_ref() {
+            Some(prev) if prev.is_ready() => self.save_statement(),
+            Some(_) => {
+                self.prev = None;
+                self.spans.pop();
+            }
+            None => (),
+        }
     }
 }
 
@@ -43,48 +67,69 @@ impl JackAstBuilder for JackStatementsBuilder {
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
         match token.payload {
             JackToken::Keyword(JackKeyword::Let) => {
-                self.save_statement();
+                self.start_statement(token);
                 let mut prev = Box::new(JackLetBuilder::default());
                 let link = prev.as_mut() as *mut dyn JackAstBuilder;
                 self.prev = Some(prev);
                 Ok(JackAstBuilderResponse::Move(link))
             }
             JackToken::Keyword(JackKeyword::Return) => {
-                self.save_statement();
+                self.start_statement(token);
                 let mut prev = Box::new(JackReturnBuilder::default());
                 let link = prev.as_mut() as *mut dyn JackAstBuilder;
                 self.prev = Some(prev);
                 Ok(JackAstBuilderResponse::Move(link))
             }
             JackToken::Keyword(JackKeyword::Do) => {
-                self.save_statement();
+                self.start_statement(token);
                 let mut prev = Box::new(JackDoBuilder::default());
                 let link = prev.as_mut() as *mut dyn JackAstBuilder;
                 self.prev = Some(prev);
                 Ok(JackAstBuilderResponse::Move(link))
             }
             JackToken::Keyword(JackKeyword::If) => {
-                self.save_statement();
+                self.start_statement(token);
                 let mut prev = Box::new(JackIfBuilder::default());
                 let link = prev.as_mut() as *mut dyn JackAstBuilder;
                 self.prev = Some(prev);
                 Ok(JackAstBuilderResponse::Move(link))
             }
             JackToken::Keyword(JackKeyword::While) => {
-                self.save_statement();
+                self.start_statement(token);
                 let mut prev = Box::new(JackWhileBuilder::default());
                 let link = prev.as_mut() as *mut dyn JackAstBuilder;
                 self.prev = Some(prev);
                 Ok(JackAstBuilderResponse::Move(link))
             }
-            _ => {
+            JackToken::Symbol(JackSymbol::CloseCurlyBracket) => {
                 self.save_statement();
                 Ok(JackAstBuilderResponse::MoveParent)
             }
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
     fn is_ready(&self) -> bool {
         true
     }
+
+    fn recover(&mut self, token: &JackToken) -> JackAstRecovery {
+        match token {
+            JackToken::Keyword(keyword) if keyword.is_statement() => {
+                self.drop_broken_statement();
+                JackAstRecovery::Feed
+            }
+            JackToken::Symbol(JackSymbol::CloseCurlyBracket) => {
+                self.drop_broken_statement();
+                JackAstRecovery::Feed
+            }
+            JackToken::Symbol(JackSymbol::Semicolon) => {
+                self.drop_broken_statement();
+                JackAstRecovery::Skip
+            }
+            _ => JackAstRecovery::Parent,
+        }
+    }
 }
@@ -4,14 +4,14 @@ use file_context::FileContext;
 
 use crate::{
     gramar::{
-        ast::{JackStatements, JackSubroutine},
+        ast::JackSubroutine,
         units::{JackSubroutineType, JackType, JackVariableName, JackVariableNameStyle},
     },
     tokens::{JackSymbol, JackToken},
 };
 
 use super::{
-    behaviour::{JackAstBuilder, JackAstBuilderResponse},
+    behaviour::{JackAstBuilder, JackAstBuilderResponse, JackAstRecovery},
     declaration::JackDeclarationBuilder,
     errors::JackAnalizerError,
     params::JackParamsBuilder,
@@ -45,6 +45,14 @@ impl JackSubroutineBuilder {
         self.subroutine
     }
 
+    /// The vars and statements parsed before an error.
+    pub fn build_partial(mut self) -> JackSubroutine {
+        if let Some(statements) = self.statements.take() {
+            self.subroutine.statements = statements.build_partial();
+        }
+        self.subroutine
+    }
+
     pub fn save_old_var(&mut self) {
         let mut old_var = None;
         mem::swap(&mut old_var, &mut self.var);
@@ -80,7 +88,7 @@ impl JackSubroutineBuilder {
         if let Some(var) = old_var {
             if var.is_ready() {
                 let new_var = var.build();
-                self.subroutine.statements = JackStatements(new_var);
+                self.subroutine.statements = new_var;
             } else {
                 unreachable!()
             }
@@ -93,6 +101,7 @@ impl JackAstBuilder for JackSubroutineBuilder {
         &mut self,
         token: &mut FileContext<JackToken>,
     ) -> Result<JackAstBuilderResponse, JackAnalizerError> {
+        let span = token.span;
         match (&self.stage, &mut token.payload) {
             (JackSubroutineStage::AwaitSubroutineKey, token_payload) => {
                 if let Some(key) = JackSubroutineType::from_token(token_payload) {
@@ -118,6 +127,7 @@ impl JackAstBuilder for JackSubroutineBuilder {
                 let style = JackVariableNameStyle::CamelCase;
                 if let Some(var_name) = JackVariableName::from_token(token_payload, style) {
                     self.subroutine.name = var_name;
+                    self.subroutine.span = span;
                     self.stage = JackSubroutineStage::AwaitSubroutineParams;
                     Ok(JackAstBuilderResponse::Continue)
                 } else {
@@ -172,11 +182,43 @@ impl JackAstBuilder for JackSubroutineBuilder {
                 let link = self.statements.as_mut().unwrap();
                 Ok(JackAstBuilderResponse::Move(link))
             }
-            _ => unimplemented!(),
+            _ => Err(JackAnalizerError::UnexpectedToken(FileContext::from_old(
+                token,
+            ))),
         }
     }
 
     fn is_ready(&self) -> bool {
         self.stage == JackSubroutineStage::Ready
     }
+
+    fn recover(&mut self, token: &JackToken) -> JackAstRecovery {
+        match (&self.stage, token) {
+            (
+                JackSubroutineStage::AwaitSubroutineType
+                | JackSubroutineStage::AwaitSubroutineName
+                | JackSubroutineStage::AwaitSubroutineParams
+                | JackSubroutineStage::AwaitOpenBracket,
+                JackToken::Symbol(JackSymbol::OpenCurlyBracket),
+            ) => {
+                self.params = None;
+                self.stage = JackSubroutineStage::AwaitSubroutineVars;
+                JackAstRecovery::Skip
+            }
+            (
+                JackSubroutineStage::AwaitSubroutineVars,
+                JackToken::Symbol(JackSymbol::Semicolon),
+            ) => {
+                self.var = None;
+                JackAstRecovery::Skip
+            }
+            (JackSubroutineStage::AwaitSubroutineVars, JackToken::Keyword(keyword))
+                if keyword.is_var_declar() || keyword.is_statement() =>
+            {
+                self.var = None;
+                JackAstRecovery::Feed
+            }
+            _ => JackAstRecovery::Parent,
+        }
+    }
 }
@@ -3,5 +3,5 @@ mod builders;
 mod units;
 
 pub use ast::*;
-pub use builders::JackASTBuilderEngine;
+pub use builders::{JackASTBuilderEngine, JackAnalizerError};
 pub use units::*;
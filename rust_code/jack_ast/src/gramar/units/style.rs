@@ -1,8 +1,27 @@
 use std::env;
 use std::str::from_utf8;
+use std::sync::atomic::{AtomicU8, Ordering};
 
 use inflections::case::{is_camel_case, is_constant_case, is_pascal_case};
 
+const STRICT_UNSET: u8 = 2;
+
+static STRICT_MODE: AtomicU8 = AtomicU8::new(STRICT_UNSET);
+
+/// Turns the name style and undeclared variable checks on or off for the
+/// whole process, `STRICT_MODE=1` is only read when this is never
+/// called.
+pub fn set_strict_mode(v: bool) {
+    STRICT_MODE.store(v as u8, Ordering::Relaxed);
+}
+
+pub fn is_strict_mode() -> bool {
+    match STRICT_MODE.load(Ordering::Relaxed) {
+        STRICT_UNSET => env::var("STRICT_MODE").is_ok_and(|v| v == "1"),
+        v => v == 1,
+    }
+}
+
 #[derive(Default, Debug)]
 pub enum JackVariableNameStyle {
     #[default]
@@ -14,13 +33,7 @@ pub enum JackVariableNameStyle {
 
 impl JackVariableNameStyle {
     pub fn check(&self, data: &[u8]) -> bool {
-        let is_strict = if let Ok(v) = env::var("STRICT_MODE") {
-            v == "1"
-        } else {
-            false
-        };
-
-        if !is_strict {
+        if !is_strict_mode() {
             return true;
         }
 
@@ -1,6 +1,6 @@
 use crate::tokens::{JackKeyword, JackToken};
 
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
 pub enum JackSubroutineType {
     #[default]
     Method,
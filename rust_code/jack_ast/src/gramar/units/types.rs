@@ -1,4 +1,4 @@
-use std::mem;
+use std::{fmt, mem};
 
 use crate::tokens::{JackKeyword, JackToken};
 
@@ -24,6 +24,21 @@ impl Default for JackType {
     }
 }
 
+/// The type as it is written in the source.
+impl fmt::Display for JackType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Basic(JackBasicType::Int) => write!(f, "int"),
+            Self::Basic(JackBasicType::Char) => write!(f, "char"),
+            Self::Basic(JackBasicType::Bool) => write!(f, "boolean"),
+            Self::Basic(JackBasicType::String) => write!(f, "String"),
+            Self::Basic(JackBasicType::Arr) => write!(f, "Array"),
+            Self::Basic(JackBasicType::Void) => write!(f, "void"),
+            Self::Class(class) => write!(f, "{}", String::from_utf8_lossy(class)),
+        }
+    }
+}
+
 impl JackType {
     pub fn is_array(&self) -> bool {
         self == &Self::Basic(JackBasicType::Arr)
@@ -33,12 +48,13 @@ impl JackType {
         self == &Self::Basic(JackBasicType::Void)
     }
 
-    pub fn as_slice(&self) -> &[u8] {
+    /// The class of the type, `None` for the types without methods.
+    pub fn as_slice(&self) -> Option<&[u8]> {
         match self {
-            Self::Class(class) => class,
-            Self::Basic(JackBasicType::Arr) => b"Array",
-            Self::Basic(JackBasicType::String) => b"String",
-            _ => unimplemented!(),
+            Self::Class(class) => Some(class),
+            Self::Basic(JackBasicType::Arr) => Some(b"Array"),
+            Self::Basic(JackBasicType::String) => Some(b"String"),
+            _ => None,
         }
     }
 
@@ -1,4 +1,4 @@
-use std::mem;
+use std::{fmt, mem};
 
 use crate::tokens::JackToken;
 
@@ -7,6 +7,12 @@ use super::style::JackVariableNameStyle;
 #[derive(Debug, PartialEq, Default, Eq, Hash)]
 pub struct JackVariableName(pub Vec<u8>);
 
+impl fmt::Display for JackVariableName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", String::from_utf8_lossy(&self.0))
+    }
+}
+
 impl JackVariableName {
     pub fn take(&mut self) -> Self {
         let mut tmp = Vec::new();
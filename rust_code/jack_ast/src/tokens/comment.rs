@@ -20,7 +20,7 @@ impl JackComment {
 
         loop {
             match (buff.get(l), is_multiline) {
-                (Some(b'\n'), false) => return (Some(JackComment(v)), l + 1, 0, true),
+                (Some(b'\n'), false) => return (Some(JackComment(v)), l + 1, 1, true),
                 (Some(b'\r'), false) => return (Some(JackComment(v)), l + 1, 0, true),
                 (Some(b'*'), true) => {
                     if let Some(b'/') = buff.get(l + 1) {
@@ -1,4 +1,4 @@
-#[derive(Debug, PartialEq, Default)]
+#[derive(Debug, PartialEq, Default, Clone, Copy)]
 pub enum JackKeyword {
     #[default]
     Constructor,
@@ -53,6 +53,13 @@ impl JackKeyword {
         }
     }
 
+    pub fn is_statement(&self) -> bool {
+        matches!(
+            self,
+            Self::Let | Self::Do | Self::If | Self::While | Self::Return
+        )
+    }
+
     pub fn is_type(&self) -> bool {
         match self {
             Self::Char => true,
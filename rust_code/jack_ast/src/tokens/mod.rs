@@ -1,3 +1,5 @@
+use std::fmt;
+use std::io::Cursor;
 use std::pin::{pin, Pin};
 
 use std::task::{Context, Poll};
@@ -62,6 +64,10 @@ impl JackTokenizer {
         Self::new(Box::pin(slice), skip_comments)
     }
 
+    pub fn from_bytes(bytes: Vec<u8>, skip_comments: bool) -> Self {
+        Self::new(Box::pin(Cursor::new(bytes)), skip_comments)
+    }
+
     async fn fill_buff(&mut self) -> Result<()> {
         let to_copy = self.len - self.cursor;
 
@@ -111,11 +117,11 @@ impl JackTokenizer {
                 FileContext::new(token_payload, self.token_idx, Some(location), Some(span))
             });
 
-            self.symbol += token_size;
-
-            self.line += lines;
-
+            // A token cut by the end of the buffer is read again after the
+            // refill, its position counts once it is complete.
             if terminator {
+                self.symbol += token_size;
+                self.line += lines;
                 self.cursor += token_size;
 
                 if !self.skip_comments {
@@ -136,6 +142,8 @@ impl JackTokenizer {
             } else {
                 self.fill_buff().await.expect("Fill buffer error");
                 if self.eof {
+                    self.symbol += token_size;
+                    self.line += lines;
                     self.cursor += token_size;
                     if !self.skip_comments {
                         self.token_idx += 1;
@@ -187,6 +195,23 @@ impl Default for JackToken {
     }
 }
 
+/// The token as it is written in the source.
+impl fmt::Display for JackToken {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            JackToken::Keyword(keyword) => write!(f, "{}", format!("{:?}", keyword).to_lowercase()),
+            JackToken::Symbol(symbol) => write!(f, "{}", symbol.to_char()),
+            JackToken::IntLiteral(JackInt(v)) | JackToken::Ident(JackIdent(v)) => {
+                write!(f, "{}", String::from_utf8_lossy(v))
+            }
+            JackToken::StringLiteral(JackString(v)) => {
+                write!(f, "\"{}\"", String::from_utf8_lossy(v))
+            }
+            JackToken::Comment(JackComment(v)) => write!(f, "//{}", String::from_utf8_lossy(v)),
+        }
+    }
+}
+
 fn trim_start(buff: &[u8], s: usize) -> (usize, usize, usize) {
     let mut cursor = 0;
     let mut lines = 0;
@@ -375,6 +400,24 @@ mod tests {
         assert_eq!(token_size, 1);
     }
 
+    #[tokio::test]
+    async fn test_spans_after_comments() {
+        let tokenizer = JackTokenizer::from_slice(b"// one\nlet /** two\n */ x\n;", true);
+        let lines: Vec<usize> = tokenizer.map(|t| t.span.unwrap().line).collect().await;
+        assert_eq!(lines, vec![1, 2, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_spans_across_buffer_end() {
+        // `longname` is cut by the end of the 1024 bytes buffer.
+        let mut source = b"\n".to_vec();
+        source.extend([b' '; 1018]);
+        source.extend(b"longname x");
+        let tokenizer = JackTokenizer::from_bytes(source, true);
+        let spans: Vec<FileSpan> = tokenizer.map(|t| t.span.unwrap()).collect().await;
+        assert_eq!(spans, vec![FileSpan::new(1, 1018), FileSpan::new(1, 1027)]);
+    }
+
     #[tokio::test]
     async fn test_arrays_from_file() {
         let file = File::open("./priv/ArrayTest.jack").await.unwrap();
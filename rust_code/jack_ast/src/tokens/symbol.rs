@@ -45,6 +45,30 @@ impl JackSymbol {
         }
     }
 
+    pub fn to_char(&self) -> char {
+        match self {
+            Self::OpenRoundBracket => '(',
+            Self::CloseRoundBracket => ')',
+            Self::OpenSquareBracket => '[',
+            Self::CloseSquareBracket => ']',
+            Self::OpenCurlyBracket => '{',
+            Self::CloseCurlyBracket => '}',
+            Self::Period => '.',
+            Self::Comma => ',',
+            Self::Semicolon => ';',
+            Self::Plus => '+',
+            Self::Minus => '-',
+            Self::Multiply => '*',
+            Self::Divide => '/',
+            Self::And => '&',
+            Self::Or => '|',
+            Self::Less => '<',
+            Self::Greater => '>',
+            Self::Eq => '=',
+            Self::Not => '~',
+        }
+    }
+
     pub fn char_to_symbol(c: u8) -> Option<Self> {
         match c {
             b'(' => Some(Self::OpenRoundBracket),
@@ -0,0 +1,264 @@
+use std::fmt;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use file_context::FileSpan;
+use jack_ast::gramar::JackAnalizerError;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Error => write!(f, "error"),
+            Self::Warning => write!(f, "warning"),
+        }
+    }
+}
+
+/// A token of the source pointed at, the message goes next to the underline.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Label {
+    pub span: FileSpan,
+    pub message: String,
+}
+
+impl Label {
+    pub fn new(span: FileSpan, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+        }
+    }
+}
+
+/// A problem found in a `.jack` source.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    /// Where the problem is, underlined with `^`.
+    pub primary: Option<Label>,
+    /// Related places, underlined with `-`.
+    pub secondary: Vec<Label>,
+    pub notes: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>) -> Self {
+        Self {
+            severity: Severity::Error,
+            message: message.into(),
+            primary: None,
+            secondary: Vec::new(),
+            notes: Vec::new(),
+        }
+    }
+
+    pub fn with_primary(mut self, span: Option<FileSpan>, message: impl Into<String>) -> Self {
+        self.primary = span.map(|span| Label::new(span, message));
+        self
+    }
+
+    pub fn with_secondary(mut self, span: Option<FileSpan>, message: impl Into<String>) -> Self {
+        self.secondary
+            .extend(span.map(|span| Label::new(span, message)));
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn span(&self) -> Option<FileSpan> {
+        self.primary.as_ref().map(|label| label.span)
+    }
+
+    /// rustc style, the lines of the labels with the labeled tokens underlined:
+    ///
+    /// ```text
+    /// error: expected a type, found `7`
+    ///  --> Main.jack:2:11
+    ///   |
+    /// 2 |     field 7 x;
+    ///   |           ^ not a type
+    /// ```
+    pub fn render(&self, path: &str, source: &str) -> String {
+        let lines: Vec<&str> = source.lines().collect();
+        let mut labels: Vec<(&Label, char)> = self.primary.iter().map(|l| (l, '^')).collect();
+        labels.extend(self.secondary.iter().map(|l| (l, '-')));
+        labels.sort_by_key(|(label, _)| (label.span.line, label.span.symbol));
+
+        let width = labels
+            .iter()
+            .map(|(label, _)| (label.span.line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+        let gutter = " ".repeat(width);
+
+        let mut out = vec![format!("{}: {}", self.severity, self.message)];
+        out.push(match self.span() {
+            Some(span) => format!(
+                "{}--> {}:{}:{}",
+                gutter,
+                path,
+                span.line + 1,
+                span.symbol + 1
+            ),
+            None => format!("{}--> {}", gutter, path),
+        });
+
+        if !labels.is_empty() {
+            out.push(format!("{} |", gutter));
+        }
+        let mut last_line = None;
+        for (label, underline) in labels {
+            let line = label.span.line;
+            let text = lines.get(line).copied().unwrap_or("");
+            if last_line != Some(line) {
+                if last_line.is_some_and(|last| last + 1 < line) {
+                    out.push("...".to_string());
+                }
+                out.push(format!("{:>width$} | {}", line + 1, text));
+                last_line = Some(line);
+            }
+
+            let symbol = label.span.symbol;
+            let column = text
+                .get(..symbol)
+                .map_or(symbol, |head| head.chars().count());
+            let marks = underline.to_string().repeat(token_size(text, symbol));
+            let row = format!("{}{} {}", " ".repeat(column), marks, label.message);
+            out.push(format!("{} | {}", gutter, row.trim_end()));
+        }
+
+        for note in self.notes.iter() {
+            out.push(format!("{} = note: {}", gutter, note));
+        }
+        out.join("\n")
+    }
+}
+
+/// Characters of the token starting at `symbol` of `line`: a name, a number
+/// or a string with its quotes, one for a symbol.
+fn token_size(line: &str, symbol: usize) -> usize {
+    let rest = line.get(symbol..).unwrap_or("");
+    let is_word = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let size = match rest.chars().next() {
+        Some('"') => rest[1..].find('"').map_or(rest.len(), |end| end + 2),
+        Some(c) if is_word(c) => rest.find(|c| !is_word(c)).unwrap_or(rest.len()),
+        _ => 1,
+    };
+    rest[..size.min(rest.len())].chars().count().max(1)
+}
+
+/// Everything that stopped a class from compiling.
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct Diagnostics(pub Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn is_empty(&self) -> bool {
+        self.0.is_empty()
+    }
+
+    /// Each diagnostic rendered against the source, a blank line between.
+    pub fn render(&self, path: &str, source: &str) -> String {
+        self.0
+            .iter()
+            .map(|diagnostic| diagnostic.render(path, source))
+            .collect::<Vec<_>>()
+            .join("\n\n")
+    }
+
+    /// An error that prints as the rendered diagnostics, see [`SourceDiagnostics`].
+    pub fn located(self, path: &Path, source: &[u8]) -> io::Error {
+        let source = SourceDiagnostics {
+            path: path.to_path_buf(),
+            source: String::from_utf8_lossy(source).to_string(),
+            diagnostics: self,
+        };
+        io::Error::new(io::ErrorKind::InvalidData, source)
+    }
+}
+
+impl From<JackAnalizerError> for Diagnostic {
+    fn from(err: JackAnalizerError) -> Self {
+        let label = match &err {
+            JackAnalizerError::UnknownType(_) => "not a type",
+            JackAnalizerError::UnknownSegment(_) => "not a variable kind",
+            JackAnalizerError::UnknownVar(_) => "not a name",
+            JackAnalizerError::UnknownSubroutineKeyword(_) => "not a subroutine kind",
+            JackAnalizerError::UnexpectedToken(_) => "unexpected token",
+            JackAnalizerError::InvalidExpression(_, _) => "",
+            JackAnalizerError::UnexpectedEnd(_) => "the source ends after this",
+        };
+        Diagnostic::error(err.to_string()).with_primary(err.span(), label)
+    }
+}
+
+impl From<JackAnalizerError> for Diagnostics {
+    fn from(err: JackAnalizerError) -> Self {
+        Diagnostics(vec![err.into()])
+    }
+}
+
+impl From<Vec<JackAnalizerError>> for Diagnostics {
+    fn from(errors: Vec<JackAnalizerError>) -> Self {
+        Diagnostics(errors.into_iter().map(Diagnostic::from).collect())
+    }
+}
+
+/// `line:column: message` with both counted from 1.
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.span() {
+            Some(span) => write!(f, "{}:{}: {}", span.line + 1, span.symbol + 1, self.message),
+            None => write!(f, "{}", self.message),
+        }
+    }
+}
+
+/// One diagnostic per line.
+impl fmt::Display for Diagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (idx, diagnostic) in self.0.iter().enumerate() {
+            if idx != 0 {
+                writeln!(f)?;
+            }
+            write!(f, "{}", diagnostic)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostics {}
+
+/// Diagnostics of a file with its source, displayed rendered. Binaries print
+/// it as is, without their usual `error: ` prefix.
+#[derive(Debug)]
+pub struct SourceDiagnostics {
+    pub path: PathBuf,
+    pub source: String,
+    pub diagnostics: Diagnostics,
+}
+
+impl SourceDiagnostics {
+    /// The diagnostics inside an error made by [`Diagnostics::located`].
+    pub fn of(err: &io::Error) -> Option<&Self> {
+        err.get_ref()?.downcast_ref()
+    }
+}
+
+impl fmt::Display for SourceDiagnostics {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let path = self.path.display().to_string();
+        write!(f, "{}", self.diagnostics.render(&path, &self.source))
+    }
+}
+
+impl std::error::Error for SourceDiagnostics {}
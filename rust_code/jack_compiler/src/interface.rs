@@ -0,0 +1,254 @@
+use std::collections::HashMap;
+use std::fmt::Write;
+
+use jack_ast::gramar::JackSubroutineType;
+use tokio::io::{Error, ErrorKind, Result};
+use vm_parser::{
+    AsmFunctionInstruction, AsmInstructionPayload, AsmMemoryInstructionSegment, VMParser,
+    VmParseError,
+};
+
+use crate::signatures::{JackSignature, JackSymbolTable};
+
+/// Extension of the interface files the compiler loads next to `.jack` files.
+pub const INTERFACE_EXTENSION: &str = "vmi";
+
+/// Code the Jack compiler puts at the start of methods and constructors.
+const METHOD_HEADER: [&str; 2] = ["push argument 0", "pop pointer 0"];
+const CONSTRUCTOR_HEADER: [&str; 2] = ["call Memory.alloc 1", "pop pointer 0"];
+
+/// A function of `.vm` code as far as it was read.
+struct VmFunction {
+    name: Vec<u8>,
+    /// The first instructions, they tell methods and constructors apart.
+    header: Vec<String>,
+    /// Arguments up to the highest one read or written.
+    args: i16,
+}
+
+impl VmFunction {
+    fn key(&self) -> JackSubroutineType {
+        let header: Vec<&str> = self.header.iter().map(String::as_str).collect();
+        if header.starts_with(&METHOD_HEADER) {
+            JackSubroutineType::Method
+        } else if header.get(1..3) == Some(&CONSTRUCTOR_HEADER[..]) {
+            JackSubroutineType::Constructor
+        } else {
+            JackSubroutineType::Function
+        }
+    }
+}
+
+/// Signatures of precompiled classes inferred from their `.vm` code: the
+/// kind from the code Jack puts at the start of methods and constructors, the
+/// parameters from the highest argument used and from the calls in the code.
+#[derive(Default)]
+pub struct VmSignatures {
+    functions: Vec<VmFunction>,
+    /// The most arguments a call passes, `this` included.
+    calls: HashMap<Vec<u8>, i16>,
+}
+
+impl VmSignatures {
+    pub async fn add_code(&mut self, code: Vec<u8>) -> std::result::Result<(), VmParseError> {
+        let mut parser = VMParser::from_bytes(code).await?;
+        while let Some(token) = parser.next_instruction().await {
+            self.add_instruction(&token?.payload);
+        }
+        Ok(())
+    }
+
+    fn add_instruction(&mut self, instruction: &AsmInstructionPayload) {
+        match instruction {
+            AsmInstructionPayload::Function(AsmFunctionInstruction::Definition(meta)) => {
+                self.functions.push(VmFunction {
+                    name: meta.name.clone(),
+                    header: Vec::new(),
+                    args: 0,
+                });
+                return;
+            }
+            AsmInstructionPayload::Function(AsmFunctionInstruction::Call(meta)) => {
+                let count = self.calls.entry(meta.name.clone()).or_default();
+                *count = (*count).max(meta.args_count);
+            }
+            AsmInstructionPayload::Memory(memory)
+                if memory.segment == AsmMemoryInstructionSegment::Arg =>
+            {
+                if let Some(function) = self.functions.last_mut() {
+                    function.args = function.args.max(memory.val + 1);
+                }
+            }
+            _ => (),
+        }
+
+        if let Some(function) = self.functions.last_mut() {
+            if function.header.len() < 3 {
+                function.header.push(instruction.to_string());
+            }
+        }
+    }
+
+    /// The parameters are exact when a call passes as many as the function
+    /// reads, at least the ones read otherwise.
+    pub fn build(self) -> JackSymbolTable {
+        let mut table = JackSymbolTable::default();
+        for function in self.functions.iter() {
+            let Some(dot) = function.name.iter().position(|c| *c == b'.') else {
+                continue;
+            };
+            let key = function.key();
+            let called = self.calls.get(&function.name).copied().unwrap_or(0);
+            let args = function.args.max(called);
+            let this = (key == JackSubroutineType::Method) as i16;
+            let signature = JackSignature {
+                key,
+                kind: None,
+                params: vec![None; (args - this).max(0) as usize],
+                more_params: called < function.args || called == 0,
+                span: None,
+            };
+            let (class, name) = function.name.split_at(dot);
+            table.insert(class.to_vec(), name[1..].to_vec(), signature);
+        }
+        table
+    }
+}
+
+/// One `kind Class.name count` line a subroutine, `count+` when calls may pass
+/// more parameters, sorted so the file does not depend on the input order.
+pub fn write_interface(table: &JackSymbolTable) -> String {
+    let mut lines = Vec::new();
+    for (class, signatures) in table.classes() {
+        for (name, signature) in signatures.iter() {
+            let key = match signature.key {
+                JackSubroutineType::Method => "method",
+                JackSubroutineType::Function => "function",
+                JackSubroutineType::Constructor => "constructor",
+            };
+            lines.push((
+                class,
+                name,
+                key,
+                signature.params.len(),
+                signature.more_params,
+            ));
+        }
+    }
+    lines.sort();
+
+    let mut out = String::new();
+    for (class, name, key, count, more) in lines {
+        let class = String::from_utf8_lossy(class);
+        let name = String::from_utf8_lossy(name);
+        let more = if more { "+" } else { "" };
+        writeln!(out, "{} {}.{} {}{}", key, class, name, count, more).unwrap();
+    }
+    out
+}
+
+/// Reads what [`write_interface`] writes, `//` comments and blank lines are
+/// skipped.
+pub fn read_interface(text: &str) -> Result<JackSymbolTable> {
+    let mut table = JackSymbolTable::default();
+    for (idx, line) in text.lines().enumerate() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let wrong_line = || {
+            Error::new(
+                ErrorKind::InvalidData,
+                format!(
+                    "line {}: expected `kind Class.name count`, found `{}`",
+                    idx + 1,
+                    line
+                ),
+            )
+        };
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let [key, full_name, count] = words[..] else {
+            return Err(wrong_line());
+        };
+        let key = match key {
+            "method" => JackSubroutineType::Method,
+            "function" => JackSubroutineType::Function,
+            "constructor" => JackSubroutineType::Constructor,
+            _ => return Err(wrong_line()),
+        };
+        let (count, more_params) = match count.strip_suffix('+') {
+            Some(count) => (count, true),
+            None => (count, false),
+        };
+        let count: usize = count.parse().map_err(|_| wrong_line())?;
+        let (class, name) = full_name.split_once('.').ok_or_else(wrong_line)?;
+
+        let signature = JackSignature {
+            key,
+            kind: None,
+            params: vec![None; count],
+            more_params,
+            span: None,
+        };
+        table.insert(class.into(), name.into(), signature);
+    }
+    Ok(table)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn infer_test() {
+        let code = b"function Point.new 0
+push constant 2
+call Memory.alloc 1
+pop pointer 0
+push argument 0
+pop this 0
+push argument 1
+pop this 1
+push pointer 0
+return
+function Point.getX 0
+push argument 0
+pop pointer 0
+push this 0
+return
+function Point.distance 1
+push argument 0
+pop pointer 0
+push argument 1
+call Point.getX 1
+return
+function Point.origin 0
+push constant 0
+push constant 0
+call Point.new 2
+push constant 0
+call Point.scale 3
+return
+function Point.scale 0
+push argument 0
+return
+";
+        let mut signatures = VmSignatures::default();
+        signatures.add_code(code.to_vec()).await.unwrap();
+        let interface = write_interface(&signatures.build());
+        assert_eq!(
+            interface,
+            "method Point.distance 1+
+method Point.getX 0
+constructor Point.new 2
+function Point.origin 0+
+function Point.scale 3
+"
+        );
+
+        let table = read_interface(&interface).unwrap();
+        assert_eq!(write_interface(&table), interface);
+        assert!(read_interface("function Point 2").is_err());
+    }
+}
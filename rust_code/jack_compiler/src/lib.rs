@@ -0,0 +1,462 @@
+use std::{
+    ffi::OsStr,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use jack_ast::gramar::*;
+use jack_ast::tokens::JackTokenizer;
+use subroutine::JackSubroutineCompilerContext;
+use tokio::{
+    fs::read_dir,
+    io::{Error, Result},
+    task::JoinSet,
+};
+
+use class::JackClassCompilerContext;
+use file_context::{SourceLocation, SourceMap};
+use vm_parser::AsmInstructionPayload;
+
+mod class;
+mod diagnostics;
+mod interface;
+mod signatures;
+mod subroutine;
+mod types;
+mod vars;
+
+pub use diagnostics::{Diagnostic, Diagnostics, Label, Severity, SourceDiagnostics};
+pub use interface::{read_interface, write_interface, VmSignatures, INTERFACE_EXTENSION};
+pub use jack_ast::gramar::{is_strict_mode, set_strict_mode};
+pub use signatures::{JackClassSignatures, JackSignature, JackSymbolTable};
+pub use types::JackTypeRules;
+
+/// How the compiler checks types and what it writes besides the `.vm` code.
+#[derive(Clone, Debug, Default)]
+pub struct CompileOpts {
+    /// A `.vm.map` from `.vm` lines back to `.jack` lines.
+    pub source_map: bool,
+    /// Independent of strict mode, which checks names and declarations.
+    pub type_rules: JackTypeRules,
+    /// Libraries with `.jack` or `.vmi` files searched after the project,
+    /// usually ending with the OS.
+    pub library_dirs: Vec<PathBuf>,
+}
+
+/// Compiles a `.jack` file or every `.jack` file of a directory. The `.vm`
+/// files go to `out_dir`, or next to the sources without it. Calls are
+/// checked against the classes of the directory and the libraries.
+pub async fn compile(
+    src_file_or_dir: &Path,
+    out_dir: Option<&Path>,
+    opts: CompileOpts,
+) -> Result<Vec<PathBuf>> {
+    let project_dir = match src_file_or_dir.parent() {
+        Some(parent) if !src_file_or_dir.is_dir() => parent,
+        _ => src_file_or_dir,
+    };
+    let mut dirs = vec![project_dir.to_path_buf()];
+    dirs.extend(opts.library_dirs.iter().cloned());
+    let project = JackSymbolTable::read_dirs(&dirs).await?;
+
+    let mut sources = Vec::new();
+    if src_file_or_dir.is_dir() {
+        let mut paths = read_dir(src_file_or_dir).await?;
+        while let Some(path) = paths.next_entry().await? {
+            let path_type = path.path();
+            if Some(OsStr::new("jack")) == path_type.extension() {
+                sources.push(path_type);
+            }
+        }
+        sources.sort();
+    } else {
+        sources.push(src_file_or_dir.to_path_buf());
+    }
+
+    let mut outputs = Vec::new();
+    for src in sources {
+        let dest = match out_dir {
+            Some(dir) => dir.join(src.file_name().unwrap()).with_extension("vm"),
+            None => src.with_extension("vm"),
+        };
+        compile_file(&src, &dest, &opts, Some(&project)).await?;
+        outputs.push(dest);
+    }
+
+    Ok(outputs)
+}
+
+/// Instructions, each with the `.jack` line it is compiled from.
+pub type Instructions = Vec<(AsmInstructionPayload, Option<usize>)>;
+
+/// The `.vm` code of a class and the `.jack` line of every `.vm` line.
+#[derive(Debug)]
+pub struct VmOutput {
+    pub code: String,
+    pub source_map: SourceMap,
+}
+
+/// Compiles one `.jack` file to `dest`.
+pub async fn compile_file(
+    src: &Path,
+    dest: &Path,
+    opts: &CompileOpts,
+    project: Option<&JackSymbolTable>,
+) -> Result<()> {
+    let source = tokio::fs::read(src)
+        .await
+        .map_err(|err| with_path(src, err))?;
+    let src_file = src.display().to_string();
+    let output = compile_class(source.clone(), &src_file, opts.type_rules, project)
+        .await
+        .map_err(|diagnostics| diagnostics.located(src, &source))?;
+
+    tokio::fs::write(dest, output.code)
+        .await
+        .map_err(|err| with_path(dest, err))?;
+
+    if opts.source_map {
+        let mut out = Vec::new();
+        output.source_map.write(&mut out);
+        tokio::fs::write(dest.with_extension("vm.map"), out).await?;
+    }
+
+    Ok(())
+}
+
+/// Compiles the source of one class to `.vm` code.
+pub async fn compile_source(src: &str) -> std::result::Result<String, Diagnostics> {
+    let rules = JackTypeRules::default();
+    Ok(compile_class(src.as_bytes().to_vec(), "", rules, None)
+        .await?
+        .code)
+}
+
+/// Compiles the source of one class, `src_file` names it in the source map.
+pub async fn compile_class(
+    src: Vec<u8>,
+    src_file: &str,
+    rules: JackTypeRules,
+    project: Option<&JackSymbolTable>,
+) -> std::result::Result<VmOutput, Diagnostics> {
+    let instructions = compile_instructions(src, rules, project).await?;
+    Ok(VmOutput::new(&instructions, src_file))
+}
+
+/// Compiles the source of one class to instructions, each with its `.jack`
+/// line. Calls of other classes are checked against `project` when given.
+pub async fn compile_instructions(
+    src: Vec<u8>,
+    rules: JackTypeRules,
+    project: Option<&JackSymbolTable>,
+) -> std::result::Result<Instructions, Diagnostics> {
+    let mut tokenizer = JackTokenizer::from_bytes(src, true);
+    let ast_builder = JackASTBuilderEngine::new(&mut tokenizer);
+    let mut ast = ast_builder.build_class().await?;
+    let type_errors = types::check_class(&ast, rules, project);
+    let class_context = JackClassCompilerContext::init(&mut ast);
+    let compiled = execute_tasks(class_context, ast).await;
+    if type_errors.is_empty() {
+        return compiled;
+    }
+
+    // Both passes go over the subroutines in order, merged by line.
+    let mut diagnostics = compiled.err().unwrap_or_default().0;
+    diagnostics.extend(type_errors);
+    diagnostics.sort_by_key(|diagnostic| diagnostic.span().map(|span| span.line));
+    Err(Diagnostics(diagnostics))
+}
+
+/// Checks the types and calls of a class without compiling it, e.g. a class
+/// whose `.vm` is reused while the classes it calls changed.
+pub async fn check_source(
+    src: Vec<u8>,
+    rules: JackTypeRules,
+    project: Option<&JackSymbolTable>,
+) -> std::result::Result<(), Diagnostics> {
+    let mut tokenizer = JackTokenizer::from_bytes(src, true);
+    let ast = JackASTBuilderEngine::new(&mut tokenizer)
+        .build_class()
+        .await?;
+    let type_errors = types::check_class(&ast, rules, project);
+    if !type_errors.is_empty() {
+        return Err(Diagnostics(type_errors));
+    }
+    Ok(())
+}
+
+impl VmOutput {
+    /// One `.vm` line an instruction, `src_file` names the `.jack` file in the map.
+    pub fn new(instructions: &[(AsmInstructionPayload, Option<usize>)], src_file: &str) -> Self {
+        let mut code = String::new();
+        let mut source_map = SourceMap::new();
+
+        for (vm_line, (i, line)) in instructions.iter().enumerate() {
+            let locations = line.map(|line| vec![SourceLocation::new(src_file.to_string(), line)]);
+            source_map.push(vm_line, locations.unwrap_or_default());
+
+            writeln!(code, "{}", i).unwrap();
+        }
+
+        Self { code, source_map }
+    }
+}
+
+async fn execute_tasks(
+    class_context: JackClassCompilerContext,
+    mut ast: JackClass,
+) -> std::result::Result<Instructions, Diagnostics> {
+    let mut tasks = JoinSet::new();
+    let link = unsafe { &*(&class_context as *const JackClassCompilerContext) };
+    for (idx, subroutine) in ast.subroutines.iter_mut().enumerate() {
+        new_task(&mut tasks, link, idx, unsafe {
+            &mut *(subroutine as *mut JackSubroutine)
+        })
+    }
+
+    // Tasks finish in any order, the code goes in the order of the source.
+    let mut subroutines: Vec<_> = ast.subroutines.iter().map(|_| Vec::new()).collect();
+    let mut diagnostics: Vec<_> = ast.subroutines.iter().map(|_| Vec::new()).collect();
+    while let Some(result) = tasks.join_next().await {
+        let (idx, instructions, found) = result.unwrap();
+        subroutines[idx] = instructions;
+        diagnostics[idx] = found;
+    }
+
+    let diagnostics = Diagnostics(diagnostics.into_iter().flatten().collect());
+    if !diagnostics.is_empty() {
+        return Err(diagnostics);
+    }
+    Ok(subroutines.into_iter().flatten().collect())
+}
+
+fn new_task(
+    set: &mut JoinSet<(usize, Instructions, Vec<Diagnostic>)>,
+    class_context: &'static JackClassCompilerContext,
+    idx: usize,
+    subroutine: &'static mut JackSubroutine,
+) {
+    set.spawn(async move {
+        let mut r = JackSubroutineCompilerContext::init(class_context, subroutine, true);
+        let instructions = r.by_ref().collect::<Vec<_>>();
+
+        (idx, instructions, r.into_diagnostics())
+    });
+}
+
+fn with_path(path: &Path, err: Error) -> Error {
+    Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn compile_source_test() {
+        let code = compile_source(
+            "class Main {
+    function int seven() {
+        return 7;
+    }
+}",
+        )
+        .await
+        .unwrap();
+        assert_eq!(code, "function Main.seven 0\npush constant 7\nreturn\n");
+    }
+
+    #[tokio::test]
+    async fn type_rules_test() {
+        let source = b"class Main {
+    function void main() {
+        var boolean b;
+        let b = 1;
+        return;
+    }
+}";
+        let lenient = compile_class(source.to_vec(), "", JackTypeRules::Lenient, None).await;
+        assert!(lenient.is_ok());
+
+        let err = compile_class(source.to_vec(), "", JackTypeRules::Strict, None)
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "4:13: mismatched types");
+    }
+
+    #[tokio::test]
+    async fn diagnostics_test() {
+        let err = compile_source("class Main {\n    field 7 x;\n}")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "2:11: expected a type, found `7`");
+
+        let err = compile_source("class Main {\n    function void main() {\n")
+            .await
+            .unwrap_err();
+        assert_eq!(err.to_string(), "2:26: unexpected end of file");
+    }
+
+    #[tokio::test]
+    async fn render_test() {
+        let source = "class Main {
+    function void main() {
+        return 7;
+    }
+}";
+        let err = compile_source(source).await.unwrap_err();
+        assert_eq!(
+            err.render("Main.jack", source),
+            "error: a void subroutine cannot return a value
+ --> Main.jack:3:9
+  |
+2 |     function void main() {
+  |                   ---- `main` is declared `void`
+3 |         return 7;
+  |         ^^^^^^ returns a value"
+        );
+
+        let source = "class Main {\n    field 7 x;\n}";
+        let err = compile_source(source).await.unwrap_err();
+        assert_eq!(
+            err.render("Main.jack", source),
+            "error: expected a type, found `7`
+ --> Main.jack:2:11
+  |
+2 |     field 7 x;
+  |           ^ not a type"
+        );
+    }
+
+    #[tokio::test]
+    async fn compiler_checks_test() {
+        let err = compile_source(
+            "class Main {
+    function int main() {
+        let y = 40000;
+        return;
+    }
+}",
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "3:17: integer is out of range
+3:13: cannot find variable `y` in this scope
+4:9: missing return value"
+        );
+    }
+
+    /// Diagnostics point at the tokens, not at the first match on the line.
+    #[tokio::test]
+    async fn token_spans_test() {
+        let err = compile_source(
+            "class Main {
+    function void main() {
+        var int a;
+        var String s;
+        let a = (a = s);
+        do Main.main(a +
+            missing[0]);
+        return;
+    }
+}",
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "5:20: cannot apply `=` to `int` and `String`
+6:17: `Main.main` takes 0 arguments but 1 was given
+7:13: cannot find variable `missing` in this scope"
+        );
+    }
+
+    #[tokio::test]
+    async fn library_dirs_test() {
+        let dir = std::env::temp_dir().join(format!("library_dirs_test_{}", std::process::id()));
+        let (project, lib) = (dir.join("Main"), dir.join("lib"));
+        std::fs::create_dir_all(&project).unwrap();
+        std::fs::create_dir_all(&lib).unwrap();
+        std::fs::write(lib.join("lib.vmi"), "function Lib.twice 1\n").unwrap();
+        let main = "class Main {
+    function int main() {
+        return Lib.twice(7);
+    }
+}";
+        std::fs::write(project.join("Main.jack"), main).unwrap();
+
+        let err = compile(&project, None, Default::default())
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("cannot find class `Lib`"),
+            "{}",
+            err
+        );
+
+        let opts = CompileOpts {
+            library_dirs: vec![lib],
+            ..Default::default()
+        };
+        let outputs = compile(&project, None, opts.clone()).await.unwrap();
+        assert_eq!(outputs, vec![project.join("Main.vm")]);
+
+        std::fs::write(project.join("Main.jack"), main.replace("(7)", "(7, 8)")).unwrap();
+        assert!(compile(&project, None, opts).await.is_err());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Subroutines are compiled in parallel, the output must not depend on
+    /// which finishes first.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn deterministic_output_test() {
+        let projects = concat!(env!("CARGO_MANIFEST_DIR"), "/../../static/jack");
+        let mut sources: Vec<_> = std::fs::read_dir(projects)
+            .unwrap()
+            .map(|project| project.unwrap().path())
+            .filter(|project| project.is_dir())
+            .flat_map(|project| std::fs::read_dir(project).unwrap())
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| path.extension() == Some(OsStr::new("jack")))
+            .collect();
+        sources.sort();
+        assert!(sources.len() > 10);
+
+        for src in sources {
+            let source = std::fs::read(&src).unwrap();
+            let first = compile_class(source.clone(), "", Default::default(), None)
+                .await
+                .unwrap()
+                .code;
+
+            let functions: Vec<_> = first
+                .lines()
+                .filter(|line| line.starts_with("function "))
+                .collect();
+            let text = String::from_utf8_lossy(&source);
+            let mut sorted_by_source = functions.clone();
+            sorted_by_source.sort_by_key(|function| {
+                let name = format!(" {}(", function.split(['.', ' ']).nth(2).unwrap());
+                text.lines().position(|line| {
+                    let line = line.trim_start();
+                    ["function", "method", "constructor"]
+                        .iter()
+                        .any(|keyword| line.starts_with(keyword))
+                        && line.contains(&name)
+                })
+            });
+            assert_eq!(functions, sorted_by_source, "{}", src.display());
+
+            for _ in 0..10 {
+                let code = compile_class(source.clone(), "", Default::default(), None)
+                    .await
+                    .unwrap()
+                    .code;
+                assert_eq!(code, first, "{}", src.display());
+            }
+        }
+    }
+}
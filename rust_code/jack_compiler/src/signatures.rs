@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::path::{Path, PathBuf};
+
+use file_context::FileSpan;
+use jack_ast::gramar::*;
+use jack_ast::tokens::JackTokenizer;
+use tokio::{
+    fs,
+    io::{Error, Result},
+};
+
+use crate::interface::{read_interface, INTERFACE_EXTENSION};
+
+/// What a call of a subroutine is checked against.
+#[derive(Debug, Clone, PartialEq)]
+pub struct JackSignature {
+    pub key: JackSubroutineType,
+    /// The return type, not known for subroutines of `.vm` code.
+    pub kind: Option<JackType>,
+    /// Types of the parameters, `this` of a method is not one.
+    pub params: Vec<Option<JackType>>,
+    /// Only the parameters the `.vm` code reads are known, calls may pass more.
+    pub more_params: bool,
+    /// The name in the source of the class.
+    pub span: Option<FileSpan>,
+}
+
+impl JackSignature {
+    pub fn new(subroutine: &JackSubroutine) -> Self {
+        let params = subroutine
+            .vars
+            .iter()
+            .filter(|declaration| declaration.segment == JackSegment::Arg)
+            .flat_map(|declaration| {
+                declaration
+                    .names
+                    .iter()
+                    .map(|_| Some(declaration.kind.clone()))
+            })
+            .collect();
+
+        Self {
+            key: subroutine.key,
+            kind: Some(subroutine.kind.clone()),
+            params,
+            more_params: false,
+            span: subroutine.span,
+        }
+    }
+}
+
+/// Subroutines of a class by name.
+pub type JackClassSignatures = HashMap<Vec<u8>, JackSignature>;
+
+/// Signatures of every class of a project and of its libraries.
+#[derive(Debug, Default)]
+pub struct JackSymbolTable {
+    classes: HashMap<Vec<u8>, JackClassSignatures>,
+}
+
+impl JackSymbolTable {
+    /// Reads the `.jack` files of the directories, then their `.vmi`
+    /// interfaces of precompiled classes. A class found earlier overrides the
+    /// later ones like it does in the linker. Syntax errors are left for the
+    /// compilation of the file, the subroutines parsed before them are kept.
+    pub async fn read_dirs(dirs: &[PathBuf]) -> Result<Self> {
+        let mut table = Self::default();
+        for dir in dirs.iter() {
+            let mut found = Self::default();
+            for src in files(dir, "jack").await? {
+                let source = fs::read(&src).await?;
+                let mut tokenizer = JackTokenizer::from_bytes(source, true);
+                let (class, _) = JackASTBuilderEngine::new(&mut tokenizer)
+                    .parse_class()
+                    .await;
+                found.add_class(&class);
+            }
+            for src in files(dir, INTERFACE_EXTENSION).await? {
+                let text = fs::read_to_string(&src).await?;
+                let interface = read_interface(&text)
+                    .map_err(|err| Error::new(err.kind(), format!("{}: {}", src.display(), err)))?;
+                found.merge(interface);
+            }
+            table.merge(found);
+        }
+        Ok(table)
+    }
+
+    /// Adds the classes that are not in the table yet.
+    pub fn merge(&mut self, other: Self) {
+        for (class, signatures) in other.classes {
+            self.classes.entry(class).or_insert(signatures);
+        }
+    }
+
+    pub fn add_class(&mut self, class: &JackClass) {
+        self.classes
+            .insert(class.name.0.clone(), class_signatures(class));
+    }
+
+    pub fn insert(&mut self, class: Vec<u8>, name: Vec<u8>, signature: JackSignature) {
+        self.classes
+            .entry(class)
+            .or_default()
+            .insert(name, signature);
+    }
+
+    pub fn class(&self, name: &[u8]) -> Option<&JackClassSignatures> {
+        self.classes.get(name)
+    }
+
+    pub fn classes(&self) -> impl Iterator<Item = (&Vec<u8>, &JackClassSignatures)> {
+        self.classes.iter()
+    }
+}
+
+pub fn class_signatures(class: &JackClass) -> JackClassSignatures {
+    class
+        .subroutines
+        .iter()
+        .map(|subroutine| (subroutine.name.0.clone(), JackSignature::new(subroutine)))
+        .collect()
+}
+
+/// The files of a directory with the extension, none when there is no
+/// directory.
+async fn files(dir: &Path, extension: &str) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    if !dir.is_dir() {
+        return Ok(files);
+    }
+
+    let mut entries = fs::read_dir(dir).await?;
+    while let Some(entry) = entries.next_entry().await? {
+        let path = entry.path();
+        if path.extension() == Some(OsStr::new(extension)) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
@@ -1,12 +1,13 @@
 use std::collections::{HashSet, VecDeque};
-use std::env;
 use std::str::from_utf8;
 
+use file_context::FileSpan;
 use jack_ast::{gramar::*, tokens::JackSymbol};
 use vm_parser::*;
 
 use crate::{
     class::JackClassCompilerContext,
+    diagnostics::Diagnostic,
     vars::{JackTableNames, JackVariable},
 };
 
@@ -74,9 +75,12 @@ pub struct JackSubroutineCompilerContext<'a> {
     statement_idx: usize,
     if_counter: usize,
     while_counter: usize,
-    acc: VecDeque<AsmInstructionPayload>,
+    acc: VecDeque<(AsmInstructionPayload, Option<usize>)>,
+    /// Keyword of the statement being compiled.
+    statement: Option<FileSpan>,
     vars: JackTableNames,
     assignments: HashSet<&'a JackVariableName>,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'a> JackSubroutineCompilerContext<'a> {
@@ -110,8 +114,10 @@ impl<'a> JackSubroutineCompilerContext<'a> {
             if_counter: 0,
             while_counter: 0,
             acc: VecDeque::new(),
+            statement: None,
             assignments: HashSet::new(),
             skip_vars_check,
+            diagnostics: Vec::new(),
         };
 
         res.compile_function_header();
@@ -133,15 +139,15 @@ impl<'a> JackSubroutineCompilerContext<'a> {
 
         match self.subroutine.key {
             JackSubroutineType::Method => {
-                self.acc.push_back(asm);
-                self.acc.push_back(PUSH_ARG_0);
-                self.acc.push_back(POP_THIS);
+                self.push(asm);
+                self.push(PUSH_ARG_0);
+                self.push(POP_THIS);
             }
-            JackSubroutineType::Function => self.acc.push_back(asm),
+            JackSubroutineType::Function => self.push(asm),
             JackSubroutineType::Constructor => {
-                self.acc.push_back(asm);
+                self.push(asm);
                 self.push_const(self.class.vars.fields() as i16);
-                self.acc.push_back({
+                self.push({
                     AsmInstructionPayload::Function(AsmFunctionInstruction::Call(
                         FunctionMetadata {
                             name: b"Memory.alloc".to_vec(),
@@ -149,73 +155,113 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                         },
                     ))
                 });
-                self.acc.push_back(POP_THIS);
+                self.push(POP_THIS);
             }
         }
     }
 
+    /// Problems found so far, the code is still compiled past them.
+    pub fn into_diagnostics(self) -> Vec<Diagnostic> {
+        self.diagnostics
+    }
+
+    fn error(&self, message: String, span: Option<FileSpan>, label: &str) -> Diagnostic {
+        Diagnostic::error(message).with_primary(span, label)
+    }
+
+    fn not_declared(&self, name: &JackVariableName, span: Option<FileSpan>) -> Diagnostic {
+        let diagnostic = self.error(
+            format!("cannot find variable `{}` in this scope", name),
+            span,
+            "not declared",
+        );
+        match self.class.vars.get(name) {
+            Some(var) if var.segment == JackSegment::Field => {
+                diagnostic.with_note("fields can not be used in functions")
+            }
+            _ => diagnostic,
+        }
+    }
+
+    fn push(&mut self, instruction: AsmInstructionPayload) {
+        let line = self.statement.map(|span| span.line);
+        self.acc.push_back((instruction, line))
+    }
+
+    fn compile_statements(&mut self, statements: &'a mut JackStatements) {
+        let JackStatements(statements, spans) = statements;
+        let parent = self.statement;
+        for (statement, span) in statements.iter_mut().zip(spans.iter()) {
+            self.statement = *span;
+            self.compile_statement(statement);
+        }
+        self.statement = parent;
+    }
+
     fn is_assigned(&self, name: &JackVariableName) -> bool {
         self.assignments.contains(name)
     }
 
-    fn get(&self, name: &JackVariableName, skip_checks: bool) -> Option<&JackVariable> {
+    fn get(&self, name: &JackVariableName) -> Option<&JackVariable> {
         if let Some(var) = self.vars.get(name) {
-            if var.segment != JackSegment::Arg
-                && !self.is_assigned(name)
-                && !skip_checks
-                && !self.skip_vars_check
-            {
-                panic!(
-                    "Try use variable {} before assign it",
-                    from_utf8(&name.0).unwrap()
-                );
-            }
             return Some(var);
         }
 
-        if let Some(var) = self.class.vars.get(name) {
-            if self.subroutine.key == JackSubroutineType::Function
-                && var.segment == JackSegment::Field
+        match self.class.vars.get(name) {
+            Some(var)
+                if self.subroutine.key == JackSubroutineType::Function
+                    && var.segment == JackSegment::Field =>
             {
-                return None;
+                None
             }
+            var => var,
+        }
+    }
 
-            if var.segment != JackSegment::Static
-                && self.subroutine.key == JackSubroutineType::Constructor
-                && !self.is_assigned(name)
-                && !skip_checks
-                && !self.skip_vars_check
-            {
-                panic!(
-                    "Try use variable {} before assign it",
-                    from_utf8(&name.0).unwrap()
-                );
+    /// Reads of locals, and of fields in constructors, before their first `let`.
+    fn check_assigned(&mut self, name: &JackVariableName, span: Option<FileSpan>) {
+        if self.skip_vars_check || self.is_assigned(name) {
+            return;
+        }
+
+        let unassigned = match (self.vars.get(name), self.class.vars.get(name)) {
+            (Some(var), _) => var.segment != JackSegment::Arg,
+            (None, Some(var)) => {
+                var.segment == JackSegment::Field
+                    && self.subroutine.key == JackSubroutineType::Constructor
             }
-            return Some(var);
+            (None, None) => false,
+        };
+        if unassigned {
+            let diagnostic = self.used_before_assign(name, span);
+            self.diagnostics.push(diagnostic);
         }
+    }
 
-        None
+    fn used_before_assign(&self, name: &JackVariableName, span: Option<FileSpan>) -> Diagnostic {
+        self.error(
+            format!("variable `{}` is used before it is assigned", name),
+            span,
+            "used here",
+        )
     }
 
     fn assign(&mut self, name: &'a JackVariableName) -> bool {
         self.assignments.insert(name)
     }
 
-    fn push_variable(&mut self, ident: &JackVariableName) {
-        let is_strict = if let Ok(v) = env::var("STRICT_MODE") {
-            v == "1"
-        } else {
-            false
-        };
-        match self.get(&ident, false) {
+    fn push_variable(&mut self, ident: &JackVariableName, span: Option<FileSpan>) {
+        self.check_assigned(ident, span);
+        match self.get(ident) {
             None => {
-                if is_strict {
-                    panic!("Variable {} not declared", from_utf8(&ident.0).unwrap())
+                if is_strict_mode() {
+                    let diagnostic = self.not_declared(ident, span);
+                    self.diagnostics.push(diagnostic);
                 }
                 self.push_const(0);
             }
             Some(v) => {
-                self.acc.push_back(v.as_asm());
+                self.push(v.as_asm());
             }
         };
     }
@@ -224,84 +270,111 @@ impl<'a> JackSubroutineCompilerContext<'a> {
         match statement {
             JackStatement::Do(do_statement) => {
                 self.compile_term(&mut do_statement.call);
-                self.acc.push_back(DROP_STACK_VALUE);
+                self.push(DROP_STACK_VALUE);
             }
             JackStatement::Return(return_statement) => {
                 match &mut return_statement.expression {
                     Some(e) => {
                         if self.subroutine.kind.is_void() {
-                            panic!("Void method should return nothing")
-                        }
-
-                        if !e.is_this() && self.subroutine.key == JackSubroutineType::Constructor {
-                            panic!("Constructor should return this")
+                            let diagnostic = self
+                                .error(
+                                    "a void subroutine cannot return a value".to_string(),
+                                    self.statement,
+                                    "returns a value",
+                                )
+                                .with_secondary(
+                                    self.subroutine.span,
+                                    format!("`{}` is declared `void`", self.subroutine.name),
+                                );
+                            self.diagnostics.push(diagnostic);
+                        } else if !e.is_this()
+                            && self.subroutine.key == JackSubroutineType::Constructor
+                        {
+                            let diagnostic = self
+                                .error(
+                                    "a constructor must return `this`".to_string(),
+                                    self.statement,
+                                    "",
+                                )
+                                .with_secondary(
+                                    self.subroutine.span,
+                                    format!("`{}` is a constructor", self.subroutine.name),
+                                );
+                            self.diagnostics.push(diagnostic);
                         }
 
                         self.compile_expression(e);
                     }
                     None => {
                         if !self.subroutine.kind.is_void() {
-                            panic!("Non void method should a value")
+                            let diagnostic = self
+                                .error("missing return value".to_string(), self.statement, "")
+                                .with_secondary(
+                                    self.subroutine.span,
+                                    format!("`{}` is not declared `void`", self.subroutine.name),
+                                );
+                            self.diagnostics.push(diagnostic);
                         }
                         self.push_const(0);
                     }
                 }
-                self.acc.push_back(AsmInstructionPayload::Function(
+                self.push(AsmInstructionPayload::Function(
                     AsmFunctionInstruction::Return,
                 ));
             }
-            JackStatement::Let(let_statement) => match &mut let_statement.variable.payload {
-                JackTermPayload::ArrayElem(ident, expr) => {
-                    if let Some(var) = self.get(ident, false) {
-                        self.acc.push_back(var.as_asm());
-                        self.compile_expression(expr.as_mut());
-                        self.acc.push_back(ADD);
+            JackStatement::Let(let_statement) => {
+                let span = let_statement.variable.span;
+                match &mut let_statement.variable.payload {
+                    JackTermPayload::ArrayElem(ident, expr) => {
+                        self.check_assigned(ident, span);
+                        if let Some(var) = self.get(ident) {
+                            self.push(var.as_asm());
+                            self.compile_expression(expr.as_mut());
+                            self.push(ADD);
+                            self.compile_expression(&mut let_statement.expression);
+                            self.push(POP_TEMP);
+                            self.push(POP_THAT);
+                            self.push(PUSH_TEMP);
+                            self.push(POP_THAT_0);
+                        } else {
+                            let diagnostic = self.not_declared(ident, span);
+                            self.diagnostics.push(diagnostic);
+                        };
+                    }
+                    JackTermPayload::Ident(ident) => {
                         self.compile_expression(&mut let_statement.expression);
-                        self.acc.push_back(POP_TEMP);
-                        self.acc.push_back(POP_THAT);
-                        self.acc.push_back(PUSH_TEMP);
-                        self.acc.push_back(POP_THAT_0);
-                    } else {
-                        panic!("Not found variable {}", from_utf8(&ident.0).unwrap())
-                    };
-                }
-                JackTermPayload::Ident(ident) => {
-                    self.compile_expression(&mut let_statement.expression);
-                    if let Some(var) = self.get(ident, true) {
-                        self.acc.push_back(var.as_assign_asm());
-                        self.assign(ident)
-                    } else {
-                        panic!("Not found variable {}", from_utf8(&ident.0).unwrap())
-                    };
+                        if let Some(var) = self.get(ident) {
+                            self.push(var.as_assign_asm());
+                            self.assign(ident);
+                        } else {
+                            let diagnostic = self.not_declared(ident, span);
+                            self.diagnostics.push(diagnostic);
+                        };
+                    }
+                    _ => unreachable!(),
                 }
-                _ => unreachable!(),
-            },
+            }
             JackStatement::If(if_statement) => {
                 self.compile_term(&mut if_statement.condition);
 
                 let [true_val, false_val, end_val] = self.build_if_names();
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::IfGoto,
-                        name: true_val.clone(),
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::IfGoto,
+                    name: true_val.clone(),
+                }));
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::Goto,
-                        name: false_val.clone(),
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::Goto,
+                    name: false_val.clone(),
+                }));
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::Label,
-                        name: true_val,
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::Label,
+                    name: true_val,
+                }));
 
-                for statement in if_statement.statements.0.iter_mut() {
-                    self.compile_statement(statement);
-                }
+                self.compile_statements(&mut if_statement.statements);
 
                 let false_label = AsmInstructionPayload::Branch(AsmBranchInstruction {
                     kind: AsmBranchInstructionKind::Label,
@@ -309,59 +382,49 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                 });
 
                 if let Some(statements) = if_statement.else_statements.as_mut() {
-                    self.acc
-                        .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                            kind: AsmBranchInstructionKind::Goto,
-                            name: end_val.clone(),
-                        }));
-                    self.acc.push_back(false_label);
-                    for statement in statements.0.iter_mut() {
-                        self.compile_statement(statement);
-                    }
-                    self.acc
-                        .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                            kind: AsmBranchInstructionKind::Label,
-                            name: end_val,
-                        }));
+                    self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                        kind: AsmBranchInstructionKind::Goto,
+                        name: end_val.clone(),
+                    }));
+                    self.push(false_label);
+                    self.compile_statements(statements);
+                    self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                        kind: AsmBranchInstructionKind::Label,
+                        name: end_val,
+                    }));
                 } else {
-                    self.acc.push_back(false_label);
+                    self.push(false_label);
                 }
             }
             JackStatement::While(while_statement) => {
                 let [exp_val, end_val] = self.build_while_names();
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::Label,
-                        name: exp_val.clone(),
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::Label,
+                    name: exp_val.clone(),
+                }));
 
                 self.compile_term(&mut while_statement.condition);
-                self.acc.push_back(AsmInstructionPayload::Arithmetic(
+                self.push(AsmInstructionPayload::Arithmetic(
                     AsmArithmeticInstruction::Not,
                 ));
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::IfGoto,
-                        name: end_val.clone(),
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::IfGoto,
+                    name: end_val.clone(),
+                }));
 
-                for statement in while_statement.statements.0.iter_mut() {
-                    self.compile_statement(statement);
-                }
+                self.compile_statements(&mut while_statement.statements);
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::Goto,
-                        name: exp_val,
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::Goto,
+                    name: exp_val,
+                }));
 
-                self.acc
-                    .push_back(AsmInstructionPayload::Branch(AsmBranchInstruction {
-                        kind: AsmBranchInstructionKind::Label,
-                        name: end_val,
-                    }));
+                self.push(AsmInstructionPayload::Branch(AsmBranchInstruction {
+                    kind: AsmBranchInstructionKind::Label,
+                    name: end_val,
+                }));
             }
         }
     }
@@ -406,12 +469,27 @@ impl<'a> JackSubroutineCompilerContext<'a> {
     }
 
     fn compile_term(&mut self, term: &mut JackTerm) {
+        let span = term.span;
         match &mut term.payload {
-            JackTermPayload::Int(integer) => self.push_const(integer.to_int()),
+            JackTermPayload::Int(integer) => {
+                match from_utf8(&integer.0)
+                    .ok()
+                    .and_then(|i| i.parse::<i16>().ok())
+                {
+                    Some(i) => self.push_const(i),
+                    None => {
+                        let diagnostic = self
+                            .error("integer is out of range".to_string(), span, "too large")
+                            .with_note("integers go from 0 to 32767");
+                        self.diagnostics.push(diagnostic);
+                        self.push_const(0);
+                    }
+                }
+            }
             JackTermPayload::String(string) => {
                 let size = string.0.len();
                 self.push_const(size as i16);
-                self.acc.push_back(AsmInstructionPayload::Function(
+                self.push(AsmInstructionPayload::Function(
                     AsmFunctionInstruction::Call(FunctionMetadata {
                         name: b"String.new".to_vec(),
                         args_count: 1,
@@ -420,7 +498,7 @@ impl<'a> JackSubroutineCompilerContext<'a> {
 
                 for i in string.0.iter() {
                     self.push_const(*i as i16);
-                    self.acc.push_back(AsmInstructionPayload::Function(
+                    self.push(AsmInstructionPayload::Function(
                         AsmFunctionInstruction::Call(FunctionMetadata {
                             name: b"String.appendChar".to_vec(),
                             args_count: 2,
@@ -428,10 +506,10 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                     ));
                 }
             }
-            JackTermPayload::Const(JackConstantTerm::This) => self.acc.push_back(PUSH_THIS),
+            JackTermPayload::Const(JackConstantTerm::This) => self.push(PUSH_THIS),
             JackTermPayload::Const(JackConstantTerm::True) => {
                 self.push_const(1);
-                self.acc.push_back(AsmInstructionPayload::Arithmetic(
+                self.push(AsmInstructionPayload::Arithmetic(
                     AsmArithmeticInstruction::Neg,
                 ));
             }
@@ -440,11 +518,11 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                 self.compile_expression(expr.as_mut());
             }
             JackTermPayload::Ident(ident) => {
-                self.push_variable(ident);
+                self.push_variable(ident, span);
             }
             JackTermPayload::Unary(op, t) => {
                 self.compile_term(t.as_mut());
-                self.acc.push_back(match op {
+                self.push(match op {
                     JackSymbol::Not => {
                         AsmInstructionPayload::Arithmetic(AsmArithmeticInstruction::Not)
                     }
@@ -455,32 +533,60 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                 });
             }
             JackTermPayload::MethodCall(method_name, expressions) => {
-                self.acc.push_back(PUSH_THIS);
+                self.push(PUSH_THIS);
                 self.call_function(&self.class.class().0, method_name, expressions, true);
             }
 
             JackTermPayload::FunctionCall(class_name, method_name, expressions) => {
-                match self.get(class_name, false) {
+                self.check_assigned(class_name, span);
+                match self.get(class_name) {
                     None => self.call_function(&class_name.0, method_name, expressions, false),
                     Some(var1) => {
                         let v = var1 as *const JackVariable;
                         let var = unsafe { &*v };
-                        self.acc.push_back(var.as_asm());
-                        self.call_function(var.kind.as_slice(), method_name, expressions, true)
+                        self.push(var.as_asm());
+                        match var.kind.as_slice() {
+                            Some(class) => {
+                                self.call_function(class, method_name, expressions, true)
+                            }
+                            None => {
+                                let diagnostic = self
+                                    .error(
+                                        format!("`{}` has no methods", class_name),
+                                        span,
+                                        "not an object",
+                                    )
+                                    .with_note("methods are called on objects, arrays and strings");
+                                self.diagnostics.push(diagnostic);
+                            }
+                        }
                     }
                 }
             }
-            JackTermPayload::ArrayElem(ident, expr) => match self.get(ident, false) {
-                None => panic!("Not found array {}", from_utf8(&ident.0).unwrap()),
-                Some(v) if v.kind.is_array() => {
-                    self.acc.push_back(v.as_asm());
-                    self.compile_expression(expr.as_mut());
-                    self.acc.push_back(ADD);
-                    self.acc.push_back(POP_THAT);
-                    self.acc.push_back(PUSH_THAT_0);
+            JackTermPayload::ArrayElem(ident, expr) => {
+                self.check_assigned(ident, span);
+                match self.get(ident) {
+                    None => {
+                        let diagnostic = self.not_declared(ident, span);
+                        self.diagnostics.push(diagnostic);
+                        self.push_const(0);
+                    }
+                    Some(v) if v.kind.is_array() => {
+                        self.push(v.as_asm());
+                        self.compile_expression(expr.as_mut());
+                        self.push(ADD);
+                        self.push(POP_THAT);
+                        self.push(PUSH_THAT_0);
+                    }
+                    _ => {
+                        let diagnostic = self
+                            .error(format!("`{}` is not an array", ident), span, "indexed here")
+                            .with_note("only `Array` variables can be indexed");
+                        self.diagnostics.push(diagnostic);
+                        self.push_const(0);
+                    }
                 }
-                _ => panic!("{} is not an array", from_utf8(&ident.0).unwrap()),
-            },
+            }
         }
     }
 
@@ -502,7 +608,7 @@ impl<'a> JackSubroutineCompilerContext<'a> {
 
         full_function_name.extend(&function_name.0);
 
-        self.acc.push_back(AsmInstructionPayload::Function(
+        self.push(AsmInstructionPayload::Function(
             AsmFunctionInstruction::Call(FunctionMetadata {
                 name: full_function_name,
                 args_count,
@@ -513,19 +619,18 @@ impl<'a> JackSubroutineCompilerContext<'a> {
     fn compile_expression(&mut self, expression: &mut JackExpression) {
         self.compile_term(&mut expression.term);
 
-        for (op, term) in expression.extra.iter_mut() {
+        for (op, _, term) in expression.extra.iter_mut() {
             self.compile_term(term);
             self.compile_op(op);
         }
     }
 
     fn push_const(&mut self, i: i16) {
-        self.acc
-            .push_back(AsmInstructionPayload::Memory(AsmMemoryInstruction {
-                segment: AsmMemoryInstructionSegment::Const,
-                kind: AsmMemoryInstructionKind::Push,
-                val: i,
-            }))
+        self.push(AsmInstructionPayload::Memory(AsmMemoryInstruction {
+            segment: AsmMemoryInstructionSegment::Const,
+            kind: AsmMemoryInstructionKind::Push,
+            val: i,
+        }))
     }
 
     fn compile_op(&mut self, op: &JackSymbol) {
@@ -549,15 +654,16 @@ impl<'a> JackSubroutineCompilerContext<'a> {
                     args_count: 2,
                 }))
             }
-            _ => panic!("unknown op"),
+            _ => unreachable!(),
         };
 
-        self.acc.push_back(asm_command)
+        self.push(asm_command)
     }
 }
 
+/// Yields VM instructions with the zero based line of the statement they come from.
 impl Iterator for JackSubroutineCompilerContext<'_> {
-    type Item = AsmInstructionPayload;
+    type Item = (AsmInstructionPayload, Option<usize>);
 
     fn next(&mut self) -> Option<Self::Item> {
         if let Some(v) = self.acc.pop_front() {
@@ -567,6 +673,13 @@ impl Iterator for JackSubroutineCompilerContext<'_> {
         while self.statement_idx < self.subroutine.statements.0.len() {
             let link =
                 self.subroutine.statements.0[self.statement_idx].as_mut() as *mut JackStatement;
+            self.statement = self
+                .subroutine
+                .statements
+                .1
+                .get(self.statement_idx)
+                .copied()
+                .flatten();
             self.compile_statement(unsafe { &mut *link });
             self.statement_idx += 1;
             if let Some(v) = self.acc.pop_front() {
@@ -0,0 +1,698 @@
+use std::collections::HashMap;
+
+use file_context::FileSpan;
+use jack_ast::{gramar::*, tokens::JackSymbol};
+
+use crate::{
+    diagnostics::Diagnostic,
+    signatures::{class_signatures, JackClassSignatures, JackSignature, JackSymbolTable},
+};
+
+const INT: JackType = JackType::Basic(JackBasicType::Int);
+const BOOL: JackType = JackType::Basic(JackBasicType::Bool);
+const STRING: JackType = JackType::Basic(JackBasicType::String);
+
+/// Type of an expression.
+#[derive(Debug, Clone, PartialEq)]
+enum JackValueType {
+    Known(JackType),
+    /// `null`, fits any object.
+    Null,
+    /// Array elements, calls of other classes and undeclared names, nothing
+    /// is checked against it.
+    Unknown,
+}
+
+impl JackValueType {
+    fn is(&self, kind: &JackType) -> bool {
+        self == &Self::Known(kind.clone())
+    }
+}
+
+/// What converts to what. Lenient follows the book: `int`, `char` and
+/// `boolean` are all words and `Array` is a pointer to anything. Strict only
+/// mixes `int` with `char`, `Array` is an object like the others.
+#[derive(Debug, Default, Clone, Copy, PartialEq)]
+pub enum JackTypeRules {
+    #[default]
+    Lenient,
+    Strict,
+}
+
+impl JackTypeRules {
+    pub fn new(strict: bool) -> Self {
+        if strict {
+            Self::Strict
+        } else {
+            Self::Lenient
+        }
+    }
+
+    fn is_lenient(self) -> bool {
+        self == Self::Lenient
+    }
+
+    fn is_primitive(kind: &JackType) -> bool {
+        matches!(
+            kind,
+            JackType::Basic(JackBasicType::Int | JackBasicType::Char | JackBasicType::Bool)
+        )
+    }
+
+    fn is_number(self, value: &JackValueType) -> bool {
+        match value {
+            JackValueType::Known(JackType::Basic(JackBasicType::Int | JackBasicType::Char)) => true,
+            JackValueType::Known(JackType::Basic(JackBasicType::Bool | JackBasicType::Arr)) => {
+                self.is_lenient()
+            }
+            JackValueType::Known(_) => false,
+            JackValueType::Null => self.is_lenient(),
+            JackValueType::Unknown => true,
+        }
+    }
+
+    fn is_logical(self, value: &JackValueType) -> bool {
+        value.is(&BOOL) || self.is_number(value)
+    }
+
+    fn is_condition(self, value: &JackValueType) -> bool {
+        match self {
+            Self::Lenient => self.is_logical(value),
+            Self::Strict => value.is(&BOOL) || value == &JackValueType::Unknown,
+        }
+    }
+
+    fn fits(self, to: &JackType, value: &JackValueType) -> bool {
+        let from = match value {
+            JackValueType::Known(from) => from,
+            JackValueType::Null => return to.as_slice().is_some() || self.is_lenient(),
+            JackValueType::Unknown => return true,
+        };
+        if from == to {
+            return true;
+        }
+
+        let number = |kind: &JackType| {
+            matches!(
+                kind,
+                JackType::Basic(JackBasicType::Int | JackBasicType::Char)
+            )
+        };
+        match self {
+            Self::Strict => number(to) && number(from),
+            Self::Lenient => {
+                let pointer = |kind: &JackType| kind.is_array() || Self::is_primitive(kind);
+                let array_of = |array: &JackType, other: &JackType| {
+                    array.is_array() && (pointer(other) || other.as_slice().is_some())
+                };
+                (Self::is_primitive(to) && Self::is_primitive(from))
+                    || array_of(to, from)
+                    || array_of(from, to)
+            }
+        }
+    }
+}
+
+/// How a subroutine is called.
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum JackCall {
+    /// `f()`, a method of `this`.
+    This,
+    /// `x.f()`, a method of the object in `x`.
+    Object,
+    /// `Foo.f()`, a function or a constructor.
+    Class,
+}
+
+/// Type errors of a class. Calls of other classes are checked against the
+/// project, they are not checked without it.
+pub fn check_class(
+    class: &JackClass,
+    rules: JackTypeRules,
+    project: Option<&JackSymbolTable>,
+) -> Vec<Diagnostic> {
+    let own = class_signatures(class);
+
+    let mut diagnostics = Vec::new();
+    for subroutine in class.subroutines.iter() {
+        let mut checker = JackTypeChecker::new(class, &own, project, subroutine, rules);
+        checker.check_statements(&subroutine.statements);
+        diagnostics.extend(checker.diagnostics);
+    }
+    diagnostics
+}
+
+struct JackTypeChecker<'a> {
+    class: &'a JackClass,
+    own: &'a JackClassSignatures,
+    project: Option<&'a JackSymbolTable>,
+    subroutine: &'a JackSubroutine,
+    vars: HashMap<&'a JackVariableName, &'a JackType>,
+    rules: JackTypeRules,
+    /// Keyword of the statement being checked.
+    statement: Option<FileSpan>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a> JackTypeChecker<'a> {
+    fn new(
+        class: &'a JackClass,
+        own: &'a JackClassSignatures,
+        project: Option<&'a JackSymbolTable>,
+        subroutine: &'a JackSubroutine,
+        rules: JackTypeRules,
+    ) -> Self {
+        let mut vars = HashMap::new();
+        let is_function = subroutine.key == JackSubroutineType::Function;
+        for declaration in class.vars.iter() {
+            if is_function && declaration.segment == JackSegment::Field {
+                continue;
+            }
+            for name in declaration.names.iter() {
+                vars.insert(name, &declaration.kind);
+            }
+        }
+        for declaration in subroutine.vars.iter() {
+            for name in declaration.names.iter() {
+                vars.insert(name, &declaration.kind);
+            }
+        }
+
+        Self {
+            class,
+            own,
+            project,
+            subroutine,
+            vars,
+            rules,
+            statement: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    fn mismatch(
+        &self,
+        span: Option<FileSpan>,
+        expected: &JackType,
+        found: &JackValueType,
+    ) -> Diagnostic {
+        let found = match found {
+            JackValueType::Known(kind) => kind.to_string(),
+            JackValueType::Null => "null".to_string(),
+            JackValueType::Unknown => unreachable!(),
+        };
+        Diagnostic::error("mismatched types")
+            .with_primary(span, format!("expected `{}`, found `{}`", expected, found))
+    }
+
+    fn check_statements(&mut self, statements: &JackStatements) {
+        let JackStatements(statements, spans) = statements;
+        let parent = self.statement;
+        for (statement, span) in statements.iter().zip(spans.iter()) {
+            self.statement = *span;
+            self.check_statement(statement);
+        }
+        self.statement = parent;
+    }
+
+    fn check_statement(&mut self, statement: &JackStatement) {
+        match statement {
+            JackStatement::Let(let_statement) => {
+                let value = self.expression_type(&let_statement.expression);
+                match &let_statement.variable.payload {
+                    JackTermPayload::ArrayElem(_, index) => self.check_index(index),
+                    JackTermPayload::Ident(name) => {
+                        if let Some(kind) = self.vars.get(name).copied() {
+                            if !self.rules.fits(kind, &value) {
+                                let span = let_statement.variable.span;
+                                let diagnostic = self.mismatch(span, kind, &value);
+                                self.diagnostics.push(diagnostic);
+                            }
+                        }
+                    }
+                    _ => unreachable!(),
+                }
+            }
+            JackStatement::Do(do_statement) => {
+                self.term_type(&do_statement.call);
+            }
+            JackStatement::Return(return_statement) => {
+                let Some(expression) = &return_statement.expression else {
+                    return;
+                };
+                let value = self.expression_type(expression);
+                // Void subroutines and constructors are checked by the compiler.
+                let kind = &self.subroutine.kind;
+                if kind.is_void() || self.subroutine.key == JackSubroutineType::Constructor {
+                    return;
+                }
+                if !self.rules.fits(kind, &value) {
+                    let diagnostic = self.mismatch(self.statement, kind, &value).with_secondary(
+                        self.subroutine.span,
+                        format!("`{}` returns `{}`", self.subroutine.name, kind),
+                    );
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+            JackStatement::If(if_statement) => {
+                self.check_condition(&if_statement.condition);
+                self.check_statements(&if_statement.statements);
+                if let Some(statements) = &if_statement.else_statements {
+                    self.check_statements(statements);
+                }
+            }
+            JackStatement::While(while_statement) => {
+                self.check_condition(&while_statement.condition);
+                self.check_statements(&while_statement.statements);
+            }
+        }
+    }
+
+    fn check_condition(&mut self, condition: &JackTerm) {
+        let value = self.term_type(condition);
+        if !self.rules.is_condition(&value) {
+            let diagnostic = self.mismatch(self.statement, &BOOL, &value);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn check_index(&mut self, index: &JackExpression) {
+        let value = self.expression_type(index);
+        if !self.rules.is_number(&value) {
+            let diagnostic = self.mismatch(index.term.span, &INT, &value);
+            self.diagnostics.push(diagnostic);
+        }
+    }
+
+    fn expression_type(&mut self, expression: &JackExpression) -> JackValueType {
+        let mut value = self.term_type(&expression.term);
+        for (op, span, term) in expression.extra.iter() {
+            let right = self.term_type(term);
+            value = self.op_type(*op, *span, value, right);
+        }
+        value
+    }
+
+    fn op_type(
+        &mut self,
+        op: JackSymbol,
+        span: Option<FileSpan>,
+        left: JackValueType,
+        right: JackValueType,
+    ) -> JackValueType {
+        let rules = self.rules;
+        let (fits, result) = match op {
+            JackSymbol::Plus | JackSymbol::Minus | JackSymbol::Multiply | JackSymbol::Divide => {
+                (rules.is_number(&left) && rules.is_number(&right), INT)
+            }
+            JackSymbol::Less | JackSymbol::Greater => {
+                (rules.is_number(&left) && rules.is_number(&right), BOOL)
+            }
+            JackSymbol::And | JackSymbol::Or => {
+                let is_bool =
+                    |value: &JackValueType| value.is(&BOOL) || value == &JackValueType::Unknown;
+                let result = if is_bool(&left) && is_bool(&right) {
+                    BOOL
+                } else {
+                    INT
+                };
+                let fits = rules.is_logical(&left) && rules.is_logical(&right);
+                // Strict mode keeps booleans and numbers apart.
+                let mixed = !rules.is_lenient()
+                    && (left.is(&BOOL) != right.is(&BOOL))
+                    && left != JackValueType::Unknown
+                    && right != JackValueType::Unknown;
+                (fits && !mixed, result)
+            }
+            JackSymbol::Eq => {
+                let comparable = match (&left, &right) {
+                    (JackValueType::Known(left_kind), _) if rules.fits(left_kind, &right) => true,
+                    (_, JackValueType::Known(right_kind)) => rules.fits(right_kind, &left),
+                    (JackValueType::Known(_), _) => false,
+                    _ => true,
+                };
+                (comparable, BOOL)
+            }
+            _ => unreachable!(),
+        };
+
+        if !fits {
+            let name = |value: &JackValueType| match value {
+                JackValueType::Known(kind) => kind.to_string(),
+                JackValueType::Null => "null".to_string(),
+                JackValueType::Unknown => "_".to_string(),
+            };
+            let op = op.to_char();
+            let diagnostic = Diagnostic::error(format!(
+                "cannot apply `{}` to `{}` and `{}`",
+                op,
+                name(&left),
+                name(&right)
+            ))
+            .with_primary(span, "");
+            self.diagnostics.push(diagnostic);
+        }
+        JackValueType::Known(result)
+    }
+
+    fn term_type(&mut self, term: &JackTerm) -> JackValueType {
+        match &term.payload {
+            JackTermPayload::Int(_) => JackValueType::Known(INT),
+            JackTermPayload::String(_) => JackValueType::Known(STRING),
+            JackTermPayload::Const(JackConstantTerm::True | JackConstantTerm::False) => {
+                JackValueType::Known(BOOL)
+            }
+            JackTermPayload::Const(JackConstantTerm::Null) => JackValueType::Null,
+            JackTermPayload::Const(JackConstantTerm::This) => {
+                JackValueType::Known(match self.class.name.0.as_slice() {
+                    b"String" => STRING,
+                    b"Array" => JackType::Basic(JackBasicType::Arr),
+                    class => JackType::Class(class.to_vec()),
+                })
+            }
+            JackTermPayload::Expression(expression) => self.expression_type(expression),
+            JackTermPayload::Unary(op, term) => {
+                let value = self.term_type(term);
+                let (fits, result) = match op {
+                    JackSymbol::Minus => (self.rules.is_number(&value), INT),
+                    _ if value.is(&BOOL) => (true, BOOL),
+                    _ => (self.rules.is_logical(&value), INT),
+                };
+                if !fits {
+                    let diagnostic = self.mismatch(term.span, &result, &value);
+                    self.diagnostics.push(diagnostic);
+                }
+                JackValueType::Known(result)
+            }
+            JackTermPayload::Ident(name) => match self.vars.get(name) {
+                Some(kind) => JackValueType::Known((*kind).clone()),
+                None => JackValueType::Unknown,
+            },
+            JackTermPayload::ArrayElem(_, index) => {
+                self.check_index(index);
+                JackValueType::Unknown
+            }
+            JackTermPayload::MethodCall(name, args) => {
+                let class = &self.class.name.0;
+                self.call_type(JackCall::This, class, name, args, term)
+            }
+            JackTermPayload::FunctionCall(target, name, args) => match self.vars.get(target) {
+                Some(kind) => match kind.as_slice() {
+                    Some(class) => self.call_type(JackCall::Object, class, name, args, term),
+                    // Reported by the compiler.
+                    None => {
+                        self.args_types(args);
+                        JackValueType::Unknown
+                    }
+                },
+                None => self.call_type(JackCall::Class, &target.0, name, args, term),
+            },
+        }
+    }
+
+    fn args_types(&mut self, args: &JackExpressions) -> Vec<JackValueType> {
+        args.data
+            .iter()
+            .map(|arg| self.expression_type(arg))
+            .collect()
+    }
+
+    /// The signature of `class.name`, errors when the class or the subroutine
+    /// are not there. `term` is the call, its spans are reported.
+    fn signature(
+        &mut self,
+        call: JackCall,
+        class: &[u8],
+        name: &JackVariableName,
+        term: &JackTerm,
+    ) -> Option<&'a JackSignature> {
+        let signatures = if class == self.class.name.0 {
+            self.own
+        } else {
+            let project = self.project?;
+            match project.class(class) {
+                Some(signatures) => signatures,
+                None => {
+                    let class = String::from_utf8_lossy(class);
+                    let (span, label) = match call {
+                        JackCall::Class => (
+                            term.span,
+                            "not a class of the project or the OS".to_string(),
+                        ),
+                        _ => (term.name_span, format!("called on a `{}`", class)),
+                    };
+                    let diagnostic = Diagnostic::error(format!("cannot find class `{}`", class))
+                        .with_primary(span, label);
+                    self.diagnostics.push(diagnostic);
+                    return None;
+                }
+            }
+        };
+
+        let signature = signatures.get(&name.0);
+        if signature.is_none() {
+            let diagnostic = Diagnostic::error(format!(
+                "no subroutine `{}` in class `{}`",
+                name,
+                String::from_utf8_lossy(class)
+            ))
+            .with_primary(term.name_span, "not found");
+            self.diagnostics.push(diagnostic);
+        }
+        signature
+    }
+
+    /// Checks the call against the signature when the class is known, the
+    /// value is of its return type.
+    fn call_type(
+        &mut self,
+        call: JackCall,
+        class: &[u8],
+        name: &JackVariableName,
+        args: &JackExpressions,
+        term: &JackTerm,
+    ) -> JackValueType {
+        let values = self.args_types(args);
+        let Some(signature) = self.signature(call, class, name, term) else {
+            return JackValueType::Unknown;
+        };
+        // Spans of other classes point into other files.
+        let span = if class == self.class.name.0 {
+            signature.span
+        } else {
+            None
+        };
+        let full_name = format!("{}.{}", String::from_utf8_lossy(class), name);
+        let name_span = term.name_span;
+
+        let key = |key: JackSubroutineType| match key {
+            JackSubroutineType::Method => "method",
+            JackSubroutineType::Function => "function",
+            JackSubroutineType::Constructor => "constructor",
+        };
+        let misuse = match (call, signature.key) {
+            (JackCall::Class, JackSubroutineType::Method) => Some((
+                format!("`{}` is a method", full_name),
+                "called on the class",
+                format!(
+                    "methods are called on an object of `{}`",
+                    String::from_utf8_lossy(class)
+                ),
+            )),
+            (
+                JackCall::This | JackCall::Object,
+                JackSubroutineType::Function | JackSubroutineType::Constructor,
+            ) => Some((
+                format!("`{}` is a {}", full_name, key(signature.key)),
+                "called on an object",
+                format!("call it as `{}()`", full_name),
+            )),
+            (JackCall::This, JackSubroutineType::Method)
+                if self.subroutine.key == JackSubroutineType::Function =>
+            {
+                Some((
+                    format!("method `{}` is called from a function", name),
+                    "no object to call it on",
+                    "functions have no `this`".to_string(),
+                ))
+            }
+            _ => None,
+        };
+        if let Some((message, label, note)) = misuse {
+            let diagnostic = Diagnostic::error(message)
+                .with_primary(name_span, label)
+                .with_secondary(span, format!("`{}` is declared here", name))
+                .with_note(note);
+            self.diagnostics.push(diagnostic);
+        }
+
+        let params = &signature.params;
+        let arity = if signature.more_params {
+            params.len() <= values.len()
+        } else {
+            params.len() == values.len()
+        };
+        if !arity {
+            let plural = |count: usize| if count == 1 { "" } else { "s" };
+            let diagnostic = Diagnostic::error(format!(
+                "`{}` takes {}{} argument{} but {} {} given",
+                full_name,
+                if signature.more_params {
+                    "at least "
+                } else {
+                    ""
+                },
+                params.len(),
+                plural(params.len()),
+                values.len(),
+                if values.len() == 1 { "was" } else { "were" }
+            ))
+            .with_primary(name_span, "")
+            .with_secondary(span, "defined here");
+            self.diagnostics.push(diagnostic);
+        } else {
+            for (idx, (param, value)) in params.iter().zip(values.iter()).enumerate() {
+                let Some(param) = param else {
+                    continue;
+                };
+                if !self.rules.fits(param, value) {
+                    let diagnostic = self.mismatch(name_span, param, value).with_note(format!(
+                        "argument {} of `{}` is `{}`",
+                        idx + 1,
+                        full_name,
+                        param
+                    ));
+                    self.diagnostics.push(diagnostic);
+                }
+            }
+        }
+
+        match &signature.kind {
+            Some(kind) => JackValueType::Known(kind.clone()),
+            None => JackValueType::Unknown,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use jack_ast::tokens::JackTokenizer;
+
+    use super::*;
+
+    async fn parse(source: &str) -> JackClass {
+        let mut tokenizer = JackTokenizer::from_bytes(source.as_bytes().to_vec(), true);
+        JackASTBuilderEngine::new(&mut tokenizer)
+            .build_class()
+            .await
+            .unwrap()
+    }
+
+    async fn check(
+        source: &str,
+        rules: JackTypeRules,
+        project: Option<&JackSymbolTable>,
+    ) -> Vec<String> {
+        let class = parse(source).await;
+        check_class(&class, rules, project)
+            .iter()
+            .map(ToString::to_string)
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn type_rules_test() {
+        let source = "class Main {
+    function int main() {
+        var int a;
+        var boolean b;
+        var String s;
+        var Array arr;
+        let b = a;
+        let arr = 5;
+        let a = s;
+        let a = Main.main(7);
+        if (a) {
+            return s;
+        }
+        return b = null;
+    }
+}";
+        assert_eq!(
+            check(source, JackTypeRules::Lenient, None).await,
+            vec![
+                "9:13: mismatched types",
+                "10:22: `Main.main` takes 0 arguments but 1 was given",
+                "12:13: mismatched types",
+            ]
+        );
+        assert_eq!(
+            check(source, JackTypeRules::Strict, None).await,
+            vec![
+                "7:13: mismatched types",
+                "8:13: mismatched types",
+                "9:13: mismatched types",
+                "10:22: `Main.main` takes 0 arguments but 1 was given",
+                "11:9: mismatched types",
+                "12:13: mismatched types",
+                "14:18: cannot apply `=` to `boolean` and `null`",
+                "14:9: mismatched types",
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn project_calls_test() {
+        let mut project = JackSymbolTable::default();
+        project.add_class(
+            &parse(
+                "class Foo {
+    function void bar(int a, int b) {
+        return;
+    }
+
+    method int size() {
+        return 1;
+    }
+}",
+            )
+            .await,
+        );
+
+        let source = "class Main {
+    function void main() {
+        var Foo foo;
+        do Foo.bar(1);
+        do Missing.run();
+        do Foo.nope();
+        do Foo.size();
+        do foo.bar(1, 2);
+        do helper();
+        do Foo.bar(foo.size(), true);
+        return;
+    }
+
+    method void helper() {
+        return;
+    }
+}";
+        let expected = vec![
+            "4:16: `Foo.bar` takes 2 arguments but 1 was given",
+            "5:12: cannot find class `Missing`",
+            "6:16: no subroutine `nope` in class `Foo`",
+            "7:16: `Foo.size` is a method",
+            "8:16: `Foo.bar` is a function",
+            "9:12: method `helper` is called from a function",
+        ];
+        assert_eq!(
+            check(source, JackTypeRules::Lenient, Some(&project)).await,
+            expected
+        );
+
+        // Without the project only the class itself is known.
+        assert_eq!(
+            check(source, JackTypeRules::Lenient, None).await,
+            vec!["9:12: method `helper` is called from a function"]
+        );
+    }
+}
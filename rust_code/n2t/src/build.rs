@@ -0,0 +1,278 @@
+//! `n2t build`: Jack classes compiled straight into the VM translator.
+
+use std::collections::HashMap;
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use jack_compiler::{JackSymbolTable, JackTypeRules, VmOutput};
+use vm_translator::{resolve_files, ProgramOpts, VmInput};
+
+use crate::{located, with_path};
+
+/// Hashes of the sources compiled by the last incremental build.
+pub const CACHE_FILE: &str = ".n2t_build";
+
+pub struct BuildOpts {
+    pub program: ProgramOpts,
+    /// `.vm` and `.vm.map` files next to the sources, as `n2t compile` writes them.
+    pub write_vm: bool,
+    /// Reuses the `.vm` of a class whose source did not change since the
+    /// last incremental build, the `.vm` files are always written.
+    pub incremental: bool,
+    pub type_rules: JackTypeRules,
+}
+
+/// Classes of the project by whether they were compiled.
+#[derive(Debug, Default, PartialEq)]
+pub struct BuildReport {
+    pub compiled: Vec<PathBuf>,
+    pub reused: Vec<PathBuf>,
+}
+
+/// Compiles the `.jack` files of `dir` and translates them with the other
+/// `.vm` files of `dir` and the libraries to `out`.
+pub async fn build(dir: &Path, out: &Path, opts: &BuildOpts) -> io::Result<BuildReport> {
+    let write_vm = opts.write_vm || opts.incremental;
+    let cache_path = dir.join(CACHE_FILE);
+    let cache = if opts.incremental {
+        read_cache(&cache_path)?
+    } else {
+        HashMap::new()
+    };
+
+    // Calls are checked against the project and the libraries, in the order
+    // they are linked.
+    let mut search_path = vec![dir.to_path_buf()];
+    search_path.extend(opts.program.library_dirs.iter().cloned());
+    let project = JackSymbolTable::read_dirs(&search_path).await?;
+
+    let mut report = BuildReport::default();
+    let mut hashes = Vec::new();
+    let mut inputs = Vec::new();
+
+    for src in jack_files(dir)? {
+        let source = fs::read(&src).map_err(|err| with_path(&src, err))?;
+        let name = src.file_name().unwrap().to_string_lossy().to_string();
+        let hash = source_hash(&source, opts.type_rules);
+        let vm_path = src.with_extension("vm");
+
+        if opts.incremental && cache.get(&name) == Some(&hash) && vm_path.exists() {
+            // The source did not change, the classes it calls may have.
+            jack_compiler::check_source(source.clone(), opts.type_rules, Some(&project))
+                .await
+                .map_err(|diagnostics| diagnostics.located(&src, &source))?;
+            inputs.push(VmInput::read(&vm_path).await?);
+            report.reused.push(src);
+        } else {
+            let instructions = jack_compiler::compile_instructions(
+                source.clone(),
+                opts.type_rules,
+                Some(&project),
+            )
+            .await
+            .map_err(|diagnostics| diagnostics.located(&src, &source))?;
+            let vm = VmOutput::new(&instructions, &src.display().to_string());
+
+            if write_vm {
+                let mut map = Vec::new();
+                vm.source_map.write(&mut map);
+                fs::write(&vm_path, &vm.code).map_err(|err| with_path(&vm_path, err))?;
+                fs::write(vm_path.with_extension("vm.map"), map)?;
+            }
+
+            let payloads = instructions.into_iter().map(|(i, _)| i).collect();
+            inputs.push(VmInput::from_instructions(
+                vm_path,
+                payloads,
+                Some(vm.source_map),
+            ));
+            report.compiled.push(src);
+        }
+        hashes.push((name, hash));
+    }
+
+    // Hand-written `.vm` files of the project, then the libraries.
+    for path in resolve_files(&search_path).await? {
+        if !inputs
+            .iter()
+            .any(|input| input.path.file_stem() == path.file_stem())
+        {
+            inputs.push(VmInput::read(&path).await?);
+        }
+    }
+
+    let output = vm_translator::translate_vm(inputs, true, &opts.program).await?;
+    vm_translator::write_output(&output, out, &opts.program).await?;
+
+    if opts.incremental {
+        let cache: String = hashes
+            .iter()
+            .map(|(name, hash)| format!("{} {:016x}\n", name, hash))
+            .collect();
+        fs::write(&cache_path, cache).map_err(|err| with_path(&cache_path, err))?;
+        eprintln!(
+            "Compiled {} classes, reused {}",
+            report.compiled.len(),
+            report.reused.len()
+        );
+    }
+
+    Ok(report)
+}
+
+/// Sorted, so the program does not depend on the directory order.
+fn jack_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).map_err(|err| with_path(dir, err))? {
+        let path = entry?.path();
+        if path.extension() == Some(OsStr::new("jack")) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// `Main.jack 0123456789abcdef` lines, a missing file is an empty cache.
+fn read_cache(path: &Path) -> io::Result<HashMap<String, u64>> {
+    if !path.exists() {
+        return Ok(HashMap::new());
+    }
+
+    let mut cache = HashMap::new();
+    for line in fs::read_to_string(path)?.lines() {
+        let hash = line
+            .split_once(' ')
+            .and_then(|(name, hash)| Some((name, u64::from_str_radix(hash, 16).ok()?)));
+        match hash {
+            Some((name, hash)) => cache.insert(name.to_string(), hash),
+            None => return Err(located(path, format!("wrong line {}", line))),
+        };
+    }
+    Ok(cache)
+}
+
+/// FNV-1a of the source, strict mode and the type rules are hashed too as
+/// they change what compiles.
+fn source_hash(source: &[u8], rules: JackTypeRules) -> u64 {
+    let strict = [
+        jack_compiler::is_strict_mode() as u8,
+        (rules == JackTypeRules::Strict) as u8,
+    ];
+    source
+        .iter()
+        .chain(&strict)
+        .fold(0xcbf29ce484222325, |hash, byte| {
+            (hash ^ *byte as u64).wrapping_mul(0x100000001b3)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use jack_compiler::CompileOpts;
+
+    use super::*;
+
+    const SEVEN: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/../../static/jack/Seven/Main.jack"
+    );
+
+    fn project(name: &str) -> PathBuf {
+        // Unique to the process, so that concurrent runs do not share it.
+        let dir = std::env::temp_dir().join(format!("{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        fs::copy(SEVEN, dir.join("Main.jack")).unwrap();
+        dir
+    }
+
+    fn opts(incremental: bool) -> BuildOpts {
+        let mut program = ProgramOpts::new();
+        program.binary = true;
+        BuildOpts {
+            program,
+            write_vm: false,
+            incremental,
+            type_rules: JackTypeRules::Lenient,
+        }
+    }
+
+    #[tokio::test]
+    async fn same_as_files_test() {
+        let dir = project("n2t_same_as_files_test");
+        let out = dir.join("Seven.hack");
+        build(&dir, &out, &opts(false)).await.unwrap();
+        assert!(!dir.join("Main.vm").exists());
+        let built = fs::read(&out).unwrap();
+
+        let compile_opts = CompileOpts {
+            library_dirs: opts(false).program.library_dirs,
+            ..Default::default()
+        };
+        jack_compiler::compile(&dir, None, compile_opts)
+            .await
+            .unwrap();
+        vm_translator::translate(&dir, &out, &opts(false).program)
+            .await
+            .unwrap();
+        assert_eq!(built, fs::read(&out).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn incremental_test() {
+        let dir = project("n2t_incremental_test");
+        let out = dir.join("Seven.hack");
+        let main = dir.join("Main.jack");
+
+        let report = build(&dir, &out, &opts(true)).await.unwrap();
+        assert_eq!(report.compiled, vec![main.clone()]);
+        assert!(dir.join("Main.vm").exists());
+        let built = fs::read(&out).unwrap();
+
+        let report = build(&dir, &out, &opts(true)).await.unwrap();
+        assert_eq!(report.reused, vec![main.clone()]);
+        assert_eq!(built, fs::read(&out).unwrap());
+
+        let source = fs::read_to_string(&main).unwrap();
+        fs::write(&main, source.replace("1 + (2 * 3)", "2 + (2 * 3)")).unwrap();
+        let report = build(&dir, &out, &opts(true)).await.unwrap();
+        assert_eq!(report.compiled, vec![main]);
+        assert_ne!(built, fs::read(&out).unwrap());
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn incremental_signatures_test() {
+        let dir = project("n2t_incremental_signatures_test");
+        let out = dir.join("Seven.hack");
+        let main = dir.join("Main.jack");
+        let foo = dir.join("Foo.jack");
+
+        let source = fs::read_to_string(&main).unwrap();
+        fs::write(&main, source.replace("1 + (2 * 3)", "Foo.bar(1)")).unwrap();
+        let foo_source = "class Foo {
+    function int bar(int x) {
+        return x;
+    }
+}";
+        fs::write(&foo, foo_source).unwrap();
+        build(&dir, &out, &opts(true)).await.unwrap();
+
+        fs::write(&foo, foo_source.replace("int x", "int x, int y")).unwrap();
+        let err = build(&dir, &out, &opts(true)).await.unwrap_err();
+        assert!(
+            err.to_string()
+                .contains("`Foo.bar` takes 2 arguments but 1 was given"),
+            "{}",
+            err
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
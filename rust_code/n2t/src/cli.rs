@@ -0,0 +1,235 @@
+use std::path::PathBuf;
+
+use clap::{Args, Parser, Subcommand};
+use hack_executor::MemorySelection;
+use vm_translator::{default_os_dir, ProgramOpts};
+
+/// The nand2tetris toolchain: Jack compiler, VM translator, assembler and emulator.
+#[derive(Parser)]
+#[command(name = "n2t", version)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Compile .jack files to .vm
+    Compile {
+        /// A .jack file or a directory of them
+        path: PathBuf,
+        /// Directory for the .vm files, next to the sources by default
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Check name styles and undeclared variables
+        #[arg(long)]
+        strict: bool,
+        /// Only mix `int` with `char`, `Array` is an object like the others
+        #[arg(long)]
+        strict_types: bool,
+        /// Write a .vm.map from .vm lines to .jack lines
+        #[arg(long)]
+        source_map: bool,
+        #[command(flatten)]
+        libraries: LibraryFlags,
+    },
+    /// Translate .vm files to .asm or .hack
+    Translate {
+        /// A .vm file, or a directory linked with the OS
+        path: PathBuf,
+        /// Output file, `dir/dir.asm` or `file.asm` by default
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Write .hack instead of .asm
+        #[arg(long)]
+        binary: bool,
+        #[command(flatten)]
+        flags: TranslateFlags,
+    },
+    /// Assemble a .asm file to .hack
+    Assemble {
+        path: PathBuf,
+        /// Output file, next to the source by default
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Write a .sym file with labels and variables
+        #[arg(long)]
+        symbols: bool,
+    },
+    /// Run a .hack program
+    Run {
+        path: PathBuf,
+        #[arg(long, default_value_t = 10_000_000)]
+        max_cycles: u64,
+        /// Print RAM instead of the top of the stack, e.g. `SP,256..260`
+        #[arg(long, value_delimiter = ',', value_parser = parse_selection)]
+        print: Vec<MemorySelection>,
+        /// Stop on faults like reads of never written RAM
+        #[arg(long)]
+        checked: bool,
+        /// Print the VM call stack at the end
+        #[arg(long)]
+        backtrace: bool,
+    },
+    /// Compile a Jack directory straight to .hack, without .vm files in between
+    Build {
+        path: PathBuf,
+        /// Output file, `dir/dir.hack` by default
+        #[arg(long)]
+        out: Option<PathBuf>,
+        /// Write .asm instead of .hack
+        #[arg(long)]
+        asm: bool,
+        /// Check name styles and undeclared variables
+        #[arg(long)]
+        strict: bool,
+        /// Only mix `int` with `char`, `Array` is an object like the others
+        #[arg(long)]
+        strict_types: bool,
+        /// Write the .vm files next to the sources too
+        #[arg(long)]
+        write_vm: bool,
+        /// Compile only the classes changed since the last incremental build
+        #[arg(long)]
+        incremental: bool,
+        #[command(flatten)]
+        flags: TranslateFlags,
+    },
+    /// Write the subroutines of precompiled .vm files to a .vmi interface
+    Interface {
+        /// A .vm file or a directory of them
+        path: PathBuf,
+        /// Output file, `dir/dir.vmi` or `file.vmi` by default
+        #[arg(long)]
+        out: Option<PathBuf>,
+    },
+}
+
+#[derive(Args)]
+pub struct TranslateFlags {
+    /// Leave comments out of .asm
+    #[arg(long)]
+    no_comments: bool,
+    /// Run the peephole optimizer
+    #[arg(long)]
+    optimize: bool,
+    /// Emit compare, call and return once and jump to them
+    #[arg(long)]
+    shared_routines: bool,
+    /// Write a .sym file with labels and variables, only for .hack
+    #[arg(long)]
+    symbols: bool,
+    /// Write a source map from ROM addresses to .vm and .jack lines
+    #[arg(long)]
+    pub source_map: bool,
+    /// List the functions dropped by the linker
+    #[arg(long)]
+    link_report: bool,
+    #[command(flatten)]
+    libraries: LibraryFlags,
+}
+
+/// Where classes missing from the project are looked up.
+#[derive(Args)]
+pub struct LibraryFlags {
+    /// OS directory linked last, `$N2T_OS_DIR`, `bifs` next to n2t or static/bifs by default
+    #[arg(long)]
+    os_dir: Option<PathBuf>,
+    /// Link without the OS
+    #[arg(long, conflicts_with = "os_dir")]
+    no_os: bool,
+    /// Library directory searched after the project and before the OS
+    #[arg(short = 'L', value_name = "DIR")]
+    libraries: Vec<PathBuf>,
+}
+
+impl TranslateFlags {
+    pub fn program_opts(&self, binary: bool) -> ProgramOpts {
+        let mut opts = ProgramOpts::new();
+        opts.translate
+            .set_comments(!self.no_comments)
+            .set_optimize(self.optimize)
+            .set_shared_routines(self.shared_routines);
+        opts.binary = binary;
+        opts.symbols = self.symbols;
+        opts.source_map = self.source_map;
+        opts.link_report = self.link_report;
+        opts.library_dirs = self.libraries.library_dirs();
+        opts
+    }
+}
+
+impl LibraryFlags {
+    /// The libraries in the given order, then the OS.
+    pub fn library_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.libraries.clone();
+        if !self.no_os {
+            dirs.push(self.os_dir.clone().unwrap_or_else(default_os_dir));
+        }
+        dirs
+    }
+}
+
+fn parse_selection(s: &str) -> Result<MemorySelection, String> {
+    MemorySelection::parse(s).ok_or_else(|| format!("unknown address {}", s))
+}
+
+#[cfg(test)]
+mod tests {
+    use clap::CommandFactory;
+
+    use super::*;
+
+    #[test]
+    fn cli_test() {
+        Cli::command().debug_assert();
+    }
+
+    #[test]
+    fn library_dirs_test() {
+        let cli = Cli::parse_from([
+            "n2t",
+            "translate",
+            "Pong",
+            "-L",
+            "lib",
+            "-Lmore",
+            "--binary",
+        ]);
+        let Command::Translate { binary, flags, .. } = cli.command else {
+            panic!("not a translate command")
+        };
+        let opts = flags.program_opts(binary);
+        assert!(opts.binary);
+        assert_eq!(
+            opts.library_dirs,
+            vec![
+                PathBuf::from("lib"),
+                PathBuf::from("more"),
+                default_os_dir()
+            ]
+        );
+
+        let cli = Cli::parse_from(["n2t", "build", "Pong", "--no-os", "-L", "lib"]);
+        let Command::Build { flags, .. } = cli.command else {
+            panic!("not a build command")
+        };
+        assert_eq!(
+            flags.program_opts(true).library_dirs,
+            vec![PathBuf::from("lib")]
+        );
+
+        assert!(
+            Cli::try_parse_from(["n2t", "build", "Pong", "--no-os", "--os-dir", "os"]).is_err()
+        );
+
+        let cli = Cli::parse_from(["n2t", "compile", "Pong", "-L", "lib", "--os-dir", "os"]);
+        let Command::Compile { libraries, .. } = cli.command else {
+            panic!("not a compile command")
+        };
+        assert_eq!(
+            libraries.library_dirs(),
+            vec![PathBuf::from("lib"), PathBuf::from("os")]
+        );
+    }
+}
@@ -0,0 +1,224 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::process::ExitCode;
+
+use clap::Parser;
+use file_context::SourceMap;
+use hack_assembler::SymbolTable;
+use hack_executor::{address_name, CallStack, Checker, HackExecutor, HaltReason, MemorySelection};
+use jack_compiler::{
+    CompileOpts, JackTypeRules, SourceDiagnostics, VmSignatures, INTERFACE_EXTENSION,
+};
+
+mod build;
+mod cli;
+use build::BuildOpts;
+use cli::{Cli, Command};
+
+#[tokio::main]
+async fn main() -> ExitCode {
+    execute(Cli::parse().command).await.unwrap_or_else(|err| {
+        match SourceDiagnostics::of(&err) {
+            Some(diagnostics) => eprintln!("{}", diagnostics),
+            None => eprintln!("error: {}", err),
+        }
+        ExitCode::FAILURE
+    })
+}
+
+async fn execute(command: Command) -> io::Result<ExitCode> {
+    match command {
+        Command::Compile {
+            path,
+            out,
+            strict,
+            strict_types,
+            source_map,
+            libraries,
+        } => {
+            jack_compiler::set_strict_mode(strict);
+            let opts = CompileOpts {
+                source_map,
+                type_rules: JackTypeRules::new(strict_types),
+                library_dirs: libraries.library_dirs(),
+            };
+            jack_compiler::compile(&path, out.as_deref(), opts).await?;
+        }
+        Command::Translate {
+            path,
+            out,
+            binary,
+            flags,
+        } => {
+            let opts = flags.program_opts(binary);
+            let out = match out {
+                Some(out) => out,
+                None => opts.default_output(&path)?,
+            };
+            vm_translator::translate(&path, &out, &opts).await?;
+        }
+        Command::Assemble { path, out, symbols } => {
+            let out = out.unwrap_or_else(|| path.with_extension("hack"));
+            assemble(&path, &out, symbols)?;
+        }
+        Command::Run {
+            path,
+            max_cycles,
+            print,
+            checked,
+            backtrace,
+        } => return run(&path, max_cycles, &print, checked, backtrace),
+        Command::Build {
+            path,
+            out,
+            asm,
+            strict,
+            strict_types,
+            write_vm,
+            incremental,
+            flags,
+        } => {
+            jack_compiler::set_strict_mode(strict);
+            let opts = BuildOpts {
+                program: flags.program_opts(!asm),
+                type_rules: JackTypeRules::new(strict_types),
+                write_vm,
+                incremental,
+            };
+            let out = match out {
+                Some(out) => out,
+                None => opts.program.default_output(&path)?,
+            };
+            build::build(&path, &out, &opts).await?;
+        }
+        Command::Interface { path, out } => {
+            let out = match out {
+                Some(out) => out,
+                None => vm_translator::output_path(&path, INTERFACE_EXTENSION)?,
+            };
+            interface(&path, &out).await?;
+        }
+    }
+
+    Ok(ExitCode::SUCCESS)
+}
+
+fn assemble(path: &Path, out: &Path, with_symbols: bool) -> io::Result<()> {
+    let src = fs::read(path).map_err(|err| with_path(path, err))?;
+    let instructions = hack_assembler::parse(&src).map_err(|err| located(path, err))?;
+    let mut symbols =
+        hack_assembler::resolve_symbols(&instructions).map_err(|err| located(path, err))?;
+
+    if with_symbols {
+        let mut symbol_file = Vec::new();
+        symbols.write_symbol_file(&mut symbol_file);
+        fs::write(out.with_extension("sym"), symbol_file)?;
+    }
+
+    let mut hack = Vec::new();
+    hack_assembler::write_hack(&instructions, &mut symbols, &mut hack);
+    fs::write(out, hack)
+}
+
+/// Lists what the `.vm` files define, for the Jack compiler to check calls of
+/// libraries without sources.
+async fn interface(path: &Path, out: &Path) -> io::Result<()> {
+    let mut signatures = VmSignatures::default();
+    for file in vm_translator::resolve_files(&[path.to_path_buf()]).await? {
+        let code = fs::read(&file).map_err(|err| with_path(&file, err))?;
+        signatures
+            .add_code(code)
+            .await
+            .map_err(|err| located(&file, err))?;
+    }
+    fs::write(out, jack_compiler::write_interface(&signatures.build()))
+}
+
+/// Fails when the program faults or jumps out of ROM.
+fn run(
+    path: &Path,
+    max_cycles: u64,
+    print: &[MemorySelection],
+    checked: bool,
+    backtrace: bool,
+) -> io::Result<ExitCode> {
+    let src = fs::read_to_string(path).map_err(|err| with_path(path, err))?;
+    let program =
+        HackExecutor::parse_program(&src).ok_or_else(|| located(path, "wrong .hack format"))?;
+    let mut executor = HackExecutor::new(program);
+
+    let result = if checked {
+        Checker::new().run_until_halt(&mut executor, max_cycles, HackExecutor::step)
+    } else {
+        Ok(executor.run_until_halt(max_cycles))
+    };
+
+    let mut call_stack = CallStack::new(&read_symbols(&path.with_extension("sym"))?);
+    let map_path = path.with_extension("hack.map");
+    if map_path.exists() {
+        let source_map = SourceMap::parse(&fs::read_to_string(&map_path)?)
+            .map_err(|err| located(&map_path, err))?;
+        call_stack.set_source_map(source_map);
+    }
+
+    let failed = match &result {
+        Ok(reason) => {
+            eprintln!(
+                "{} after {} cycles, PC = {}",
+                reason,
+                executor.cycles(),
+                executor.pc
+            );
+            *reason == HaltReason::PcOutOfRom
+        }
+        Err(fault) => {
+            eprintln!("{}", fault);
+            for location in call_stack.location(fault.pc) {
+                eprintln!("    at {}", location);
+            }
+            true
+        }
+    };
+    if backtrace || failed && call_stack.has_functions() {
+        call_stack.write_backtrace(&executor, &mut io::stderr().lock())?;
+    }
+
+    if print.is_empty() {
+        println!("{}", executor.result());
+    }
+    for selection in print {
+        for addr in selection.addresses(&executor) {
+            let value = executor.read_memory(addr);
+            match address_name(addr) {
+                Some(name) => println!("RAM[{}] ({}) = {}", addr, name, value),
+                None => println!("RAM[{}] = {}", addr, value),
+            }
+        }
+    }
+
+    Ok(if failed {
+        ExitCode::FAILURE
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// An empty table when the program has no `.sym` file.
+fn read_symbols(path: &Path) -> io::Result<SymbolTable> {
+    if !path.exists() {
+        return Ok(SymbolTable::new());
+    }
+    SymbolTable::from_symbol_file(&fs::read(path)?).map_err(|err| located(path, err))
+}
+
+fn located(path: &Path, err: impl std::fmt::Display) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("{}:{}", path.display(), err),
+    )
+}
+
+fn with_path(path: &Path, err: io::Error) -> io::Error {
+    io::Error::new(err.kind(), format!("{}: {}", path.display(), err))
+}
@@ -0,0 +1,72 @@
+use std::{error, fmt, io, str::from_utf8};
+
+use file_context::FileContext;
+
+#[derive(Debug)]
+pub enum VmParseError {
+    Io(io::Error),
+    InvalidComment(FileContext<Vec<u8>>),
+    UnknownInstruction(FileContext<Vec<u8>>),
+    UnknownSegment(FileContext<Vec<u8>>),
+    InvalidNumber(FileContext<Vec<u8>>),
+    /// The payload is the segment, only `push` takes a constant.
+    PopConstant(FileContext<Vec<u8>>),
+    /// The payload is the segment and the index, `pointer` takes 0..=1 and `temp` 0..=7.
+    IndexOutOfSegment(FileContext<Vec<u8>>),
+    /// The payload is the instruction missing the word.
+    MissingWord(FileContext<Vec<u8>>),
+}
+
+impl VmParseError {
+    fn description(&self) -> &'static str {
+        match self {
+            Self::Io(_) => "read error",
+            Self::InvalidComment(_) => "invalid comment start",
+            Self::UnknownInstruction(_) => "unknown instruction",
+            Self::UnknownSegment(_) => "unknown segment",
+            Self::InvalidNumber(_) => "expected a number in range 0..=32767",
+            Self::PopConstant(_) => "cannot pop to",
+            Self::IndexOutOfSegment(_) => "index out of segment",
+            Self::MissingWord(_) => "missing operand of",
+        }
+    }
+}
+
+impl fmt::Display for VmParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let context = match self {
+            Self::Io(err) => return write!(f, "{}", err),
+            Self::InvalidComment(c)
+            | Self::UnknownInstruction(c)
+            | Self::UnknownSegment(c)
+            | Self::InvalidNumber(c)
+            | Self::PopConstant(c)
+            | Self::IndexOutOfSegment(c)
+            | Self::MissingWord(c) => c,
+        };
+        let text = from_utf8(&context.payload).unwrap_or("<non utf8>");
+
+        if let Some(span) = &context.span {
+            write!(f, "{}: ", span.line + 1)?;
+        }
+
+        write!(f, "{} `{}`", self.description(), text)
+    }
+}
+
+impl error::Error for VmParseError {}
+
+impl From<io::Error> for VmParseError {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<VmParseError> for io::Error {
+    fn from(err: VmParseError) -> Self {
+        match err {
+            VmParseError::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+        }
+    }
+}
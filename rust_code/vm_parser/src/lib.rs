@@ -1,24 +1,30 @@
 use file_context::{FileContext, FileSpan};
-use std::io::{Error, ErrorKind};
+use std::ffi::OsStr;
+use std::io::{Cursor, Error, ErrorKind};
 use std::path::Path;
+use std::pin::Pin;
+use std::str::from_utf8;
 use tokio::fs::File;
-use tokio::io::{self, AsyncReadExt};
+use tokio::io::{self, AsyncRead, AsyncReadExt};
 
+mod errors;
 mod instructions;
 
+pub use errors::VmParseError;
 pub use instructions::*;
 
 const PARSER_BUFFER_SIZE: usize = 4096;
 
-#[derive(Debug)]
 pub struct VMParser {
-    file: File,
+    reader: Pin<Box<dyn AsyncRead + Send>>,
     buffer: [u8; PARSER_BUFFER_SIZE],
     cursor: usize,
     end_word_cursor: usize,
     end_page_cursor: usize,
     is_eof: bool,
     src_line: usize,
+    /// Line of the last word, `src_line` may already point past it.
+    word_line: usize,
     instruction_number: usize,
     is_in_progress: bool,
 }
@@ -26,14 +32,22 @@ pub struct VMParser {
 impl VMParser {
     pub async fn new(path: &str) -> io::Result<Self> {
         let file_path = Path::new(&path);
-        if file_path.extension().expect("File extension undefined") != "vm" {
+        if file_path.extension() != Some(OsStr::new("vm")) {
             return Err(Error::new(ErrorKind::Other, "File ext should be vm!"));
         }
 
         let file = File::open(&file_path).await?;
+        Self::from_reader(Box::pin(file)).await
+    }
+
+    /// Parses `.vm` code already in memory.
+    pub async fn from_bytes(bytes: Vec<u8>) -> io::Result<Self> {
+        Self::from_reader(Box::pin(Cursor::new(bytes))).await
+    }
 
+    async fn from_reader(reader: Pin<Box<dyn AsyncRead + Send>>) -> io::Result<Self> {
         let mut self_state = Self {
-            file,
+            reader,
             buffer: [0; PARSER_BUFFER_SIZE],
             cursor: PARSER_BUFFER_SIZE,
             end_word_cursor: PARSER_BUFFER_SIZE,
@@ -42,6 +56,7 @@ impl VMParser {
             is_in_progress: false,
             instruction_number: 0,
             src_line: 1,
+            word_line: 1,
         };
         self_state.fill_buffer().await?;
         Ok(self_state)
@@ -56,15 +71,15 @@ impl VMParser {
             std::ptr::copy_nonoverlapping(p.add(self.cursor), p, to_copy);
         }
 
-        let n = self.file.read(&mut self.buffer[to_copy..]).await?;
-        self.is_eof = self.cursor > n;
+        let n = self.reader.read(&mut self.buffer[to_copy..]).await?;
+        self.is_eof = n == 0 || self.cursor > n;
         self.end_word_cursor -= self.cursor;
         self.cursor = 0;
         self.end_page_cursor = n + to_copy;
         Ok(())
     }
 
-    async fn next_word(&mut self) -> Option<&[u8]> {
+    async fn next_word(&mut self) -> Result<Option<&[u8]>, VmParseError> {
         let mut is_comment = false;
         let mut is_word = false;
         self.cursor = self.end_word_cursor;
@@ -72,13 +87,15 @@ impl VMParser {
         loop {
             if self.end_word_cursor >= self.end_page_cursor {
                 if self.is_eof {
-                    return if is_word {
+                    return Ok(if is_word {
                         Some(&self.buffer[self.cursor..self.end_word_cursor])
                     } else {
                         None
-                    };
+                    });
                 } else {
-                    self.fill_buffer().await.expect("fail to fill buffer");
+                    // The read may bring nothing, e.g. when the code ends with the page.
+                    self.fill_buffer().await?;
+                    continue;
                 }
             }
 
@@ -94,8 +111,11 @@ impl VMParser {
                     self.end_word_cursor += 1;
                 }
                 (false, _, b'/') => {
-                    if self.buffer[self.end_word_cursor + 1] != b'/' {
-                        panic!("Comment parsing error")
+                    if self.buffer.get(self.end_word_cursor + 1) != Some(&b'/') {
+                        // Skipped, so the next call does not stop on it again.
+                        self.end_word_cursor += 1;
+                        self.word_line = self.src_line;
+                        return Err(VmParseError::InvalidComment(self.error_context(b"/")));
                     }
                     self.cursor += 2;
                     self.end_word_cursor += 2;
@@ -110,54 +130,101 @@ impl VMParser {
                     self.cursor += 1;
                     self.end_word_cursor += 1;
                 }
-                (false, false, _) => is_word = true,
+                (false, false, _) => {
+                    self.word_line = self.src_line;
+                    is_word = true
+                }
+                // The terminator is read again by the next call, so the line is counted there.
                 (false, true, v) if v == b' ' || v == b'\t' || v == b'\r' || v == b'\n' => {
-                    if v == b'\n' {
-                        self.src_line += 1;
-                    }
-                    return Some(&self.buffer[self.cursor..self.end_word_cursor]);
+                    return Ok(Some(&self.buffer[self.cursor..self.end_word_cursor]));
                 }
                 (false, true, _) => self.end_word_cursor += 1,
             }
         }
     }
 
+    /// The word after `instruction`.
+    async fn operand(&mut self, instruction: &[u8]) -> Result<Vec<u8>, VmParseError> {
+        match self.next_word().await? {
+            Some(word) => Ok(word.to_vec()),
+            None => Err(VmParseError::MissingWord(self.error_context(instruction))),
+        }
+    }
+
+    fn number(&self, word: &[u8]) -> Result<i16, VmParseError> {
+        from_utf8(word)
+            .ok()
+            .filter(|s| s.bytes().all(|c| c.is_ascii_digit()))
+            .and_then(|s| s.parse().ok())
+            .ok_or_else(|| VmParseError::InvalidNumber(self.error_context(word)))
+    }
+
+    fn error_context(&self, text: &[u8]) -> FileContext<Vec<u8>> {
+        let span = Some(FileSpan::new(self.word_line - 1, 0));
+        FileContext::new(text.to_vec(), self.instruction_number, None, span)
+    }
+
     async fn build_memory_instruction(
         &mut self,
         kind: AsmMemoryInstructionKind,
-    ) -> Option<AsmInstructionPayload> {
-        let word = self.next_word().await.unwrap();
-        let segment = SEGMENTS[word];
-        let val: i16 = self
-            .next_word()
-            .await
-            .unwrap()
-            .iter()
-            .fold(0, |v, x| v * 10 - 48 + *x as i16);
+        instruction: &[u8],
+    ) -> Result<AsmInstructionPayload, VmParseError> {
+        let word = self.operand(instruction).await?;
+        let segment = *SEGMENTS
+            .get(word.as_slice())
+            .ok_or_else(|| VmParseError::UnknownSegment(self.error_context(&word)))?;
+        let val = self.operand(instruction).await?;
+        let val = self.number(&val)?;
+
+        let segment_size = match segment {
+            AsmMemoryInstructionSegment::Const if kind == AsmMemoryInstructionKind::Pop => {
+                return Err(VmParseError::PopConstant(self.error_context(&word)));
+            }
+            AsmMemoryInstructionSegment::Pointer => Some(2),
+            AsmMemoryInstructionSegment::Temp => Some(8),
+            _ => None,
+        };
+        if segment_size.is_some_and(|size| val >= size) {
+            let text = format!("{} {}", from_utf8(&word).unwrap_or_default(), val);
+            return Err(VmParseError::IndexOutOfSegment(
+                self.error_context(text.as_bytes()),
+            ));
+        }
 
-        Some(AsmInstructionPayload::Memory(AsmMemoryInstruction {
+        Ok(AsmInstructionPayload::Memory(AsmMemoryInstruction {
             kind,
             segment,
             val,
         }))
     }
 
-    pub async fn next_instruction(&mut self) -> Option<FileContext<AsmInstructionPayload>> {
+    /// `None` at the end of the code. The parser can go on after an error.
+    pub async fn next_instruction(
+        &mut self,
+    ) -> Option<Result<FileContext<AsmInstructionPayload>, VmParseError>> {
+        self.parse_instruction().await.transpose()
+    }
+
+    async fn parse_instruction(
+        &mut self,
+    ) -> Result<Option<FileContext<AsmInstructionPayload>>, VmParseError> {
         self.is_in_progress = true;
         self.instruction_number += 1;
 
-        let src_line = self.src_line.clone();
-        let word = self.next_word().await?;
+        let Some(word) = self.next_word().await? else {
+            return Ok(None);
+        };
+        let word = word.to_vec();
 
-        let instruction_payload = match word {
-            [b'p', b'u', b's', b'h', ..] => self
-                .build_memory_instruction(AsmMemoryInstructionKind::Push)
-                .await
-                .expect("fail to build push instruction"),
-            [b'p', b'o', b'p', ..] => self
-                .build_memory_instruction(AsmMemoryInstructionKind::Pop)
-                .await
-                .expect("fail to build pop instruction"),
+        let instruction_payload = match word.as_slice() {
+            [b'p', b'u', b's', b'h', ..] => {
+                self.build_memory_instruction(AsmMemoryInstructionKind::Push, &word)
+                    .await?
+            }
+            [b'p', b'o', b'p', ..] => {
+                self.build_memory_instruction(AsmMemoryInstructionKind::Pop, &word)
+                    .await?
+            }
             [b'a', b'd', b'd', ..] => {
                 self.build_arithmetic_instruction(AsmArithmeticInstruction::Add)
             }
@@ -181,62 +248,53 @@ impl VMParser {
                 AsmInstructionPayload::Function(AsmFunctionInstruction::Return)
             }
             [b'f', b'u', b'n', b'c', b't', b'i', b'o', b'n', ..] => {
-                self.build_function_instruction(true).await
+                self.build_function_instruction(true, &word).await?
             }
-            [b'c', b'a', b'l', b'l', ..] => self.build_function_instruction(false).await,
+            [b'c', b'a', b'l', b'l', ..] => self.build_function_instruction(false, &word).await?,
             [b'l', b'a', b'b', b'e', b'l', ..] => {
-                self.build_branch_instruction(AsmBranchInstructionKind::Label)
-                    .await
+                self.build_branch_instruction(AsmBranchInstructionKind::Label, &word)
+                    .await?
             }
             [b'g', b'o', b't', b'o', ..] => {
-                self.build_branch_instruction(AsmBranchInstructionKind::Goto)
-                    .await
+                self.build_branch_instruction(AsmBranchInstructionKind::Goto, &word)
+                    .await?
             }
             [b'i', b'f', b'-', b'g', b'o', b't', b'o', ..] => {
-                self.build_branch_instruction(AsmBranchInstructionKind::IfGoto)
-                    .await
-            }
-            _ => {
-                let i = std::str::from_utf8(word).unwrap();
-                panic!("{}: Unexected instruction {}", src_line, i);
+                self.build_branch_instruction(AsmBranchInstructionKind::IfGoto, &word)
+                    .await?
             }
+            _ => return Err(VmParseError::UnknownInstruction(self.error_context(&word))),
         };
-        Some(self.enrich_instruction_payload(instruction_payload))
+        Ok(Some(self.enrich_instruction_payload(instruction_payload)))
     }
 
     async fn build_branch_instruction(
         &mut self,
         kind: AsmBranchInstructionKind,
-    ) -> AsmInstructionPayload {
-        let word = self.next_word().await.unwrap();
-        AsmInstructionPayload::Branch(AsmBranchInstruction {
+        instruction: &[u8],
+    ) -> Result<AsmInstructionPayload, VmParseError> {
+        let name = self.operand(instruction).await?;
+        Ok(AsmInstructionPayload::Branch(AsmBranchInstruction {
             kind,
-            name: word.to_vec(),
-        })
+            name,
+        }))
     }
 
-    async fn build_function_instruction(&mut self, is_definition: bool) -> AsmInstructionPayload {
-        let name = self.next_word().await.unwrap().to_vec();
-        let args_count_bytes = self.next_word().await.unwrap();
-
-        let args_count = match args_count_bytes.len() {
-            0 => unreachable!(),
-            1 => parse_an_int_val(args_count_bytes[0]),
-            2 => parse_an_int_val(args_count_bytes[0]) * 10 + parse_an_int_val(args_count_bytes[1]),
-            _ => panic!("Too much args"),
-        };
+    async fn build_function_instruction(
+        &mut self,
+        is_definition: bool,
+        instruction: &[u8],
+    ) -> Result<AsmInstructionPayload, VmParseError> {
+        let name = self.operand(instruction).await?;
+        let args_count = self.operand(instruction).await?;
+        let args_count = self.number(&args_count)?;
 
-        if is_definition {
-            AsmInstructionPayload::Function(AsmFunctionInstruction::Definition(FunctionMetadata {
-                name,
-                args_count,
-            }))
+        let meta = FunctionMetadata { name, args_count };
+        Ok(AsmInstructionPayload::Function(if is_definition {
+            AsmFunctionInstruction::Definition(meta)
         } else {
-            AsmInstructionPayload::Function(AsmFunctionInstruction::Call(FunctionMetadata {
-                name,
-                args_count,
-            }))
-        }
+            AsmFunctionInstruction::Call(meta)
+        }))
     }
 
     fn build_arithmetic_instruction(
@@ -250,23 +308,7 @@ impl VMParser {
         &self,
         payload: AsmInstructionPayload,
     ) -> FileContext<AsmInstructionPayload> {
-        let span = Some(FileSpan::new(self.src_line, 0));
+        let span = Some(FileSpan::new(self.word_line - 1, 0));
         FileContext::new(payload, self.instruction_number, None, span)
     }
 }
-
-fn parse_an_int_val(i: u8) -> i16 {
-    match i {
-        b'0' => 0,
-        b'1' => 1,
-        b'2' => 2,
-        b'3' => 3,
-        b'4' => 4,
-        b'5' => 5,
-        b'6' => 6,
-        b'7' => 7,
-        b'8' => 8,
-        b'9' => 9,
-        _ => panic!("Count args must be an integer"),
-    }
-}
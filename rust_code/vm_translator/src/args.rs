@@ -0,0 +1,63 @@
+use std::io;
+use std::path::PathBuf;
+
+use vm_translator::default_os_dir;
+
+pub struct Args {
+    pub file_path: PathBuf,
+    pub write_path: Option<PathBuf>,
+    /// `None` links the project alone.
+    pub os_dir: Option<PathBuf>,
+    pub library_dirs: Vec<PathBuf>,
+}
+
+impl Args {
+    /// `vm_translator <file.vm|dir> [output] [--os-dir dir] [--no-os] [-L dir]...`
+    pub fn parse(args: &[String]) -> io::Result<Self> {
+        let mut file_path = None;
+        let mut write_path = None;
+        let mut os_dir = Some(default_os_dir());
+        let mut library_dirs = Vec::new();
+
+        let mut iter = args.iter().skip(1);
+        while let Some(arg) = iter.next() {
+            match arg.as_str() {
+                "--os-dir" => os_dir = Some(PathBuf::from(value(arg, iter.next())?)),
+                "--no-os" => os_dir = None,
+                "-L" => library_dirs.push(PathBuf::from(value(arg, iter.next())?)),
+                _ if arg.starts_with("-L") => library_dirs.push(PathBuf::from(&arg[2..])),
+                _ if arg.starts_with('-') => {
+                    return Err(invalid_input(format!("Unknown option {}", arg)))
+                }
+                _ if file_path.is_none() => file_path = Some(PathBuf::from(arg)),
+                _ if write_path.is_none() => write_path = Some(PathBuf::from(arg)),
+                _ => return Err(invalid_input(format!("Unexpected argument {}", arg))),
+            }
+        }
+
+        Ok(Self {
+            file_path: file_path
+                .ok_or_else(|| invalid_input("Missing .vm file or directory".to_string()))?,
+            write_path,
+            os_dir,
+            library_dirs,
+        })
+    }
+
+    /// Directories linked after the project in search order: `-L` libraries, the OS.
+    pub fn library_dirs(&self) -> Vec<PathBuf> {
+        let mut dirs = self.library_dirs.clone();
+        dirs.extend(self.os_dir.iter().cloned());
+        dirs
+    }
+}
+
+fn value<'a>(arg: &str, value: Option<&'a String>) -> io::Result<&'a str> {
+    value
+        .map(String::as_str)
+        .ok_or_else(|| invalid_input(format!("Missing value for {}", arg)))
+}
+
+fn invalid_input(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, message)
+}
@@ -1,9 +1,18 @@
 use std::collections::HashMap;
 
+use file_context::{SourceLocation, SourceMap};
+
 #[derive(Debug)]
 pub struct WriteFileContext {
     pointer: usize,
     pub pointer_map: HashMap<Vec<u8>, Vec<usize>>,
+    /// Keys of `static_map` that are ROM labels, the rest are static variables.
+    pub labels: Vec<Vec<u8>>,
+    /// ROM address to `.vm` line, and to `.jack` line when the file has a `.vm.map`.
+    pub source_map: SourceMap,
+    source_file: String,
+    source_file_map: Option<SourceMap>,
+    source_line: Option<Option<usize>>,
 }
 
 impl WriteFileContext {
@@ -11,6 +20,11 @@ impl WriteFileContext {
         Self {
             pointer: 0,
             pointer_map: HashMap::new(),
+            labels: Vec::new(),
+            source_map: SourceMap::new(),
+            source_file: String::new(),
+            source_file_map: None,
+            source_line: None,
         }
     }
 
@@ -22,6 +36,37 @@ impl WriteFileContext {
         self.pointer += 1;
     }
 
+    pub fn set_label(&mut self, label: Vec<u8>) {
+        self.labels.push(label)
+    }
+
+    pub fn set_source_file(&mut self, file: String, file_map: Option<SourceMap>) {
+        self.source_file = file;
+        self.source_file_map = file_map;
+        self.source_line = None;
+    }
+
+    /// Maps the current ROM address, `None` is generated code like the bootstrap.
+    pub fn set_source_line(&mut self, line: Option<usize>) {
+        if self.source_line == Some(line) {
+            return;
+        }
+        self.source_line = Some(line);
+
+        let locations = match line {
+            Some(line) => {
+                let mut locations = vec![SourceLocation::new(self.source_file.clone(), line)];
+                if let Some(file_map) = &self.source_file_map {
+                    locations.extend_from_slice(file_map.lookup(line));
+                }
+                locations
+            }
+            None => Vec::new(),
+        };
+
+        self.source_map.push(self.pointer, locations)
+    }
+
     pub fn set_intruction(&mut self, instruction: Vec<u8>) {
         self.pointer_map
             .entry(instruction)
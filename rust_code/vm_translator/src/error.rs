@@ -0,0 +1,53 @@
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use vm_parser::VmParseError;
+
+use crate::program::ROM_SIZE;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    /// `.vm` code of the file that does not parse.
+    Parse(PathBuf, VmParseError),
+    /// A function defined twice or a call of a function defined nowhere.
+    Link(String),
+    /// A jump to a label the program does not have.
+    UndefinedLabel(String),
+    /// More instructions than ROM holds.
+    RomOverflow(usize),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(err) => write!(f, "{}", err),
+            Self::Parse(path, err) => write!(f, "{}:{}", path.display(), err),
+            Self::Link(message) => write!(f, "{}", message),
+            Self::UndefinedLabel(label) => write!(f, "undefined label {}", label),
+            Self::RomOverflow(size) => write!(
+                f,
+                "the program has {} instructions, ROM holds {}",
+                size, ROM_SIZE
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Self {
+        Self::Io(err)
+    }
+}
+
+impl From<Error> for io::Error {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Io(err) => err,
+            err => io::Error::new(io::ErrorKind::InvalidData, err.to_string()),
+        }
+    }
+}
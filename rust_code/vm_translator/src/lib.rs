@@ -0,0 +1,13 @@
+mod context;
+mod error;
+mod linker;
+mod program;
+mod translator;
+
+pub use error::Error;
+pub use linker::{resolve_files, CallGraph, LinkReport, ENTRY_FUNCTION};
+pub use program::{
+    default_os_dir, output_path, translate, translate_vm, write_output, AsmOutput, ProgramOpts,
+    VmInput, VmSource, OS_DIR_VAR, ROM_SIZE,
+};
+pub use translator::{TranslateOpts, Translator};
@@ -0,0 +1,340 @@
+use std::collections::{HashMap, HashSet};
+use std::ffi::OsStr;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::from_utf8;
+
+use tokio::fs::read_dir;
+use tokio::io;
+use vm_parser::{AsmFunctionInstruction, AsmInstructionPayload, VMParser};
+
+use crate::error::Error;
+use crate::program::{VmInput, VmSource};
+
+/// The function the bootstrap calls, everything else is linked from it.
+pub const ENTRY_FUNCTION: &[u8] = b"Sys.init";
+
+#[derive(Debug)]
+struct FunctionNode {
+    name: Vec<u8>,
+    file: PathBuf,
+    calls: Vec<Vec<u8>>,
+}
+
+/// `.vm` files of the directories in search order. A file is skipped when
+/// an earlier directory has a file of the same class, so the project
+/// overrides the OS. A file instead of a directory is taken as it is.
+pub async fn resolve_files(search_path: &[PathBuf]) -> io::Result<Vec<PathBuf>> {
+    let mut files: Vec<PathBuf> = Vec::new();
+
+    for dir in search_path {
+        let candidates = if dir.is_dir() {
+            vm_files(dir).await?
+        } else if dir.exists() {
+            vec![dir.clone()]
+        } else {
+            let message = format!("{} does not exist", dir.display());
+            return Err(io::Error::new(io::ErrorKind::NotFound, message));
+        };
+
+        for file in candidates {
+            if !files.iter().any(|f| f.file_stem() == file.file_stem()) {
+                files.push(file);
+            }
+        }
+    }
+
+    Ok(files)
+}
+
+/// Sorted, so the program does not depend on the directory order.
+async fn vm_files(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut paths = read_dir(dir)
+        .await
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", dir.display(), err)))?;
+
+    while let Some(path) = paths.next_entry().await? {
+        let path = path.path();
+        if path.extension() == Some(OsStr::new("vm")) {
+            files.push(path);
+        }
+    }
+
+    files.sort();
+    Ok(files)
+}
+
+/// `function` and `call` instructions of the linked `.vm` files.
+#[derive(Debug, Default)]
+pub struct CallGraph {
+    /// In the order of definition.
+    functions: Vec<FunctionNode>,
+}
+
+/// Functions left out of ROM, in the order of definition.
+#[derive(Debug, PartialEq)]
+pub struct LinkReport {
+    pub kept: HashSet<Vec<u8>>,
+    pub dropped: Vec<(String, PathBuf)>,
+}
+
+impl CallGraph {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub async fn add_file(&mut self, path: &Path) -> Result<(), Error> {
+        self.add_input(&VmInput::read(path).await?).await
+    }
+
+    pub async fn add_input(&mut self, input: &VmInput) -> Result<(), Error> {
+        match &input.source {
+            VmSource::Code(code) => {
+                let mut parser = VMParser::from_bytes(code.clone()).await?;
+                while let Some(token) = parser.next_instruction().await {
+                    let token = token.map_err(|err| Error::Parse(input.path.clone(), err))?;
+                    self.add_instruction(&token.payload, &input.path);
+                }
+            }
+            VmSource::Instructions(instructions) => {
+                for instruction in instructions {
+                    self.add_instruction(instruction, &input.path);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    fn add_instruction(&mut self, instruction: &AsmInstructionPayload, file: &Path) {
+        match instruction {
+            AsmInstructionPayload::Function(AsmFunctionInstruction::Definition(meta)) => {
+                self.add_function(meta.name.clone(), file)
+            }
+            AsmInstructionPayload::Function(AsmFunctionInstruction::Call(meta)) => {
+                self.add_call(meta.name.clone())
+            }
+            _ => (),
+        }
+    }
+
+    pub fn add_function(&mut self, name: Vec<u8>, file: &Path) {
+        self.functions.push(FunctionNode {
+            name,
+            file: file.to_path_buf(),
+            calls: Vec::new(),
+        });
+    }
+
+    /// A call from the last added function.
+    pub fn add_call(&mut self, callee: Vec<u8>) {
+        if let Some(function) = self.functions.last_mut() {
+            function.calls.push(callee);
+        }
+    }
+
+    /// Walks the calls from `root`. A function defined twice or a call of a
+    /// function defined nowhere is an error.
+    pub fn link(&self, root: &[u8]) -> Result<LinkReport, Error> {
+        let mut by_name: HashMap<&[u8], &FunctionNode> = HashMap::new();
+        for function in &self.functions {
+            if let Some(previous) = by_name.insert(&function.name, function) {
+                let message = format!(
+                    "{} is defined in both {} and {}",
+                    from_utf8(&function.name).unwrap(),
+                    previous.file.display(),
+                    function.file.display()
+                );
+                return Err(Error::Link(message));
+            }
+        }
+
+        let mut kept = HashSet::new();
+        let mut stack = vec![(root, None)];
+        while let Some((name, caller)) = stack.pop() {
+            if kept.contains(name) {
+                continue;
+            }
+
+            let Some(definition) = by_name.get(name) else {
+                let message = match caller {
+                    Some(caller) => format!(
+                        "{} calls undefined function {}",
+                        from_utf8(caller).unwrap(),
+                        from_utf8(name).unwrap()
+                    ),
+                    None => format!("{} is not defined", from_utf8(name).unwrap()),
+                };
+                return Err(Error::Link(message));
+            };
+
+            kept.insert(name.to_vec());
+            for callee in &definition.calls {
+                stack.push((callee, Some(name)));
+            }
+        }
+
+        let dropped = self
+            .functions
+            .iter()
+            .filter(|function| !kept.contains(&function.name))
+            .map(|function| {
+                (
+                    String::from_utf8_lossy(&function.name).to_string(),
+                    function.file.clone(),
+                )
+            })
+            .collect();
+
+        Ok(LinkReport { kept, dropped })
+    }
+}
+
+impl LinkReport {
+    /// The dropped functions are listed when `verbose`.
+    pub fn write(&self, out: &mut impl Write, verbose: bool) -> std::io::Result<()> {
+        writeln!(
+            out,
+            "Linked {} functions, dropped {} unreachable from {}",
+            self.kept.len(),
+            self.dropped.len(),
+            from_utf8(ENTRY_FUNCTION).unwrap()
+        )?;
+        if verbose {
+            for (name, file) in &self.dropped {
+                writeln!(out, "    {} ({})", name, file.display())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn fibonacci_link_test() {
+        let mut graph = CallGraph::new();
+        graph
+            .add_file(Path::new("./priv/programs/FibonacciElement/Main.vm"))
+            .await
+            .unwrap();
+        graph
+            .add_file(Path::new("./priv/programs/FibonacciElement/Sys.vm"))
+            .await
+            .unwrap();
+
+        let report = graph.link(ENTRY_FUNCTION).unwrap();
+        assert_eq!(report.kept.len(), 2);
+        assert!(report.dropped.is_empty());
+    }
+
+    #[test]
+    fn dropped_functions_test() {
+        let mut graph = CallGraph::new();
+        let sys = Path::new("Sys.vm");
+        let math = Path::new("Math.vm");
+        graph.add_function(b"Sys.init".to_vec(), sys);
+        graph.add_call(b"Math.multiply".to_vec());
+        graph.add_function(b"Math.divide".to_vec(), math);
+        graph.add_call(b"Math.abs".to_vec());
+        graph.add_function(b"Math.multiply".to_vec(), math);
+        graph.add_call(b"Math.multiply".to_vec());
+        graph.add_function(b"Math.abs".to_vec(), math);
+
+        let report = graph.link(ENTRY_FUNCTION).unwrap();
+        assert!(report.kept.contains(&b"Math.multiply"[..]));
+        assert!(!report.kept.contains(&b"Math.abs"[..]));
+        assert_eq!(
+            report.dropped,
+            vec![
+                ("Math.divide".to_string(), math.to_path_buf()),
+                ("Math.abs".to_string(), math.to_path_buf())
+            ]
+        );
+
+        let mut out = Vec::new();
+        report.write(&mut out, true).unwrap();
+        assert_eq!(
+            from_utf8(&out).unwrap(),
+            "Linked 2 functions, dropped 2 unreachable from Sys.init\n    Math.divide (Math.vm)\n    Math.abs (Math.vm)\n"
+        );
+
+        graph.add_function(b"Math.abs".to_vec(), sys);
+        assert_eq!(
+            graph.link(ENTRY_FUNCTION).unwrap_err().to_string(),
+            "Math.abs is defined in both Math.vm and Sys.vm"
+        );
+    }
+
+    #[test]
+    fn undefined_function_test() {
+        let mut graph = CallGraph::new();
+        let main = Path::new("Main.vm");
+        graph.add_function(b"Sys.init".to_vec(), main);
+        graph.add_call(b"Main.main".to_vec());
+        graph.add_function(b"Main.main".to_vec(), main);
+        graph.add_call(b"Main.missing".to_vec());
+
+        assert_eq!(
+            graph.link(ENTRY_FUNCTION).unwrap_err().to_string(),
+            "Main.main calls undefined function Main.missing"
+        );
+        assert_eq!(
+            CallGraph::new()
+                .link(ENTRY_FUNCTION)
+                .unwrap_err()
+                .to_string(),
+            "Sys.init is not defined"
+        );
+    }
+
+    #[tokio::test]
+    async fn override_os_test() {
+        let search_path = [
+            PathBuf::from("./priv/programs/FibonacciElement"),
+            PathBuf::from("./priv/os"),
+        ];
+        let files = resolve_files(&search_path).await.unwrap();
+        assert_eq!(
+            files,
+            vec![
+                PathBuf::from("./priv/programs/FibonacciElement/Main.vm"),
+                PathBuf::from("./priv/programs/FibonacciElement/Sys.vm"),
+                PathBuf::from("./priv/os/Math.vm"),
+            ]
+        );
+
+        let mut graph = CallGraph::new();
+        for file in &files {
+            graph.add_file(file).await.unwrap();
+        }
+        let report = graph.link(ENTRY_FUNCTION).unwrap();
+        assert_eq!(report.kept.len(), 2);
+        assert_eq!(
+            report.dropped,
+            vec![(
+                "Math.multiply".to_string(),
+                PathBuf::from("./priv/os/Math.vm")
+            )]
+        );
+
+        // Without the project `Sys.vm` the OS one calls `Main.main`.
+        let files = resolve_files(&[
+            PathBuf::from("./priv/programs/FibonacciElement/Main.vm"),
+            PathBuf::from("./priv/os"),
+        ])
+        .await
+        .unwrap();
+        let mut graph = CallGraph::new();
+        for file in &files {
+            graph.add_file(file).await.unwrap();
+        }
+        assert_eq!(
+            graph.link(ENTRY_FUNCTION).unwrap_err().to_string(),
+            "Sys.init calls undefined function Main.main"
+        );
+    }
+}
@@ -0,0 +1,711 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::ffi::OsStr;
+use std::iter::Enumerate;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::vec;
+
+use file_context::{FileContext, FileSpan, SourceMap};
+use hack_assembler::SymbolTable;
+use hack_instructions::{Instruction, VariableFactory};
+use tokio::io;
+use vm_parser::{AsmFunctionInstruction, AsmInstructionPayload, VMParser, VmParseError};
+
+use crate::context::WriteFileContext;
+use crate::error::Error;
+use crate::linker::{resolve_files, CallGraph, LinkReport, ENTRY_FUNCTION};
+use crate::translator::{TranslateOpts, Translator};
+
+/// Environment variable with the OS directory, e.g. for an installed binary.
+pub const OS_DIR_VAR: &str = "N2T_OS_DIR";
+
+/// The OS of the checkout the binary was built from.
+const BUILD_OS_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/../../static/bifs");
+
+/// The OS linked by default: `N2T_OS_DIR`, a `bifs` directory next to the
+/// executable, or `static/bifs` of the checkout the binary was built from.
+pub fn default_os_dir() -> PathBuf {
+    if let Some(dir) = env::var_os(OS_DIR_VAR) {
+        return PathBuf::from(dir);
+    }
+
+    let next_to_exe = env::current_exe()
+        .ok()
+        .and_then(|exe| Some(exe.parent()?.join("bifs")));
+    match next_to_exe {
+        Some(dir) if dir.is_dir() => dir,
+        _ => PathBuf::from(BUILD_OS_DIR),
+    }
+}
+
+/// Instructions the Hack computer can hold, labels past it do not fit in an
+/// A-instruction.
+pub const ROM_SIZE: usize = 32768;
+
+/// Length of a `.hack` line with its `\n`.
+const BINARY_LINE: usize = 17;
+
+/// What `translate` writes besides the code.
+#[derive(Clone)]
+pub struct ProgramOpts {
+    pub translate: TranslateOpts,
+    /// `.hack` instead of `.asm`.
+    pub binary: bool,
+    /// A `.sym` file, only for `.hack`.
+    pub symbols: bool,
+    pub source_map: bool,
+    /// Lists the dropped functions, not only their number.
+    pub link_report: bool,
+    /// Searched after a directory in this order, the OS goes last.
+    pub library_dirs: Vec<PathBuf>,
+}
+
+impl ProgramOpts {
+    pub fn new() -> Self {
+        Self {
+            translate: TranslateOpts::new(),
+            binary: false,
+            symbols: false,
+            source_map: false,
+            link_report: false,
+            library_dirs: vec![default_os_dir()],
+        }
+    }
+
+    /// `dir/dir.asm` for a directory, `file.asm` for a file.
+    pub fn default_output(&self, file_path: &Path) -> io::Result<PathBuf> {
+        let ext = if self.binary { "hack" } else { "asm" };
+        output_path(file_path, ext)
+    }
+}
+
+/// `dir/dir.ext` for a directory, `.` included, `file.ext` for a file.
+pub fn output_path(file_path: &Path, ext: &str) -> io::Result<PathBuf> {
+    if !file_path.is_dir() {
+        return Ok(file_path.with_extension(ext));
+    }
+
+    let dir = file_path
+        .canonicalize()
+        .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", file_path.display(), err)))?;
+    let Some(stem) = dir.file_stem() else {
+        let message = format!("{} has no name for the output", file_path.display());
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+    };
+    let mut name = stem.to_os_string();
+    name.push(".");
+    name.push(ext);
+    Ok(file_path.join(name))
+}
+
+impl Default for ProgramOpts {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// `.vm` code, or instructions that are already parsed, e.g. the output of
+/// the jack compiler. The line of an instruction is its index.
+#[derive(Debug)]
+pub enum VmSource {
+    Code(Vec<u8>),
+    Instructions(Vec<AsmInstructionPayload>),
+}
+
+/// A `.vm` file in memory. The stem of `path` names its statics and
+/// `path` itself names it in the source map.
+#[derive(Debug)]
+pub struct VmInput {
+    pub path: PathBuf,
+    pub source: VmSource,
+    /// `.vm` lines back to `.jack` lines, the `.vm.map` of the jack compiler.
+    pub source_map: Option<SourceMap>,
+}
+
+impl VmInput {
+    pub fn new(path: impl Into<PathBuf>, source: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path: path.into(),
+            source: VmSource::Code(source.into()),
+            source_map: None,
+        }
+    }
+
+    pub fn from_instructions(
+        path: impl Into<PathBuf>,
+        instructions: Vec<AsmInstructionPayload>,
+        source_map: Option<SourceMap>,
+    ) -> Self {
+        Self {
+            path: path.into(),
+            source: VmSource::Instructions(instructions),
+            source_map,
+        }
+    }
+
+    /// Reads the file and the `.vm.map` next to it when there is one.
+    pub async fn read(path: &Path) -> io::Result<Self> {
+        if path.extension() != Some(OsStr::new("vm")) {
+            let message = format!("{}: expected a .vm file or a directory", path.display());
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, message));
+        }
+        let source = tokio::fs::read(path)
+            .await
+            .map_err(|err| io::Error::new(err.kind(), format!("{}: {}", path.display(), err)))?;
+
+        // `Main.vm.map` is written by the jack compiler.
+        let source_map = match tokio::fs::read_to_string(path.with_extension("vm.map")).await {
+            Ok(src) => Some(SourceMap::parse(&src).map_err(|err| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("{}.map:{}", path.display(), err),
+                )
+            })?),
+            Err(_) => None,
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            source: VmSource::Code(source),
+            source_map,
+        })
+    }
+
+    async fn tokens(self) -> io::Result<Tokens> {
+        Ok(match self.source {
+            VmSource::Code(code) => Tokens::Parser(Box::new(VMParser::from_bytes(code).await?)),
+            VmSource::Instructions(instructions) => {
+                Tokens::Instructions(instructions.into_iter().enumerate())
+            }
+        })
+    }
+}
+
+/// Instructions of an input with their `.vm` lines.
+enum Tokens {
+    Parser(Box<VMParser>),
+    Instructions(Enumerate<vec::IntoIter<AsmInstructionPayload>>),
+}
+
+impl Tokens {
+    async fn next(&mut self) -> Option<Result<FileContext<AsmInstructionPayload>, VmParseError>> {
+        match self {
+            Tokens::Parser(parser) => parser.next_instruction().await,
+            Tokens::Instructions(instructions) => instructions.next().map(|(line, payload)| {
+                Ok(FileContext::new(
+                    payload,
+                    line + 1,
+                    None,
+                    Some(FileSpan::new(line, 0)),
+                ))
+            }),
+        }
+    }
+}
+
+/// A translated program.
+#[derive(Debug)]
+pub struct AsmOutput {
+    /// `.asm` code, or `.hack` with `binary`.
+    pub code: Vec<u8>,
+    /// Labels and variables, only for `.hack` with `symbols`.
+    pub symbols: Option<SymbolTable>,
+    /// ROM addresses back to `.vm` and `.jack` lines.
+    pub source_map: SourceMap,
+    /// `None` when nothing was linked.
+    pub link_report: Option<LinkReport>,
+}
+
+/// Translates a `.vm` file, or a directory linked with the libraries of
+/// `program_opts`, to `write_file_path`. Symbols and the source map are written next to it.
+pub async fn translate(
+    file_path: &Path,
+    write_file_path: &Path,
+    program_opts: &ProgramOpts,
+) -> Result<(), Error> {
+    let link = file_path.is_dir();
+    let paths = if link {
+        let mut search_path = vec![file_path.to_path_buf()];
+        search_path.extend(program_opts.library_dirs.iter().cloned());
+        resolve_files(&search_path).await?
+    } else {
+        vec![file_path.to_path_buf()]
+    };
+
+    let mut inputs = Vec::new();
+    for path in &paths {
+        inputs.push(VmInput::read(path).await?);
+    }
+
+    let output = translate_vm(inputs, link, program_opts).await?;
+    write_output(&output, write_file_path, program_opts).await?;
+    Ok(())
+}
+
+/// Writes the code to `write_file_path`, the symbols and the source map
+/// next to it when `program_opts` asks for them, and the link report to stderr.
+pub async fn write_output(
+    output: &AsmOutput,
+    write_file_path: &Path,
+    program_opts: &ProgramOpts,
+) -> io::Result<()> {
+    if let Some(report) = &output.link_report {
+        report.write(&mut std::io::stderr().lock(), program_opts.link_report)?;
+    }
+
+    tokio::fs::write(write_file_path, &output.code)
+        .await
+        .map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("{}: {}", write_file_path.display(), err),
+            )
+        })?;
+
+    if let Some(symbols) = &output.symbols {
+        let mut out = Vec::new();
+        symbols.write_symbol_file(&mut out);
+        tokio::fs::write(write_file_path.with_extension("sym"), out).await?;
+    }
+
+    if program_opts.source_map {
+        let ext = if program_opts.binary { "hack" } else { "asm" };
+        let mut out = Vec::new();
+        output.source_map.write(&mut out);
+        tokio::fs::write(write_file_path.with_extension(format!("{}.map", ext)), out).await?;
+    }
+
+    Ok(())
+}
+
+/// Translates the inputs in their order. With `link` the bootstrap goes
+/// first and only functions reachable from `Sys.init` are kept, the
+/// `library_dirs` of `program_opts` are not searched, the inputs are the
+/// whole program.
+pub async fn translate_vm(
+    inputs: Vec<VmInput>,
+    link: bool,
+    program_opts: &ProgramOpts,
+) -> Result<AsmOutput, Error> {
+    let mut buff = [0u8; 4096];
+
+    let opts = program_opts.translate;
+    let binary_target = program_opts.binary;
+
+    let mut code = Vec::new();
+    let mut pointer = 16;
+    let mut static_map = HashMap::new();
+
+    let mut file_context = WriteFileContext::new();
+    let mut link_report = None;
+
+    if link {
+        let mut translator = Box::new(Translator::new_with_opts(opts));
+        let mut factory = VariableFactory::new(b"initial_call");
+        translator.init_translator(&mut factory);
+        // The shared routines follow the bootstrap, it can take several chunks.
+        write_chunks(
+            &mut translator,
+            &mut code,
+            &mut buff,
+            binary_target,
+            &mut pointer,
+            &mut static_map,
+            &mut file_context,
+        );
+
+        // Only functions reachable from `Sys.init` go to ROM.
+        let mut graph = CallGraph::new();
+        for input in &inputs {
+            graph.add_input(input).await?;
+        }
+        let report = graph.link(ENTRY_FUNCTION)?;
+
+        for input in inputs {
+            translate_input(
+                input,
+                &mut code,
+                &mut buff,
+                opts,
+                binary_target,
+                &mut pointer,
+                &mut static_map,
+                &mut file_context,
+                Some(&report.kept),
+            )
+            .await?
+        }
+        link_report = Some(report);
+    } else {
+        for input in inputs {
+            translate_input(
+                input,
+                &mut code,
+                &mut buff,
+                opts,
+                binary_target,
+                &mut pointer,
+                &mut static_map,
+                &mut file_context,
+                None,
+            )
+            .await?;
+        }
+
+        // Without the bootstrap the routines go after the code.
+        if opts.shared_routines() {
+            let mut translator = Box::new(Translator::new_with_opts(opts));
+            let mut factory = VariableFactory::new(b"shared_routines");
+            translator.save_shared_routines(&mut factory);
+            write_chunks(
+                &mut translator,
+                &mut code,
+                &mut buff,
+                binary_target,
+                &mut pointer,
+                &mut static_map,
+                &mut file_context,
+            );
+        }
+    };
+
+    let size = file_context.global_instruction_number();
+    if size > ROM_SIZE {
+        return Err(Error::RomOverflow(size));
+    }
+
+    for (label, idxs) in file_context.pointer_map.iter() {
+        let value = static_map
+            .get(label)
+            .ok_or_else(|| Error::UndefinedLabel(String::from_utf8_lossy(label).to_string()))?;
+        for idx in idxs {
+            let start = idx * BINARY_LINE;
+            code[start..start + value.len()].copy_from_slice(value.as_bytes());
+        }
+    }
+
+    let symbols = if binary_target && program_opts.symbols {
+        let mut symbols = SymbolTable::new();
+        for label in file_context.labels {
+            let undefined = || Error::UndefinedLabel(String::from_utf8_lossy(&label).to_string());
+            let value = static_map.get(&label).ok_or_else(undefined)?.clone();
+            let address = i16::from_str_radix(&value, 2).map_err(|_| undefined())?;
+
+            if symbols.static_map.insert(label.clone(), value).is_none() {
+                symbols.labels.push((label, address));
+            }
+        }
+        Some(symbols)
+    } else {
+        None
+    };
+
+    Ok(AsmOutput {
+        code,
+        symbols,
+        source_map: file_context.source_map,
+        link_report,
+    })
+}
+
+/// Functions missing in `kept` are skipped, `None` translates everything.
+#[allow(clippy::too_many_arguments)]
+async fn translate_input(
+    input: VmInput,
+    code: &mut Vec<u8>,
+    buff: &mut [u8],
+    opts: TranslateOpts,
+    binary_target: bool,
+    static_pointer: &mut i16,
+    static_map: &mut HashMap<Vec<u8>, String>,
+    file_pointer: &mut WriteFileContext,
+    kept: Option<&HashSet<Vec<u8>>>,
+) -> Result<(), Error> {
+    let path = input.path.clone();
+    let Some(src_file_name) = path.file_stem() else {
+        let message = format!("{}: no file name for the statics", path.display());
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, message).into());
+    };
+    let file_parse_comment = format!("Start parsing {}", src_file_name.to_string_lossy());
+    let mut factory = VariableFactory::new(src_file_name.as_bytes());
+    // The translator is too big to live in the future.
+    let mut translator = Box::new(Translator::new_with_opts(opts));
+
+    file_pointer.set_source_file(input.path.display().to_string(), input.source_map.clone());
+    let mut tokens = input.tokens().await?;
+
+    translator.save_instruction(Instruction::new_line());
+    translator.save_instruction(Instruction::new_line());
+    translator.save_instruction(Instruction::new_comment(file_parse_comment.as_bytes()));
+
+    let mut skip = false;
+
+    'outer: loop {
+        let space = translator.check_free_space();
+
+        for _i in 0..space {
+            if let Some(token) = tokens.next().await {
+                let token = token.map_err(|err| Error::Parse(path.clone(), err))?;
+                if let AsmInstructionPayload::Function(AsmFunctionInstruction::Definition(meta)) =
+                    &token.payload
+                {
+                    skip = kept.is_some_and(|kept| !kept.contains(&meta.name));
+                }
+                if !skip {
+                    translator.save_token(token);
+                }
+            } else {
+                translator.translate(&mut factory);
+                write_chunks(
+                    &mut translator,
+                    code,
+                    buff,
+                    binary_target,
+                    static_pointer,
+                    static_map,
+                    file_pointer,
+                );
+                break 'outer;
+            }
+        }
+
+        translator.translate(&mut factory);
+
+        write_chunks(
+            &mut translator,
+            code,
+            buff,
+            binary_target,
+            static_pointer,
+            static_map,
+            file_pointer,
+        );
+        translator.reset();
+    }
+
+    Ok(())
+}
+
+fn write_chunks(
+    translator: &mut Translator<'_>,
+    code: &mut Vec<u8>,
+    buff: &mut [u8],
+    binary_target: bool,
+    static_pointer: &mut i16,
+    static_map: &mut HashMap<Vec<u8>, String>,
+    file_pointer: &mut WriteFileContext,
+) {
+    loop {
+        let l = if binary_target {
+            translator.instructions_to_bytes(buff, 100, static_pointer, static_map, file_pointer)
+        } else {
+            translator.instructions_to_symbols_with_context(buff, 300, file_pointer)
+        };
+
+        if l == 0 {
+            break;
+        }
+
+        code.extend_from_slice(&buff[..l]);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::from_utf8;
+
+    use hack_executor::HackExecutor;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn translate_vm_test() {
+        let inputs = vec![
+            VmInput::new(
+                "Main.vm",
+                std::fs::read("./priv/programs/FibonacciElement/Main.vm").unwrap(),
+            ),
+            VmInput::new(
+                "Sys.vm",
+                std::fs::read("./priv/programs/FibonacciElement/Sys.vm").unwrap(),
+            ),
+        ];
+        let mut opts = ProgramOpts::new();
+        opts.binary = true;
+
+        let output = translate_vm(inputs, true, &opts).await.unwrap();
+        assert!(output.symbols.is_none());
+        assert_eq!(output.link_report.unwrap().kept.len(), 2);
+
+        let program = HackExecutor::parse_program(from_utf8(&output.code).unwrap()).unwrap();
+        let mut executor = HackExecutor::new(program);
+        executor.run_until_halt(100_000);
+        assert_eq!(executor.result(), 3);
+    }
+
+    #[tokio::test]
+    async fn shared_routines_link_test() {
+        let mut opts = ProgramOpts::new();
+        opts.binary = true;
+        opts.translate.set_shared_routines(true);
+
+        let inputs = ["Main.vm", "Sys.vm"].map(|name| {
+            let path = format!("./priv/programs/FibonacciElement/{}", name);
+            VmInput::new(name, std::fs::read(path).unwrap())
+        });
+        let output = translate_vm(inputs.into(), true, &opts).await.unwrap();
+
+        let program = HackExecutor::parse_program(from_utf8(&output.code).unwrap()).unwrap();
+        let mut executor = HackExecutor::new(program);
+        executor.run_until_halt(100_000);
+        assert_eq!(executor.result(), 3);
+
+        // The symbolic code defines every routine it jumps to.
+        let dir = Path::new("./priv/programs/FibonacciElement");
+        let out = std::env::temp_dir().join(format!(
+            "shared_routines_link_test_{}.asm",
+            std::process::id()
+        ));
+        opts.binary = false;
+        opts.library_dirs.clear();
+        translate(dir, &out, &opts).await.unwrap();
+        let asm = std::fs::read_to_string(&out).unwrap();
+        for label in ["$call", "$return", "$eq", "$gt", "$lt", "$halt"] {
+            assert!(asm.contains(&format!("({})", label)), "{}", label);
+        }
+        std::fs::remove_file(&out).unwrap();
+    }
+
+    #[tokio::test]
+    async fn instructions_input_test() {
+        let path = "./priv/programs/FibonacciElement/Main.vm";
+        let code = std::fs::read(path).unwrap();
+        let mut opts = ProgramOpts::new();
+        opts.binary = true;
+        opts.translate.set_comments(false);
+
+        // Only instructions are kept, so the code has one instruction a line.
+        let mut parser = VMParser::from_bytes(code.clone()).await.unwrap();
+        let mut instructions = Vec::new();
+        let mut lines = Vec::new();
+        while let Some(token) = parser.next_instruction().await {
+            let token = token.unwrap();
+            lines.push(format!("{}", token.payload));
+            instructions.push(token.payload);
+        }
+        let input = VmInput::new(path, lines.join("\n"));
+
+        let parsed = translate_vm(vec![input], false, &opts).await.unwrap();
+        let direct = translate_vm(
+            vec![VmInput::from_instructions(path, instructions, None)],
+            false,
+            &opts,
+        )
+        .await
+        .unwrap();
+        assert_eq!(direct.code, parsed.code);
+        assert_eq!(direct.source_map, parsed.source_map);
+    }
+
+    #[test]
+    fn default_output_test() {
+        let mut opts = ProgramOpts::new();
+        let dir = Path::new("./priv/programs/FibonacciElement");
+        assert_eq!(
+            opts.default_output(dir).unwrap(),
+            dir.join("FibonacciElement.asm")
+        );
+
+        opts.binary = true;
+        let cwd = std::env::current_dir().unwrap();
+        let name = format!("{}.hack", cwd.file_name().unwrap().to_str().unwrap());
+        assert_eq!(
+            opts.default_output(Path::new(".")).unwrap(),
+            Path::new(".").join(name)
+        );
+        assert_eq!(
+            opts.default_output(Path::new("Main.vm")).unwrap(),
+            PathBuf::from("Main.hack")
+        );
+        assert!(opts.default_output(Path::new("/")).is_err());
+    }
+
+    #[tokio::test]
+    async fn parse_error_test() {
+        let opts = ProgramOpts::new();
+        for (code, message) in [
+            (
+                "push constant 1\nbogus 1\n",
+                "Main.vm:2: unknown instruction `bogus`",
+            ),
+            (
+                "push constant 1 / 2\n",
+                "Main.vm:1: invalid comment start `/`",
+            ),
+            ("push nowhere 1\n", "Main.vm:1: unknown segment `nowhere`"),
+            (
+                "push constant x\n",
+                "Main.vm:1: expected a number in range 0..=32767 `x`",
+            ),
+            (
+                "call Main.f 99999\n",
+                "Main.vm:1: expected a number in range 0..=32767 `99999`",
+            ),
+            (
+                "function Main.main 0\npush",
+                "Main.vm:2: missing operand of `push`",
+            ),
+            ("pop constant 5\n", "Main.vm:1: cannot pop to `constant`"),
+            (
+                "push pointer 5\n",
+                "Main.vm:1: index out of segment `pointer 5`",
+            ),
+            ("pop temp 8\n", "Main.vm:1: index out of segment `temp 8`"),
+        ] {
+            let inputs = vec![VmInput::new("Main.vm", code)];
+            let err = translate_vm(inputs, false, &opts).await.unwrap_err();
+            assert_eq!(err.to_string(), message);
+        }
+
+        let err = VmInput::read(Path::new("nosuch")).await.unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidInput);
+    }
+
+    #[tokio::test]
+    async fn rom_overflow_test() {
+        let code = "push constant 1\n".repeat(ROM_SIZE / 4);
+        let mut opts = ProgramOpts::new();
+        opts.binary = true;
+        opts.symbols = true;
+
+        let err = translate_vm(vec![VmInput::new("Main.vm", code)], false, &opts)
+            .await
+            .unwrap_err();
+        assert!(
+            matches!(err, Error::RomOverflow(size) if size > ROM_SIZE),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn undefined_label_test() {
+        let inputs = || {
+            vec![VmInput::new(
+                "Main.vm",
+                "function Main.main 0\ngoto NOWHERE\n",
+            )]
+        };
+        let mut opts = ProgramOpts::new();
+        opts.binary = true;
+
+        let err = translate_vm(inputs(), false, &opts).await.unwrap_err();
+        assert!(matches!(err, Error::UndefinedLabel(_)), "{}", err);
+
+        // The symbolic code is left to the assembler.
+        opts.binary = false;
+        let output = translate_vm(inputs(), false, &opts).await.unwrap();
+        assert!(from_utf8(&output.code).unwrap().contains("NOWHERE"));
+    }
+}
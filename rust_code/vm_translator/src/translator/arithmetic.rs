@@ -3,10 +3,37 @@ use hack_macro::instruction;
 
 use vm_parser::AsmArithmeticInstruction;
 
-use super::{constants::POP_INSTRUCTIONS, model::Translator};
+use super::{
+    constants::{
+        EQ_ROUTINE, GT_ROUTINE, LT_ROUTINE, POP_INSTRUCTIONS, RETURN_FROM_R15, SAVE_RETURN_TO_R15,
+    },
+    model::Translator,
+};
 
 const POINT_STACK_VALUE: [Instruction<'static>; 2] = [instruction!(b"@SP"), instruction!(b"A=M-1")];
 
+/// Pops `y`, leaves `x - y` in `D` and `A` pointing to `x`, which is set to true.
+const COMPARE_ROUTINE_START: [Instruction<'static>; 6] = [
+    instruction!(b"@SP"),
+    instruction!(b"AM=M-1"),
+    instruction!(b"D=M"),
+    instruction!(b"A=A-1"),
+    instruction!(b"D=M-D"),
+    instruction!(b"M=-1"),
+];
+
+const COMPARE_ROUTINE_FALSE: [Instruction<'static>; 3] = [
+    instruction!(b"@SP"),
+    instruction!(b"A=M-1"),
+    instruction!(b"M=0"),
+];
+
+const COMPARE_ROUTINES: [(AsmArithmeticInstruction, &[u8]); 3] = [
+    (AsmArithmeticInstruction::Eq, EQ_ROUTINE),
+    (AsmArithmeticInstruction::Gt, GT_ROUTINE),
+    (AsmArithmeticInstruction::Lt, LT_ROUTINE),
+];
+
 pub fn translate_arithmetic_token<'a, 'b>(
     translator: &'a mut Translator<'b>,
     token: &'a AsmArithmeticInstruction,
@@ -41,6 +68,16 @@ pub fn translate_arithmetic_token<'a, 'b>(
             translator.save_link(&POINT_STACK_VALUE);
             translator.save_instruction(instruction!(b"M=D&M"));
         }
+        t if translator.shared_routines() => {
+            let (label_back, var_back, _, _) = factory.new_bool_variables();
+            translator.save_instruction(var_back);
+            translator.save_link(&SAVE_RETURN_TO_R15);
+            translator.save_instruction(Instruction::new_raw_var_label(
+                compare_routine_name(t).to_vec(),
+            ));
+            translator.save_instruction(instruction!(b"0;JMP"));
+            translator.save_instruction(label_back);
+        }
         t => {
             let (label_true, var_true, label_false, var_false) = factory.new_bool_variables();
             translator.save_link(&POP_INSTRUCTIONS);
@@ -60,6 +97,31 @@ pub fn translate_arithmetic_token<'a, 'b>(
     }
 }
 
+/// `eq`, `gt` and `lt` routines for `TranslateOpts::shared_routines`.
+pub fn save_compare_routines(translator: &mut Translator<'_>) {
+    for (token, name) in COMPARE_ROUTINES {
+        let mut end = name.to_vec();
+        end.extend_from_slice(b".end");
+
+        translator.save_instruction(Instruction::new_raw_label(name.to_vec()));
+        translator.save_link(&COMPARE_ROUTINE_START);
+        translator.save_instruction(Instruction::new_raw_var_label(end.clone()));
+        translator.save_instruction(jump_instruction_by_token(&token));
+        translator.save_link(&COMPARE_ROUTINE_FALSE);
+        translator.save_instruction(Instruction::new_raw_label(end));
+        translator.save_link(&RETURN_FROM_R15);
+    }
+}
+
+fn compare_routine_name(token: &AsmArithmeticInstruction) -> &'static [u8] {
+    match token {
+        AsmArithmeticInstruction::Eq => EQ_ROUTINE,
+        AsmArithmeticInstruction::Gt => GT_ROUTINE,
+        AsmArithmeticInstruction::Lt => LT_ROUTINE,
+        _ => unreachable!(),
+    }
+}
+
 fn jump_instruction_by_token(token: &AsmArithmeticInstruction) -> Instruction<'static> {
     match token {
         AsmArithmeticInstruction::Eq => instruction!(b"D;JEQ"),
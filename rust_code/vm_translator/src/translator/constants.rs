@@ -20,3 +20,24 @@ pub const PUSH_INSTRUCTIONS: [Instruction<'static>; 7] = [
     instruction!(b"@SP"),
     instruction!(b"M=M+1"),
 ];
+
+/// Labels of the routines shared by every call site with `TranslateOpts::shared_routines`,
+/// `$` can not start a Jack or VM name.
+pub const CALL_ROUTINE: &[u8] = b"$call";
+pub const RETURN_ROUTINE: &[u8] = b"$return";
+pub const EQ_ROUTINE: &[u8] = b"$eq";
+pub const GT_ROUTINE: &[u8] = b"$gt";
+pub const LT_ROUTINE: &[u8] = b"$lt";
+
+/// Routines return to the address a call site leaves in `R15`.
+pub const SAVE_RETURN_TO_R15: [Instruction<'static>; 3] = [
+    instruction!(b"D=A"),
+    instruction!(b"@R15"),
+    instruction!(b"M=D"),
+];
+
+pub const RETURN_FROM_R15: [Instruction<'static>; 3] = [
+    instruction!(b"@R15"),
+    instruction!(b"A=M"),
+    instruction!(b"0;JMP"),
+];
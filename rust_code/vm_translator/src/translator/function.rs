@@ -8,7 +8,7 @@ use vm_parser::{
 };
 
 use super::branch::translate_branch_token;
-use super::constants::{POP_INSTRUCTIONS, PUSH_INSTRUCTIONS};
+use super::constants::{CALL_ROUTINE, POP_INSTRUCTIONS, PUSH_INSTRUCTIONS, RETURN_ROUTINE};
 use super::memory::{segment_as_instruction, translate_memory_token};
 use super::Translator;
 
@@ -54,6 +54,27 @@ const SAVE_RETURN_TO_R14: [Instruction<'static>; 7] = [
     instruction!(b"M=D"),
 ];
 
+const SAVE_D_TO_R13: [Instruction<'static>; 2] = [instruction!(b"@R13"), instruction!(b"M=D")];
+
+const SAVE_D_TO_R14: [Instruction<'static>; 2] = [instruction!(b"@R14"), instruction!(b"M=D")];
+
+const SET_ARG_FROM_R14: [Instruction<'static>; 8] = [
+    instruction!(b"@SP"),
+    instruction!(b"D=M"),
+    instruction!(b"@5"),
+    instruction!(b"D=D-A"),
+    instruction!(b"@R14"),
+    instruction!(b"D=D-M"),
+    instruction!(b"@ARG"),
+    instruction!(b"M=D"),
+];
+
+const GOTO_R13: [Instruction<'static>; 3] = [
+    instruction!(b"@R13"),
+    instruction!(b"A=M"),
+    instruction!(b"0;JMP"),
+];
+
 const RETURN_FROM_R14: [Instruction<'static>; 3] = [
     instruction!(b"@R14"),
     instruction!(b"A=M"),
@@ -88,34 +109,71 @@ pub fn translate_function_token<'a, 'b>(
             }
             translator.save_instruction(instruction!(b"// Execute body"));
         }
-        AsmFunctionInstruction::Return => {
-            translator.save_instruction(instruction!(
-                b"// Save return address in case zero arguments"
-            ));
-            translator.save_link(&SAVE_RETURN_TO_R14);
-            translator.save_instruction(instruction!(b"// Push value to zero ARG"));
-            let memory_token = AsmMemoryInstruction {
-                segment: AsmMemoryInstructionSegment::Arg,
-                kind: AsmMemoryInstructionKind::Pop,
-                val: 0,
-            };
-            translate_memory_token(translator, &memory_token, factory);
-            translator.save_instruction(instruction!(b"// Move SP to restoring segments"));
-            translator.save_link(&MOVE_SP_TO_COLLER);
-            translator.save_link(&SAVE_PARENT_SP_TO_R13);
-            translator.save_instruction(instruction!(b"// Restoring coller segemnts"));
-            restore_context_elem(translator, AsmMemoryInstructionSegment::That);
-            restore_context_elem(translator, AsmMemoryInstructionSegment::This);
-            restore_context_elem(translator, AsmMemoryInstructionSegment::Arg);
-            restore_context_elem(translator, AsmMemoryInstructionSegment::Local);
-            translator.save_instruction(instruction!(b"// Move PC back"));
-            translator.save_link(&RESTORE_PARENT_SP_FROM_R13);
-            translator.save_link(&RETURN_FROM_R14);
-            translator.save_instruction(instruction!(b"// Return back to caller function"));
+        AsmFunctionInstruction::Return if translator.shared_routines() => {
+            translator.save_instruction(instruction!(b"// Return through the shared routine"));
+            translator.save_instruction(Instruction::new_raw_var_label(RETURN_ROUTINE.to_vec()));
+            translator.save_instruction(instruction!(b"0;JMP"));
         }
+        AsmFunctionInstruction::Return => return_to_caller(translator, factory),
     }
 }
 
+fn return_to_caller<'a, 'b>(
+    translator: &'a mut Translator<'b>,
+    factory: &'a mut VariableFactory<'b>,
+) {
+    translator.save_instruction(instruction!(
+        b"// Save return address in case zero arguments"
+    ));
+    translator.save_link(&SAVE_RETURN_TO_R14);
+    translator.save_instruction(instruction!(b"// Push value to zero ARG"));
+    let memory_token = AsmMemoryInstruction {
+        segment: AsmMemoryInstructionSegment::Arg,
+        kind: AsmMemoryInstructionKind::Pop,
+        val: 0,
+    };
+    translate_memory_token(translator, &memory_token, factory);
+    translator.save_instruction(instruction!(b"// Move SP to restoring segments"));
+    translator.save_link(&MOVE_SP_TO_COLLER);
+    translator.save_link(&SAVE_PARENT_SP_TO_R13);
+    translator.save_instruction(instruction!(b"// Restoring coller segemnts"));
+    restore_context_elem(translator, AsmMemoryInstructionSegment::That);
+    restore_context_elem(translator, AsmMemoryInstructionSegment::This);
+    restore_context_elem(translator, AsmMemoryInstructionSegment::Arg);
+    restore_context_elem(translator, AsmMemoryInstructionSegment::Local);
+    translator.save_instruction(instruction!(b"// Move PC back"));
+    translator.save_link(&RESTORE_PARENT_SP_FROM_R13);
+    translator.save_link(&RETURN_FROM_R14);
+    translator.save_instruction(instruction!(b"// Return back to caller function"));
+}
+
+/// `call` and `return` routines for `TranslateOpts::shared_routines`.
+pub fn save_call_routines<'a, 'b>(
+    translator: &'a mut Translator<'b>,
+    factory: &'a mut VariableFactory<'b>,
+) {
+    translator.save_instruction(Instruction::new_raw_label(CALL_ROUTINE.to_vec()));
+    translator.save_instruction(instruction!(
+        b"// Saving current segemnts, D is the return address"
+    ));
+    translator.save_link(&PUSH_INSTRUCTIONS);
+    push_context_elem(translator, AsmMemoryInstructionSegment::Local);
+    push_context_elem(translator, AsmMemoryInstructionSegment::Arg);
+    push_context_elem(translator, AsmMemoryInstructionSegment::This);
+    push_context_elem(translator, AsmMemoryInstructionSegment::That);
+    translator.save_instruction(instruction!(
+        b"// Set ARG pointer, R14 is the arguments count"
+    ));
+    translator.save_link(&SET_ARG_FROM_R14);
+    translator.save_instruction(instruction!(b"// Set LCL = SP"));
+    translator.save_link(&SET_SP_TO_LCL);
+    translator.save_instruction(instruction!(b"// Goto function body from R13"));
+    translator.save_link(&GOTO_R13);
+
+    translator.save_instruction(Instruction::new_raw_label(RETURN_ROUTINE.to_vec()));
+    return_to_caller(translator, factory);
+}
+
 fn call<'a, 'b>(
     translator: &'a mut Translator<'b>,
     call_id: &[u8],
@@ -140,6 +198,22 @@ fn call<'a, 'b>(
         name,
     };
 
+    if translator.shared_routines() {
+        translator.save_instruction(instruction!(b"// Call function through the shared routine"));
+        translator.save_instruction(Instruction::new_raw_var_label(function_name));
+        translator.save_instruction(instruction!(b"D=A"));
+        translator.save_link(&SAVE_D_TO_R13);
+        translator.save_instruction(Instruction::new_number(meta.args_count));
+        translator.save_instruction(instruction!(b"D=A"));
+        translator.save_link(&SAVE_D_TO_R14);
+        translator.save_instruction(Instruction::new_raw_var_label(caller_name.clone()));
+        translator.save_instruction(instruction!(b"D=A"));
+        translator.save_instruction(Instruction::new_raw_var_label(CALL_ROUTINE.to_vec()));
+        translator.save_instruction(instruction!(b"0;JMP"));
+        translate_branch_token(translator, &mut label_token, factory);
+        return;
+    }
+
     let mut goto_function_token = AsmBranchInstruction {
         kind: AsmBranchInstructionKind::Goto,
         name: function_name,
@@ -197,3 +271,49 @@ fn restore_context_elem<'a, 'b>(
     translator.save_instruction(segment_as_instruction(segement));
     translator.save_instruction(instruction!(b"M=D"));
 }
+
+#[cfg(test)]
+mod tests {
+    use hack_executor::{HackExecutor, HaltReason};
+
+    use crate::translator::programs::build;
+    use crate::translator::TranslateOpts;
+
+    async fn run(dir: &str, opts: TranslateOpts) -> (usize, HackExecutor) {
+        let program = build(dir, opts).await;
+        let size = program.len();
+        let mut executor = HackExecutor::new(program);
+        assert_eq!(executor.run_until_halt(100_000), HaltReason::Halted);
+        (size, executor)
+    }
+
+    #[tokio::test]
+    async fn shared_routines_program_test() {
+        let mut shared = TranslateOpts::new();
+        shared.set_shared_routines(true);
+        let mut shared_optimized = shared;
+        shared_optimized.set_optimize(true);
+
+        for dir in [
+            "./priv/programs/FibonacciElement",
+            "./priv/programs/Segments",
+        ] {
+            let (size, executor) = run(dir, TranslateOpts::new()).await;
+            for opts in [shared, shared_optimized] {
+                let (shared_size, shared_executor) = run(dir, opts).await;
+                assert!(shared_size < size, "{}: {} >= {}", dir, shared_size, size);
+
+                assert_eq!(shared_executor.result(), executor.result());
+                for addr in (0..5).chain(16..256).chain(3000..3012) {
+                    assert_eq!(
+                        shared_executor.read_memory(addr),
+                        executor.read_memory(addr),
+                        "{} RAM[{}]",
+                        dir,
+                        addr
+                    );
+                }
+            }
+        }
+    }
+}
@@ -19,12 +19,6 @@ pub fn translate_memory_token<'links, 'structs>(
     token: &'links AsmMemoryInstruction,
     factory: &'links mut VariableFactory<'structs>,
 ) {
-    if token.kind == AsmMemoryInstructionKind::Pop
-        && token.segment == AsmMemoryInstructionSegment::Const
-    {
-        panic!("Pop const commands are restricted");
-    }
-
     if token.segment == AsmMemoryInstructionSegment::Const {
         // TODO check if successfully saved
         translator.save_instruction(instruction!(b"// Set const value to D"));
@@ -4,5 +4,8 @@ mod constants;
 mod function;
 mod memory;
 mod model;
+mod optimizer;
+#[cfg(test)]
+mod programs;
 
 pub use model::{TranslateOpts, Translator};
@@ -1,7 +1,4 @@
-use std::{
-    collections::HashMap,
-    mem::{self, MaybeUninit},
-};
+use std::collections::HashMap;
 
 use hack_instructions::*;
 
@@ -13,12 +10,16 @@ use vm_parser::{AsmFunctionInstruction, AsmInstructionPayload, FunctionMetadata}
 use crate::context::WriteFileContext;
 
 use super::{
-    arithmetic::translate_arithmetic_token, branch::translate_branch_token,
-    function::translate_function_token, memory::translate_memory_token,
+    arithmetic::{save_compare_routines, translate_arithmetic_token},
+    branch::translate_branch_token,
+    function::{save_call_routines, translate_function_token},
+    memory::translate_memory_token,
+    optimizer::{optimize, Entry},
 };
 
 const TRANSLATOR_INSTRUCTIONS_CAPACITY: usize = 2048;
 const TRANSLATOR_TOKEN_CAPACITY: usize = 64;
+const HALT_LABEL: &[u8] = b"$halt";
 const INIT_SP: [Instruction<'static>; 4] = [
     instruction!(b"@256"),
     instruction!(b"D=A"),
@@ -29,17 +30,46 @@ const INIT_SP: [Instruction<'static>; 4] = [
 #[derive(Clone, Copy)]
 pub struct TranslateOpts {
     comments: bool,
+    optimize: bool,
+    shared_routines: bool,
 }
 
 impl TranslateOpts {
     pub fn new() -> Self {
-        Self { comments: true }
+        Self {
+            comments: true,
+            optimize: false,
+            shared_routines: false,
+        }
     }
 
     pub fn set_comments(&mut self, v: bool) -> &mut Self {
         self.comments = v;
         self
     }
+
+    /// Runs the peephole optimizer over every translated batch.
+    pub fn set_optimize(&mut self, v: bool) -> &mut Self {
+        self.optimize = v;
+        self
+    }
+
+    /// `call`, `return`, `eq`, `gt` and `lt` jump to one routine each instead of
+    /// being inlined, a smaller ROM for more cycles.
+    pub fn set_shared_routines(&mut self, v: bool) -> &mut Self {
+        self.shared_routines = v;
+        self
+    }
+
+    pub fn shared_routines(&self) -> bool {
+        self.shared_routines
+    }
+}
+
+impl Default for TranslateOpts {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[derive(Debug)]
@@ -56,33 +86,39 @@ pub struct Translator<'a> {
     cursor: usize,
     cursor_link: usize,
     cursor_down: usize,
+    /// `(instruction slot, .vm line)` for every token, ordered by slot.
+    lines: Vec<(usize, usize)>,
+    lines_cursor: usize,
+    /// `.vm` line of the last instruction taken by `next_instruction`.
+    line: Option<usize>,
+    /// Instructions taken from the buffer and optimized, used with `TranslateOpts::optimize`.
+    optimized: Vec<Entry<'a>>,
+    optimized_cursor: usize,
     translate_opts: TranslateOpts,
 }
 
+impl Default for Translator<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl<'a> Translator<'a> {
-    #[allow(dead_code)]
     pub fn new() -> Self {
         Self::new_with_opts(TranslateOpts::new())
     }
 
     pub fn new_with_opts(opts: TranslateOpts) -> Self {
-        let instructions = {
-            let x: [MaybeUninit<InstructionOrLink<'_>>; TRANSLATOR_INSTRUCTIONS_CAPACITY] =
-                unsafe { MaybeUninit::uninit().assume_init() };
-            unsafe {
-                mem::transmute::<_, [InstructionOrLink<'_>; TRANSLATOR_INSTRUCTIONS_CAPACITY]>(x)
-            }
-        };
-
-        let tokens = {
-            let x: [MaybeUninit<FileContext<AsmInstructionPayload>>; TRANSLATOR_TOKEN_CAPACITY] =
-                unsafe { MaybeUninit::uninit().assume_init() };
-            unsafe {
-                mem::transmute::<_, [FileContext<AsmInstructionPayload>; TRANSLATOR_TOKEN_CAPACITY]>(
-                    x,
-                )
-            }
-        };
+        // Slots are assigned, not written, so they have to hold something to drop.
+        let instructions = std::array::from_fn(|_| InstructionOrLink::L(&[]));
+        let tokens = std::array::from_fn(|_| {
+            FileContext::new(
+                AsmInstructionPayload::Function(AsmFunctionInstruction::Return),
+                0,
+                None,
+                None,
+            )
+        });
 
         Self {
             instructions,
@@ -92,6 +128,11 @@ impl<'a> Translator<'a> {
             tokens_cursor_down: 0,
             cursor_link: 0,
             cursor_down: 0,
+            lines: Vec::new(),
+            lines_cursor: 0,
+            line: None,
+            optimized: Vec::new(),
+            optimized_cursor: 0,
             translate_opts: opts,
         }
     }
@@ -99,10 +140,49 @@ impl<'a> Translator<'a> {
     pub fn reset(&mut self) {
         self.cursor = 0;
         self.cursor_down = 0;
+        self.lines.clear();
+        self.lines_cursor = 0;
+        self.optimized.clear();
+        self.optimized_cursor = 0;
+    }
+
+    fn next_instruction(&mut self) -> Option<&Instruction<'a>> {
+        if !self.translate_opts.optimize {
+            return self.next_buffered_instruction();
+        }
+
+        if self.cursor_down < self.cursor {
+            self.optimize_buffer();
+        }
+
+        let (instruction, line) = self.optimized.get(self.optimized_cursor)?;
+        self.optimized_cursor += 1;
+        self.line = *line;
+        Some(instruction)
+    }
+
+    fn optimize_buffer(&mut self) {
+        self.optimized.drain(..self.optimized_cursor);
+        self.optimized_cursor = 0;
+
+        let mut entries = Vec::new();
+        while let Some(instruction) = self.next_buffered_instruction().cloned() {
+            entries.push((instruction, self.line));
+        }
+        optimize(&mut entries);
+        self.optimized.extend(entries);
     }
 
-    fn next_instruction(&mut self) -> Option<&Instruction<'_>> {
+    fn next_buffered_instruction(&mut self) -> Option<&Instruction<'a>> {
         while self.cursor_down < self.cursor {
+            while let Some((slot, line)) = self.lines.get(self.lines_cursor) {
+                if *slot > self.cursor_down {
+                    break;
+                }
+                self.line = Some(*line);
+                self.lines_cursor += 1;
+            }
+
             let res = match &self.instructions[self.cursor_down] {
                 InstructionOrLink::I(i) => {
                     self.cursor_down += 1;
@@ -129,11 +209,47 @@ impl<'a> Translator<'a> {
         None
     }
 
+    #[allow(dead_code)]
     pub fn instructions_to_symbols(&mut self, buff: &mut [u8], chunk: usize) -> usize {
+        self.write_symbols_chunk(buff, chunk, None)
+    }
+
+    /// The same as `instructions_to_symbols`, but also counts ROM addresses
+    /// for the source map.
+    pub fn instructions_to_symbols_with_context(
+        &mut self,
+        buff: &mut [u8],
+        chunk: usize,
+        file_context: &mut WriteFileContext,
+    ) -> usize {
+        self.write_symbols_chunk(buff, chunk, Some(file_context))
+    }
+
+    fn write_symbols_chunk(
+        &mut self,
+        buff: &mut [u8],
+        chunk: usize,
+        mut file_context: Option<&mut WriteFileContext>,
+    ) -> usize {
         let mut res = 0;
         for _idx in 0..chunk {
             res += if let Some(i) = self.next_instruction() {
-                i.write_symbols(&mut buff[res..])
+                let in_rom = !matches!(
+                    i,
+                    Instruction::Helper(
+                        HelperInstruction::Comment(_)
+                            | HelperInstruction::RawLabel(_)
+                            | HelperInstruction::Label(_)
+                    )
+                );
+                let l = i.write_symbols(&mut buff[res..]);
+
+                if let (true, Some(file_context)) = (in_rom, file_context.as_mut()) {
+                    file_context.set_source_line(self.line);
+                    file_context.incr();
+                }
+
+                l
             } else {
                 break;
             };
@@ -164,7 +280,21 @@ impl<'a> Translator<'a> {
                     file_context.set_intruction(val_to_save)
                 }
 
+                match i {
+                    Instruction::Helper(HelperInstruction::RawLabel(label)) => {
+                        file_context.set_label(label.clone())
+                    }
+                    Instruction::Helper(HelperInstruction::Label(label)) => {
+                        let mut v = vec![0; label.prefix_len + label.name_len + 8];
+                        let l = label.write_symbols(&mut v);
+                        v.truncate(l);
+                        file_context.set_label(v)
+                    }
+                    _ => (),
+                }
+
                 if l != 0 {
+                    file_context.set_source_line(self.line);
                     file_context.incr();
                     buff[res + l] = b'\n';
                     l += 1;
@@ -185,6 +315,9 @@ impl<'a> Translator<'a> {
                 as *mut FileContext<AsmInstructionPayload>;
             let token = unsafe { &mut *raw_token };
             self.tokens_cursor_down += 1;
+            if let Some(span) = token.span {
+                self.lines.push((self.cursor, span.line));
+            }
             self.run_for_token(token, factory)
         }
     }
@@ -236,9 +369,27 @@ impl<'a> Translator<'a> {
         let context = FileContext::new(payload, 0, None, None);
         self.save_token(context);
         self.translate(factory);
+        if self.shared_routines() {
+            self.save_shared_routines(factory);
+        }
         self.save_instruction(instruction!(b"// Finish init"));
     }
 
+    pub fn shared_routines(&self) -> bool {
+        self.translate_opts.shared_routines
+    }
+
+    /// The code `TranslateOpts::shared_routines` jumps to, behind a halt loop
+    /// so that execution does not fall into it.
+    pub fn save_shared_routines(&mut self, factory: &mut VariableFactory<'a>) {
+        self.save_instruction(instruction!(b"// Shared routines"));
+        self.save_instruction(Instruction::new_raw_label(HALT_LABEL.to_vec()));
+        self.save_instruction(Instruction::new_raw_var_label(HALT_LABEL.to_vec()));
+        self.save_instruction(instruction!(b"0;JMP"));
+        save_compare_routines(self);
+        save_call_routines(self, factory);
+    }
+
     pub fn save_instruction(&mut self, i: Instruction<'a>) -> bool {
         if !self.instruction_is_needed(&i) {
             return true;
@@ -279,6 +430,8 @@ mod tests {
         AsmMemoryInstructionSegment,
     };
 
+    use file_context::{FileSpan, SourceLocation};
+
     use super::*;
 
     #[test]
@@ -431,6 +584,43 @@ mod tests {
         assert!(buff[..l] == file_buff[..l2]);
     }
 
+    #[test]
+    fn source_map_test() {
+        let mut factory = VariableFactory::new(b"AnyFile");
+        let mut file_context = WriteFileContext::new();
+        file_context.set_source_file("AnyFile.vm".to_string(), None);
+
+        let push = AsmInstructionPayload::Memory(AsmMemoryInstruction {
+            kind: AsmMemoryInstructionKind::Push,
+            segment: AsmMemoryInstructionSegment::Const,
+            val: 7,
+        });
+        let add = AsmInstructionPayload::Arithmetic(AsmArithmeticInstruction::Add);
+
+        let mut t = Translator::new();
+        t.save_token(FileContext::new(push, 1, None, Some(FileSpan::new(0, 0))));
+        t.save_token(FileContext::new(add, 2, None, Some(FileSpan::new(2, 0))));
+        t.translate(&mut factory);
+
+        let mut buff = [0u8; 1024];
+        let l = t.instructions_to_bytes(
+            &mut buff,
+            100,
+            &mut 16,
+            &mut HashMap::new(),
+            &mut file_context,
+        );
+        assert_eq!(l, 17 * file_context.global_instruction_number());
+
+        assert_eq!(
+            file_context.source_map.entries(),
+            &[
+                (0, vec![SourceLocation::new("AnyFile.vm", 0)]),
+                (7, vec![SourceLocation::new("AnyFile.vm", 2)]),
+            ]
+        );
+    }
+
     fn new_asm_instruction(payload: AsmInstructionPayload) -> FileContext<AsmInstructionPayload> {
         FileContext::new(payload, 0, None, None)
     }
@@ -0,0 +1,299 @@
+use hack_instructions::*;
+use symbolic::SymbolicElem;
+
+const SYMBOLS_CAPACITY: usize = 512;
+
+/// The `PUSH_INSTRUCTIONS` and `POP_INSTRUCTIONS` templates without comments.
+const PUSH_POP: [&[u8]; 10] = [
+    b"@SP", b"A=M", b"M=D", b"@SP", b"M=M+1", b"@SP", b"M=M-1", b"@SP", b"A=M", b"D=M",
+];
+
+/// An instruction of the stream and the `.vm` line it was translated from.
+pub type Entry<'a> = (Instruction<'a>, Option<usize>);
+
+/// Peephole rewrites over one translated batch. Labels are jump targets,
+/// nothing is assumed about the machine state after them.
+///
+/// * a push right before a pop leaves `D` as it is, the pair is dropped when
+///   `A` is loaded again after it;
+/// * `@X` is dropped when `A` already holds `X`;
+/// * stores to `D` or `M` overwritten before any read are dropped.
+pub fn optimize(entries: &mut Vec<Entry<'_>>) {
+    while collapse_push_pop(entries) {}
+    remove_a_reloads(entries);
+    remove_dead_stores(entries);
+}
+
+fn collapse_push_pop(entries: &mut Vec<Entry<'_>>) -> bool {
+    let code = code_indexes(entries);
+    let mut removed = vec![false; entries.len()];
+    let mut changed = false;
+
+    let mut idx = 0;
+    while idx + PUSH_POP.len() < code.len() {
+        let window = &code[idx..=idx + PUSH_POP.len()];
+        let matched = window
+            .iter()
+            .zip(PUSH_POP)
+            .all(|(entry, pattern)| symbols(&entries[*entry].0) == pattern);
+
+        if matched && is_a_instruction(&entries[window[PUSH_POP.len()]].0) {
+            // Comments inside the pair go with it.
+            removed[window[0]..window[PUSH_POP.len()]].fill(true);
+            changed = true;
+            idx += PUSH_POP.len();
+        } else {
+            idx += 1;
+        }
+    }
+
+    retain(entries, &removed);
+    changed
+}
+
+fn remove_a_reloads(entries: &mut Vec<Entry<'_>>) {
+    let mut removed = vec![false; entries.len()];
+    let mut loaded: Option<Vec<u8>> = None;
+
+    for (idx, (instruction, _)) in entries.iter().enumerate() {
+        match instruction {
+            Instruction::Helper(HelperInstruction::Comment(_)) => (),
+            Instruction::C(c) if writes(&c.dest, b'A') => loaded = None,
+            Instruction::C(_) => (),
+            i if is_a_instruction(i) => {
+                let text = symbols(i);
+                if loaded.as_ref() == Some(&text) {
+                    removed[idx] = true;
+                } else {
+                    loaded = Some(text);
+                }
+            }
+            _ => loaded = None,
+        }
+    }
+
+    retain(entries, &removed);
+}
+
+fn remove_dead_stores(entries: &mut Vec<Entry<'_>>) {
+    let code = code_indexes(entries);
+    let mut removed = vec![false; entries.len()];
+
+    for (idx, entry) in code.iter().enumerate() {
+        let Instruction::C(c) = &entries[*entry].0 else {
+            continue;
+        };
+        if !matches!(c.jump, CInstructionJump::NOJMP) {
+            continue;
+        }
+
+        let register = match c.dest {
+            CInstructionDest::D => b'D',
+            CInstructionDest::M => b'M',
+            _ => continue,
+        };
+
+        let rest = code[idx + 1..].iter().map(|entry| &entries[*entry].0);
+        removed[*entry] = is_overwritten(register, rest);
+    }
+
+    retain(entries, &removed);
+}
+
+/// `true` when the register is written before anything reads it. `M` is
+/// only followed while `A` stays the same, jumps and labels end the search.
+fn is_overwritten<'b>(register: u8, mut rest: impl Iterator<Item = &'b Instruction<'b>>) -> bool {
+    rest.find_map(|instruction| match instruction {
+        Instruction::C(c) => {
+            if reads(&c.expression, register) || !matches!(c.jump, CInstructionJump::NOJMP) {
+                Some(false)
+            } else if writes(&c.dest, register) {
+                Some(true)
+            } else if register == b'M' && writes(&c.dest, b'A') {
+                Some(false)
+            } else {
+                None
+            }
+        }
+        i if is_a_instruction(i) => (register == b'M').then_some(false),
+        _ => Some(false),
+    })
+    .unwrap_or(false)
+}
+
+fn code_indexes(entries: &[Entry<'_>]) -> Vec<usize> {
+    entries
+        .iter()
+        .enumerate()
+        .filter(|(_, (i, _))| !matches!(i, Instruction::Helper(HelperInstruction::Comment(_))))
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+fn retain(entries: &mut Vec<Entry<'_>>, removed: &[bool]) {
+    let mut idx = 0;
+    entries.retain(|_| {
+        idx += 1;
+        !removed[idx - 1]
+    });
+}
+
+fn is_a_instruction(i: &Instruction<'_>) -> bool {
+    matches!(
+        i,
+        Instruction::A(_)
+            | Instruction::Helper(
+                HelperInstruction::RawVarLabel(_) | HelperInstruction::LabelVariable(_)
+            )
+    )
+}
+
+fn symbols(i: &Instruction<'_>) -> Vec<u8> {
+    let mut buff = [0u8; SYMBOLS_CAPACITY];
+    let l = i.write_symbols(&mut buff);
+    buff[..l].to_vec()
+}
+
+fn reads(expression: &CInstructionExpression, register: u8) -> bool {
+    let mut buff = [0u8; 4];
+    let l = expression.write_symbols(&mut buff);
+    buff[..l].contains(&register)
+}
+
+fn writes(dest: &CInstructionDest, register: u8) -> bool {
+    let mut buff = [0u8; 4];
+    let l = dest.write_symbols(&mut buff);
+    buff[..l].contains(&register)
+}
+
+#[cfg(test)]
+mod tests {
+    use hack_executor::{HackExecutor, HaltReason};
+    use hack_macro::instruction;
+
+    use crate::translator::constants::{POP_INSTRUCTIONS, PUSH_INSTRUCTIONS};
+    use crate::translator::programs::build;
+    use crate::translator::TranslateOpts;
+
+    use super::*;
+
+    /// Code left after the optimizer, comments are skipped.
+    fn optimized(instructions: Vec<Instruction<'static>>) -> Vec<String> {
+        let mut entries = instructions.into_iter().map(|i| (i, None)).collect();
+        optimize(&mut entries);
+        code_indexes(&entries)
+            .into_iter()
+            .map(|idx| String::from_utf8(symbols(&entries[idx].0)).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn push_pop_test() {
+        let mut instructions = vec![instruction!(b"@7"), instruction!(b"D=A")];
+        instructions.extend(PUSH_INSTRUCTIONS);
+        instructions.extend(POP_INSTRUCTIONS);
+        instructions.extend([instruction!(b"@THIS"), instruction!(b"M=D")]);
+
+        assert_eq!(
+            optimized(instructions.clone()),
+            vec!["@7", "D=A", "@THIS", "M=D"]
+        );
+
+        // `A` after the pair is still used, only the second `@SP` of the pop goes.
+        instructions.truncate(instructions.len() - 2);
+        instructions.push(instruction!(b"M=D"));
+        assert_eq!(optimized(instructions).len(), 11);
+    }
+
+    #[test]
+    fn a_reload_test() {
+        let instructions = vec![
+            instruction!(b"@SP"),
+            instruction!(b"M=M-1"),
+            instruction!(b"@SP"),
+            instruction!(b"A=M"),
+            instruction!(b"@SP"),
+            Instruction::new_raw_label(b"LOOP".to_vec()),
+            instruction!(b"@SP"),
+            instruction!(b"0;JMP"),
+        ];
+
+        assert_eq!(
+            optimized(instructions),
+            vec!["@SP", "M=M-1", "A=M", "@SP", "(LOOP)", "@SP", "0;JMP",]
+        );
+    }
+
+    #[test]
+    fn dead_store_test() {
+        let instructions = vec![
+            instruction!(b"@R13"),
+            instruction!(b"D=A"),
+            instruction!(b"M=D"),
+            instruction!(b"D=M"),
+            instruction!(b"M=0"),
+            instruction!(b"M=-1"),
+            instruction!(b"D=0"),
+            instruction!(b"D;JEQ"),
+        ];
+
+        assert_eq!(
+            optimized(instructions),
+            vec!["@R13", "D=A", "M=D", "M=-1", "D=0", "D;JEQ",]
+        );
+    }
+
+    /// Runs both programs to the end and compares the RAM that means something:
+    /// pointers, temp, statics, the top of the stack and the given ranges.
+    async fn assert_same_run(dir: &str, ranges: &[std::ops::Range<i16>]) {
+        let mut opts = TranslateOpts::new();
+        let program = build(dir, opts).await;
+        let optimized_program = build(dir, *opts.set_optimize(true)).await;
+        assert!(optimized_program.len() < program.len());
+
+        let mut executor = HackExecutor::new(program);
+        let mut optimized = HackExecutor::new(optimized_program);
+        assert_eq!(executor.run_until_halt(100_000), HaltReason::Halted);
+        assert_eq!(optimized.run_until_halt(100_000), HaltReason::Halted);
+        assert!(optimized.cycles() < executor.cycles());
+
+        // Saved return addresses differ, the code is shorter.
+        assert_eq!(optimized.result(), executor.result());
+        let mut ranges = ranges.to_vec();
+        ranges.extend([0..13, 16..256]);
+        for addr in ranges.into_iter().flatten() {
+            assert_eq!(
+                optimized.read_memory(addr),
+                executor.read_memory(addr),
+                "RAM[{}]",
+                addr
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn fibonacci_program_test() {
+        assert_same_run("./priv/programs/FibonacciElement", &[]).await;
+    }
+
+    #[tokio::test]
+    async fn segments_program_test() {
+        assert_same_run("./priv/programs/Segments", &[3000..3003, 3010..3012]).await;
+
+        let mut executor = HackExecutor::new(
+            build(
+                "./priv/programs/Segments",
+                *TranslateOpts::new().set_optimize(true),
+            )
+            .await,
+        );
+        executor.run_until_halt(100_000);
+        // static 0..=2 are the first variables
+        assert_eq!(executor.read_memory(16), 0);
+        assert_eq!(executor.read_memory(17), -1);
+        assert_eq!(executor.read_memory(18), 10);
+        assert_eq!(executor.read_memory(8), 0);
+        assert_eq!(executor.read_memory(3002), -5);
+        assert_eq!(executor.read_memory(3011), -2);
+    }
+}
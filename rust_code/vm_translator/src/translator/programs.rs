@@ -0,0 +1,68 @@
+//! Whole programs translated the same way `main` does, to run them in tests.
+
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::str::from_utf8;
+
+use hack_executor::HackExecutor;
+use hack_instructions::VariableFactory;
+use vm_parser::VMParser;
+
+use crate::translator::{TranslateOpts, Translator};
+
+fn drain(translator: &mut Translator<'_>, asm: &mut Vec<u8>) {
+    let mut buff = [0u8; 4096];
+    loop {
+        let l = translator.instructions_to_symbols(&mut buff, 50);
+        if l == 0 {
+            break;
+        }
+        asm.extend_from_slice(&buff[..l]);
+    }
+}
+
+/// Translates the bootstrap and every `.vm` file of the directory, the same as `main`.
+pub async fn build(dir: &str, opts: TranslateOpts) -> Vec<i16> {
+    let mut asm = Vec::new();
+
+    let mut paths: Vec<_> = fs::read_dir(dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    paths.sort();
+    let stems: Vec<_> = paths
+        .iter()
+        .map(|path| path.file_stem().unwrap().as_bytes().to_vec())
+        .collect();
+
+    // The translator is too big to live in the future.
+    let mut translator = Box::new(Translator::new_with_opts(opts));
+    let mut factory = VariableFactory::new(b"initial_call");
+    translator.init_translator(&mut factory);
+    drain(&mut translator, &mut asm);
+    translator.reset();
+
+    for (path, stem) in paths.iter().zip(&stems) {
+        let mut parser = VMParser::new(path.to_str().unwrap()).await.unwrap();
+        let mut factory = VariableFactory::new(stem);
+
+        let mut done = false;
+        while !done {
+            for _ in 0..translator.check_free_space() {
+                match parser.next_instruction().await {
+                    Some(token) => translator.save_token(token.unwrap()),
+                    None => {
+                        done = true;
+                        break;
+                    }
+                }
+            }
+            translator.translate(&mut factory);
+            drain(&mut translator, &mut asm);
+            translator.reset();
+        }
+    }
+
+    let hack = hack_assembler::assemble(&asm).unwrap();
+    HackExecutor::parse_program(from_utf8(&hack).unwrap()).unwrap()
+}